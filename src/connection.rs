@@ -0,0 +1,142 @@
+use crate::database::result::{Output, ResultSet};
+use crate::database::session::Session;
+use crate::system::commands::parser::execute;
+use crate::system::commands::system::database_statement;
+use crate::system::errors::Errors;
+
+/// A handle to the engine for programs embedding it. Databases are the files of
+/// `sql_files` under the working directory; connections of one process to the same
+/// database share it, each one runs its own transactions.
+#[derive(Default)]
+pub struct Connection {
+    session: Session,
+}
+
+impl Connection {
+    /// A connection without a database, `CREATE DATABASE` and `USE` pick one.
+    pub fn new() -> Connection {
+        Connection::default()
+    }
+
+    pub fn open(db_name: &str) -> Result<Connection, Errors> {
+        let mut conn = Connection::new();
+        conn.execute(&format!("USE {}", db_name))?;
+        Ok(conn)
+    }
+
+    /// Name of the database in use, empty before one is opened.
+    pub fn database(&self) -> &str {
+        self.session.db_name()
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.session.in_transaction()
+    }
+
+    /// Runs a statement and returns the number of rows it inserted, updated, deleted or
+    /// selected.
+    pub fn execute(&mut self, sql: &str) -> Result<u64, Errors> {
+        let count = match self.run(sql)? {
+            Output::Rows(result)
+            | Output::Schema {
+                columns: result, ..
+            } => result.len(),
+            Output::Affected(affected) => affected,
+            Output::Message(_) | Output::Done => 0,
+        };
+        Ok(count as u64)
+    }
+
+    /// Runs a query and returns its rows, a statement without rows gives an empty result.
+    pub fn query(&mut self, sql: &str) -> Result<ResultSet, Errors> {
+        match self.run(sql)? {
+            Output::Rows(result)
+            | Output::Schema {
+                columns: result, ..
+            } => Ok(result),
+            _ => Ok(ResultSet::default()),
+        }
+    }
+
+    fn run(&mut self, sql: &str) -> Result<Output, Errors> {
+        let sql = sql.trim().trim_end_matches(';').trim();
+        match database_statement(sql, &mut self.session) {
+            Some(result) => result.map(|_| Output::Done),
+            None => execute(sql.to_string(), &mut self.session),
+        }
+    }
+}
+
+#[test]
+fn test_connection_returns_typed_rows() {
+    use crate::database::base::Value;
+    use crate::system::utils::TempDb;
+
+    let temp = TempDb::new("connection");
+    let db_name = temp.name.as_str();
+    let mut conn = Connection::new();
+    conn.execute(&format!("USE {}", db_name)).unwrap();
+    assert_eq!(conn.database(), db_name);
+    conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT, score FLOAT, ok BOOLEAN)")
+        .unwrap();
+    let inserted = conn
+        .execute(
+            "INSERT INTO t (id, name, score, ok) VALUES (1, 'a', 1.5, true), (2, 'b', 2.5, false)",
+        )
+        .unwrap();
+    assert_eq!(inserted, 2);
+    conn.execute("INSERT INTO t (id) VALUES (3)").unwrap();
+
+    let result = conn
+        .query("SELECT id, name, score, ok FROM t WHERE id = 1")
+        .unwrap();
+    assert_eq!(result.columns, vec!["id", "name", "score", "ok"]);
+    assert_eq!(
+        result.rows,
+        vec![vec![
+            Value::Int(1),
+            Value::Str("a".to_string()),
+            Value::Float(1.5),
+            Value::Bool(true)
+        ]]
+    );
+    let result = conn.query("SELECT name FROM t WHERE id = 3").unwrap();
+    assert_eq!(result.rows, vec![vec![Value::Null]]);
+    let ids = |result: ResultSet| {
+        result
+            .rows
+            .into_iter()
+            .map(|row| row[0].clone())
+            .collect::<Vec<Value>>()
+    };
+    let result = conn.query("SELECT id FROM t ORDER BY name DESC").unwrap();
+    assert_eq!(
+        ids(result),
+        vec![Value::Int(3), Value::Int(2), Value::Int(1)]
+    );
+    let result = conn
+        .query("SELECT id, score FROM t ORDER BY 2 NULLS FIRST, t.id DESC")
+        .unwrap();
+    assert_eq!(
+        ids(result),
+        vec![Value::Int(3), Value::Int(1), Value::Int(2)]
+    );
+    let result = conn
+        .query("SELECT id FROM t ORDER BY id DESC LIMIT 1 OFFSET 1")
+        .unwrap();
+    assert_eq!(ids(result), vec![Value::Int(2)]);
+    let result = conn
+        .query("SELECT id FROM t OFFSET 2 ROWS FETCH FIRST 5 ROWS ONLY")
+        .unwrap();
+    assert_eq!(ids(result), vec![Value::Int(3)]);
+    assert!(matches!(
+        conn.query("SELECT id FROM t ORDER BY 2"),
+        Err(Errors::InvalidOrderPosition(_))
+    ));
+    assert!(matches!(
+        conn.execute("INSERT INTO t (id) VALUES (1)"),
+        Err(Errors::PrimaryKeyExisted(_))
+    ));
+    assert_eq!(conn.execute("DELETE FROM t WHERE id = 2").unwrap(), 1);
+    assert_eq!(conn.execute("SELECT id FROM t").unwrap(), 2);
+}
@@ -0,0 +1,220 @@
+use crate::database::base::{ColumnAttr, DataType, Value};
+use crate::database::table::{Rows, Table};
+use crate::parser::aggregate::{AggFunc, Aggregate};
+use crate::parser::select::Projection;
+use crate::system::errors::Errors;
+use std::collections::HashMap;
+
+/// Groups `row_ixs` of `rows` by the `group_by` columns into a table with one row per
+/// group, holding the group's columns followed by the `aggregates`. Without `group_by`
+/// all the rows make one group, even when there are none.
+pub fn group_rows<R: Rows + ?Sized>(
+    rows: &R,
+    group_by: &[String],
+    aggregates: &[Aggregate],
+    projection: &[Projection],
+    row_ixs: &[usize],
+) -> Result<Table, Errors> {
+    let mut keys = vec![];
+    for name in group_by {
+        match rows.resolve_column(name) {
+            Some(col) => keys.push(col.to_string()),
+            None => return Err(Errors::ColumnNotExisted(name.to_string())),
+        }
+    }
+    for proj in projection {
+        let expr = match proj {
+            Projection::Expr { expr, .. } => expr,
+            Projection::Wildcard(_) => return Err(Errors::ColumnNotGrouped("*".to_string())),
+        };
+        let mut columns = vec![];
+        expr.columns(&mut columns);
+        for name in columns {
+            if aggregates.iter().any(|a| a.name == name) {
+                continue;
+            }
+            match rows.resolve_column(name) {
+                Some(col) if keys.iter().any(|k| k == col) => {}
+                Some(_) => return Err(Errors::ColumnNotGrouped(name.to_string())),
+                None => return Err(Errors::ColumnNotExisted(name.to_string())),
+            }
+        }
+    }
+
+    let mut grouped = Table::derived(rows.name());
+    for key in &keys {
+        let attr = rows.columns().iter().find(|c| c.name == *key).unwrap();
+        grouped.add_column(attr.clone());
+    }
+    let mut args = vec![];
+    for aggregate in aggregates {
+        let arg = match &aggregate.column {
+            None => None,
+            Some(name) => match (rows.resolve_column(name), rows.column_type(name)) {
+                (Some(col), Some(datatype)) => Some((col, datatype)),
+                _ => return Err(Errors::ColumnNotExisted(name.to_string())),
+            },
+        };
+        grouped.add_column(ColumnAttr {
+            name: aggregate.name.to_string(),
+            datatype: output_type(aggregate.func, arg.as_ref().map(|(_, t)| t))?,
+            is_pk: false,
+            is_nullable: true,
+            default: None,
+        });
+        args.push(arg);
+    }
+
+    let mut groups: Vec<(Vec<Value>, Vec<usize>)> = vec![];
+    let mut group_ix: HashMap<Vec<Option<String>>, usize> = HashMap::new();
+    for ix in row_ixs {
+        let values = keys
+            .iter()
+            .map(|k| rows.value_of(*ix, k).unwrap())
+            .collect::<Vec<Value>>();
+        // NULLs make a group of their own
+        let key = values
+            .iter()
+            .map(|v| (!v.is_null()).then(|| v.to_string()))
+            .collect::<Vec<Option<String>>>();
+        match group_ix.get(&key) {
+            Some(g) => groups[*g].1.push(*ix),
+            None => {
+                group_ix.insert(key, groups.len());
+                groups.push((values, vec![*ix]));
+            }
+        }
+    }
+    if keys.is_empty() && groups.is_empty() {
+        groups.push((vec![], vec![]));
+    }
+    for (mut values, ixs) in groups {
+        for (aggregate, arg) in aggregates.iter().zip(&args) {
+            values.push(aggregate_value(rows, aggregate, arg.as_ref(), &ixs));
+        }
+        grouped.push_values(values);
+    }
+    Ok(grouped)
+}
+
+fn output_type(func: AggFunc, datatype: Option<&DataType>) -> Result<DataType, Errors> {
+    let datatype = match datatype {
+        None => return Ok(DataType::Int),
+        Some(datatype) => datatype.clone(),
+    };
+    match func {
+        AggFunc::Count => Ok(DataType::Int),
+        AggFunc::Sum | AggFunc::Avg if datatype != DataType::Int && datatype != DataType::Float => {
+            Err(Errors::InvalidColumnType)
+        }
+        AggFunc::Avg => Ok(DataType::Float),
+        AggFunc::Sum | AggFunc::Min | AggFunc::Max => Ok(datatype),
+    }
+}
+
+// the aggregate over the rows `ixs` of a group of the column `arg` names, NULLs are
+// skipped
+fn aggregate_value<R: Rows + ?Sized>(
+    rows: &R,
+    aggregate: &Aggregate,
+    arg: Option<&(&str, DataType)>,
+    ixs: &[usize],
+) -> Value {
+    let (col, datatype) = match arg {
+        Some(v) => v,
+        None => return Value::Int(ixs.len() as i64),
+    };
+    let mut values = ixs
+        .iter()
+        .map(|ix| rows.value_of(*ix, col).unwrap())
+        .filter(|v| !v.is_null())
+        .collect::<Vec<Value>>();
+    // equal like the rows of `SELECT DISTINCT`
+    if aggregate.distinct {
+        values.sort_by(|a, b| a.total_cmp(b));
+        values.dedup_by(|a, b| a.total_cmp(b).is_eq());
+    }
+    if values.is_empty() && aggregate.func != AggFunc::Count {
+        return Value::Null;
+    }
+    match aggregate.func {
+        AggFunc::Count => Value::Int(values.len() as i64),
+        AggFunc::Sum => match datatype {
+            DataType::Int => Value::Int(values.iter().filter_map(Value::as_int).sum()),
+            _ => Value::Float(values.iter().filter_map(Value::as_float).sum()),
+        },
+        AggFunc::Avg => {
+            let sum = values.iter().filter_map(Value::as_float).sum::<f64>();
+            Value::Float(sum / values.len() as f64)
+        }
+        AggFunc::Min => values.into_iter().min_by(|a, b| a.total_cmp(b)).unwrap(),
+        AggFunc::Max => values.into_iter().max_by(|a, b| a.total_cmp(b)).unwrap(),
+    }
+}
+
+#[test]
+fn test_group_rows() {
+    use crate::database::mvcc::Snapshot;
+    use crate::parser::create::CreateQuery;
+    use crate::parser::select::SelectQuery;
+    use crate::parser::utils::parse_sql;
+
+    let create = parse_sql("CREATE TABLE t (id INT PRIMARY KEY, name TEXT, score INT)").unwrap();
+    let mut tb = Table::new(CreateQuery::format_stat(create).unwrap());
+    let str = |s: &str| Value::Str(s.to_string());
+    for (id, name, score) in [
+        (1, str("a"), Value::Int(3)),
+        (2, str("b"), Value::Int(4)),
+        (3, str("a"), Value::Int(3)),
+        (4, Value::Null, Value::Null),
+        (5, str("a"), Value::Int(5)),
+    ] {
+        tb.push_values(vec![Value::Int(id), name, score]);
+    }
+    let snapshot = Snapshot { txn: 1, csn: 0 };
+    let select = |sql: &str| {
+        let query = SelectQuery::format_stat(parse_sql(sql).unwrap()).unwrap();
+        tb.select_data(query, None, &snapshot)
+    };
+
+    let result =
+        select("SELECT COUNT(*), COUNT(name), SUM(score), AVG(score), MAX(name) FROM t").unwrap();
+    assert_eq!(
+        result.rows,
+        vec![vec![
+            Value::Int(5),
+            Value::Int(4),
+            Value::Int(15),
+            Value::Float(3.75),
+            str("b")
+        ]]
+    );
+    let result = select(
+        "SELECT name, COUNT(DISTINCT score), MIN(score) FROM t GROUP BY name HAVING COUNT(*) > 1",
+    )
+    .unwrap();
+    assert_eq!(
+        result.rows,
+        vec![vec![str("a"), Value::Int(2), Value::Int(3)]]
+    );
+    let result =
+        select("SELECT name, SUM(score) FROM t GROUP BY name ORDER BY SUM(score) DESC").unwrap();
+    assert_eq!(
+        result.rows,
+        vec![
+            vec![Value::Null, Value::Null],
+            vec![str("a"), Value::Int(11)],
+            vec![str("b"), Value::Int(4)]
+        ]
+    );
+    let result = select("SELECT COUNT(*) FROM t WHERE id > 10").unwrap();
+    assert_eq!(result.rows, vec![vec![Value::Int(0)]]);
+    assert!(matches!(
+        select("SELECT name, score FROM t GROUP BY name"),
+        Err(Errors::ColumnNotGrouped(_))
+    ));
+    assert!(matches!(
+        select("SELECT SUM(name) FROM t"),
+        Err(Errors::InvalidColumnType)
+    ));
+}
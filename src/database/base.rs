@@ -1,6 +1,6 @@
 use crate::system::errors::Errors;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::Formatter;
 
@@ -72,28 +72,15 @@ impl ColumnAttr {
             default,
         }
     }
-
-    pub fn attr(&self) -> HashMap<String, String> {
-        let mut row: HashMap<String, String> = HashMap::new();
-        row.insert("name".to_string(), self.name.to_string());
-        row.insert("datatype".to_string(), self.datatype.data_type());
-        row.insert("is_pk".to_string(), self.is_pk.to_string());
-        row.insert("is_nullable".to_string(), self.is_nullable.to_string());
-        row.insert(
-            "default".to_string(),
-            self.default.clone().unwrap_or("None".to_string()),
-        );
-        row
-    }
 }
 
 impl std::fmt::Display for ColumnAttr {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.write_str(&*self.name)
+        f.write_str(&self.name)
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 pub struct ForeignKeyAttr {
     pub table: String,
     // current table's column
@@ -102,6 +89,125 @@ pub struct ForeignKeyAttr {
     pub col_b: String,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+}
+
+impl Value {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// The value of a float or int column.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Float(v) => Some(*v),
+            Value::Int(v) => Some(*v as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(v) => Some(v.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    // Nulls sort first, then booleans, numbers and strings.
+    fn rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Int(_) | Value::Float(_) => 2,
+            Value::Str(_) => 3,
+        }
+    }
+
+    /// Total order used by indexes: ints and floats compare numerically with each other.
+    pub fn total_cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a.cmp(b),
+            (Value::Int(a), Value::Float(b)) => (*a as f64).total_cmp(b),
+            (Value::Float(a), Value::Int(b)) => a.total_cmp(&(*b as f64)),
+            (Value::Float(a), Value::Float(b)) => a.total_cmp(b),
+            (Value::Str(a), Value::Str(b)) => a.cmp(b),
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+
+    /// Orders values for `ORDER BY`. NULLs are placed by `nulls_first` whatever the
+    /// direction is.
+    pub fn sort_cmp(&self, other: &Value, asc: bool, nulls_first: bool) -> Ordering {
+        match (self, other) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Null, _) if nulls_first => Ordering::Less,
+            (Value::Null, _) => Ordering::Greater,
+            (_, Value::Null) if nulls_first => Ordering::Greater,
+            (_, Value::Null) => Ordering::Less,
+            _ if asc => self.total_cmp(other),
+            _ => self.total_cmp(other).reverse(),
+        }
+    }
+
+    /// Parses a literal taken from a condition into a value of the given column type.
+    pub fn from_literal(literal: &str, datatype: &DataType) -> Option<Value> {
+        let literal = literal.trim();
+        if literal.eq_ignore_ascii_case("null") {
+            return Some(Value::Null);
+        }
+        match datatype {
+            DataType::Int => literal.parse::<i32>().ok().map(|v| Value::Int(v as i64)),
+            // columns hold f32, parse at that precision so equal literals match exactly
+            DataType::Float => literal.parse::<f32>().ok().map(|v| Value::Float(widen(v))),
+            DataType::Bool => literal.parse::<bool>().ok().map(Value::Bool),
+            DataType::String => {
+                let stripped = literal
+                    .strip_prefix('\'')
+                    .and_then(|v| v.strip_suffix('\''))
+                    .or_else(|| literal.strip_prefix('"').and_then(|v| v.strip_suffix('"')))
+                    .unwrap_or(literal);
+                Some(Value::Str(stripped.to_string()))
+            }
+            DataType::Invalid => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("NULL"),
+            Value::Int(v) => write!(f, "{}", v),
+            // columns store f32, printed at that precision unless it would lose digits
+            Value::Float(v) if (*v as f32) as f64 == *v => write!(f, "{}", *v as f32),
+            Value::Float(v) => write!(f, "{}", v),
+            Value::Str(v) => f.write_str(v),
+            Value::Bool(v) => write!(f, "{}", v),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub enum ColumnData {
     Int(Vec<Option<i32>>),
@@ -180,16 +286,6 @@ impl ColumnData {
         }
     }
 
-    pub fn update_val(&mut self, ix: usize, val: String) {
-        match self {
-            ColumnData::Int(v) => v[ix] = Option::from(val.parse::<i32>().unwrap()),
-            ColumnData::Float(v) => v[ix] = Option::from(val.parse::<f32>().unwrap()),
-            ColumnData::Str(v) => v[ix] = Option::from(val),
-            ColumnData::Bool(v) => v[ix] = Option::from(val.parse::<bool>().unwrap()),
-            ColumnData::None => {}
-        }
-    }
-
     pub fn delete_val(&mut self, ixs: Vec<usize>) {
         match self {
             ColumnData::Int(v) => {
@@ -227,4 +323,45 @@ impl ColumnData {
             ColumnData::None => {}
         }
     }
+
+    pub fn get_value(&self, ix: usize) -> Value {
+        match self {
+            ColumnData::Int(v) => v[ix].map_or(Value::Null, |x| Value::Int(x as i64)),
+            ColumnData::Float(v) => v[ix].map_or(Value::Null, |x| Value::Float(widen(x))),
+            ColumnData::Str(v) => v[ix].clone().map_or(Value::Null, Value::Str),
+            ColumnData::Bool(v) => v[ix].map_or(Value::Null, Value::Bool),
+            ColumnData::None => Value::Null,
+        }
+    }
+
+    pub fn push_value(&mut self, val: Value) {
+        match (self, val) {
+            (ColumnData::Int(v), Value::Int(x)) => v.push(Some(x as i32)),
+            (ColumnData::Float(v), Value::Float(x)) => v.push(Some(x as f32)),
+            (ColumnData::Float(v), Value::Int(x)) => v.push(Some(x as f32)),
+            (ColumnData::Str(v), Value::Str(x)) => v.push(Some(x)),
+            (ColumnData::Bool(v), Value::Bool(x)) => v.push(Some(x)),
+            (ColumnData::Int(v), _) => v.push(None),
+            (ColumnData::Float(v), _) => v.push(None),
+            (ColumnData::Str(v), _) => v.push(None),
+            (ColumnData::Bool(v), _) => v.push(None),
+            (ColumnData::None, _) => {}
+        }
+    }
+
+    /// Appends row `ix` of `other`, a column of the same type, or NULL without a row.
+    pub fn push_from(&mut self, other: &ColumnData, ix: Option<usize>) {
+        match (self, other, ix) {
+            (ColumnData::Int(v), ColumnData::Int(o), Some(ix)) => v.push(o[ix]),
+            (ColumnData::Float(v), ColumnData::Float(o), Some(ix)) => v.push(o[ix]),
+            (ColumnData::Str(v), ColumnData::Str(o), Some(ix)) => v.push(o[ix].clone()),
+            (ColumnData::Bool(v), ColumnData::Bool(o), Some(ix)) => v.push(o[ix]),
+            (col, _, _) => col.push_value(Value::Null),
+        }
+    }
+}
+
+/// The f64 written like `v`, a cast would add the digits f32 rounded away.
+pub fn widen(v: f32) -> f64 {
+    v.to_string().parse::<f64>().unwrap()
 }
@@ -1,37 +1,97 @@
-use crate::database::table::Table;
+use crate::database::base::Value;
+use crate::database::join::JoinRows;
+use crate::database::mvcc::{Csn, Snapshot, Transaction, Transactions, Undo, FROZEN, PENDING};
+use crate::database::table::{Rows, Table};
+use crate::parser::create::CreateQuery;
+use crate::parser::expr::Expression;
+use crate::parser::index::CreateIndexQuery;
+use crate::parser::join::{FromType, JoinType};
+use crate::storage::btree::BTree;
+use crate::storage::catalog::{Catalog, IndexMeta, TableMeta};
+use crate::storage::heap::{HeapCheckpoint, HeapState, RowSlot};
+use crate::storage::lock::{FileLock, LockMode};
+use crate::storage::page::PageId;
+use crate::storage::pager::Pager;
+use crate::storage::wal::{Mutation, Wal};
 use crate::system::errors::Errors;
 use serde::{Deserialize, Serialize};
-use serde_json;
-use std::fs::File;
-use std::io::{self, Write};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::Path;
+use std::{io, mem};
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Database {
     pub db_name: String,
     pub tables: Vec<Table>,
+    #[serde(skip)]
+    pager: Option<Pager>,
+    // catalog bytes last written to disk, the catalog is only rewritten when it changes
+    #[serde(skip)]
+    catalog: Vec<u8>,
+    // heap chains of dropped tables, released on the next save
+    #[serde(skip)]
+    dropped: Vec<PageId>,
+    // b-tree roots of dropped indexes, released on the next save
+    #[serde(skip)]
+    dropped_indexes: Vec<PageId>,
+    // mutations applied in memory that the next save logs to the wal
+    #[serde(skip)]
+    pending: Vec<Mutation>,
+    // mutations replayed from the wal when the database was loaded
+    #[serde(skip)]
+    pub recovered: Vec<Mutation>,
+    // transaction ids and commit numbers shared by the sessions using this database
+    #[serde(skip)]
+    pub txns: Transactions,
+    // advisory lock on the database file, held while a transaction of this process
+    // reads or writes it
+    #[serde(skip)]
+    lock: Option<FileLock>,
 }
 
 impl Database {
     pub fn new() -> Database {
-        return Database {
+        Database {
             db_name: "".to_string(),
             tables: vec![],
-        };
+            pager: None,
+            catalog: vec![],
+            dropped: vec![],
+            dropped_indexes: vec![],
+            pending: vec![],
+            recovered: vec![],
+            txns: Transactions::default(),
+            lock: None,
+        }
     }
 
     pub fn set_dbname(&mut self, db_name: String) {
         self.db_name = db_name;
     }
 
-    pub fn create_table(&mut self, tb: Table) -> Result<(), Errors> {
+    pub fn create_table(&mut self, mut tb: Table) -> Result<(), Errors> {
         if self.check_table(tb.name.clone()) {
             return Err(Errors::TableExisted(tb.name));
         }
+        self.pager().map_err(|_| Errors::DiskSaveError)?;
+        tb.add_primary_key_index(self.pager.as_mut().unwrap())
+            .map_err(|_| Errors::DiskSaveError)?;
         self.tables.push(tb);
         Ok(())
     }
 
     pub fn drop_table(&mut self, drop_tbs: Vec<String>) {
+        for tb in &self.tables {
+            if !drop_tbs.contains(&tb.name) {
+                continue;
+            }
+            if tb.heap.first_page != 0 {
+                self.dropped.push(tb.heap.first_page);
+            }
+            self.dropped_indexes
+                .extend(tb.indexes.iter().map(|idx| idx.root));
+        }
         self.tables.retain(|table| !drop_tbs.contains(&table.name));
     }
 
@@ -47,6 +107,45 @@ impl Database {
         }
         Err(Errors::TableNotExisted(tb_name))
     }
+    /// The table `name` of a query, the tables it names by a subquery or by `WITH`
+    /// hide stored ones of the same name, the first of them the others.
+    pub fn query_table<'a>(&'a self, name: &str, named: &[&'a Table]) -> Result<&'a Table, Errors> {
+        match named.iter().find(|tb| tb.name == name) {
+            Some(tb) => Ok(*tb),
+            None => self.get_table(name.to_string()),
+        }
+    }
+
+    /// The rows of a `FROM` list visible to `snapshot`: its tables joined in order,
+    /// with the columns named `<table or alias>.<column>` when there are several.
+    pub fn join_tables<'a>(
+        &'a self,
+        from: &[FromType],
+        named: &[&'a Table],
+        snapshot: &Snapshot,
+    ) -> Result<JoinRows<'a>, Errors> {
+        let mut joint: Option<JoinRows> = None;
+        for item in from {
+            let (tb, alias, joins) = match item {
+                FromType::Join { tb, alias, joins } => (tb, alias, joins.as_slice()),
+                FromType::String { tb, alias } => (tb, alias, [].as_slice()),
+            };
+            let name = alias.as_ref().unwrap_or(tb);
+            let mut rows = JoinRows::new(name, self.query_table(tb, named)?, snapshot);
+            for join in joins {
+                let name = join.alias.as_ref().unwrap_or(&join.tb);
+                let right = JoinRows::new(name, self.query_table(&join.tb, named)?, snapshot);
+                rows = rows.join(right, &join.join_type, &join.on)?;
+            }
+            // items separated by commas are cross joined
+            joint = Some(match joint {
+                Some(left) => left.join(rows, &JoinType::Inner, &None)?,
+                None => rows,
+            });
+        }
+        joint.ok_or(Errors::InvalidExpression)
+    }
+
     pub fn get_table_mut(&mut self, tb_name: String) -> Result<&mut Table, Errors> {
         for tb in &mut self.tables {
             if tb.name == tb_name {
@@ -56,27 +155,683 @@ impl Database {
         Err(Errors::TableNotExisted(tb_name))
     }
 
-    pub fn insert_row(&mut self, tb_name: String, cols: Vec<String>, rows: Vec<Vec<String>>) {
-        let tb: &mut Table = match self.get_table_mut(tb_name.clone()) {
-            Ok(v) => v,
-            Err(err) => {
-                err.print();
-                return;
+    pub fn create_index(&mut self, query: CreateIndexQuery) -> Result<(), Errors> {
+        if self.find_index(&query.idx_name).is_some() {
+            return Err(Errors::IndexExisted(query.idx_name));
+        }
+        let tb = self.get_table(query.tb_name.clone())?;
+        if !tb.columns.iter().any(|c| c.name == query.column) {
+            return Err(Errors::ColumnNotExisted(query.column));
+        }
+        self.pager().map_err(|_| Errors::DiskSaveError)?;
+        let pager = self.pager.as_mut().unwrap();
+        let tb = self
+            .tables
+            .iter_mut()
+            .find(|tb| tb.name == query.tb_name)
+            .unwrap();
+        let tree = tb
+            .build_index(pager, &query.column)
+            .map_err(|_| Errors::DiskSaveError)?;
+        tb.indexes.push(IndexMeta {
+            name: query.idx_name,
+            column: query.column,
+            root: tree.root,
+            unique: false,
+        });
+        Ok(())
+    }
+
+    pub fn drop_index(&mut self, idx_names: Vec<String>) -> Result<(), Errors> {
+        for name in &idx_names {
+            match self.find_index(name) {
+                None => return Err(Errors::IndexNotExisted(name.to_string())),
+                Some(idx) if idx.unique => return Err(Errors::IndexRequired(name.to_string())),
+                Some(_) => {}
+            }
+        }
+        for tb in &mut self.tables {
+            for idx in &tb.indexes {
+                if idx_names.contains(&idx.name) {
+                    self.dropped_indexes.push(idx.root);
+                }
             }
+            tb.indexes.retain(|idx| !idx_names.contains(&idx.name));
+        }
+        Ok(())
+    }
+
+    // index names are unique within a database, like table names
+    fn find_index(&self, idx_name: &str) -> Option<&IndexMeta> {
+        self.tables
+            .iter()
+            .flat_map(|tb| tb.indexes.iter())
+            .find(|idx| idx.name == idx_name)
+    }
+
+    /// Rows of `tb_name` an index narrows `condition` down to, `None` when the
+    /// table has to be scanned.
+    pub fn index_candidates(
+        &mut self,
+        tb_name: &str,
+        condition: &Option<Expression>,
+    ) -> io::Result<Option<Vec<usize>>> {
+        let condition = match condition {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        match self.tables.iter().find(|tb| tb.name == tb_name) {
+            Some(tb) if !tb.indexes.is_empty() => {}
+            _ => return Ok(None),
+        }
+        self.pager()?;
+        let pager = self.pager.as_mut().unwrap();
+        let tb = self.tables.iter().find(|tb| tb.name == tb_name).unwrap();
+        tb.index_lookup(pager, condition)
+    }
+
+    /// Inserts `rows` as versions of transaction `txn`, they stay invisible to other
+    /// transactions until it commits.
+    pub fn insert_row(
+        &mut self,
+        tb_name: String,
+        cols: Vec<String>,
+        rows: Vec<Vec<String>>,
+        txn: &mut Transaction,
+    ) -> Result<(), Errors> {
+        self.lock_file(LockMode::Exclusive)?;
+        self.pager().map_err(|_| Errors::DiskSaveError)?;
+        let pager = self.pager.as_mut().unwrap();
+        let tb = match self.tables.iter_mut().find(|tb| tb.name == tb_name) {
+            Some(v) => v,
+            None => return Err(Errors::TableNotExisted(tb_name)),
         };
-        tb.insert_row(cols, rows);
+        // the rows are checked as a whole before any of them is added
+        let rows = tb.parse_rows(&cols, &rows)?;
+        if let Some(pk_ix) = tb.columns.iter().position(|col| col.is_pk) {
+            let keys = rows
+                .iter()
+                .map(|row| (row[pk_ix].clone(), None))
+                .collect::<Vec<(Value, Option<u64>)>>();
+            tb.check_primary_keys(pager, &keys, txn.id())?;
+        }
+        if !rows.iter().all(|row| HeapState::fits(row)) {
+            return Err(Errors::RowTooLarge(tb_name));
+        }
+        for rid in tb.insert_row(rows, txn.id()) {
+            txn.undo.push(Undo::Created {
+                table: tb_name.to_string(),
+                rid,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn update_rows(
+        &mut self,
+        tb_name: String,
+        row_ixs: &[usize],
+        assignments: &HashMap<String, String>,
+        txn: &mut Transaction,
+    ) -> Result<(), Errors> {
+        self.lock_file(LockMode::Exclusive)?;
+        self.pager().map_err(|_| Errors::DiskSaveError)?;
+        let pager = self.pager.as_mut().unwrap();
+        let tb = match self.tables.iter_mut().find(|tb| tb.name == tb_name) {
+            Some(v) => v,
+            None => return Err(Errors::TableNotExisted(tb_name)),
+        };
+        tb.check_write_conflicts(row_ixs, txn.id())?;
+        let assignments = tb.parse_assignments(assignments)?;
+        if let Some(pk) = tb.primary_key() {
+            if let Some(key) = assignments.get(&pk.name) {
+                let keys = row_ixs
+                    .iter()
+                    .map(|ix| (key.clone(), Some(tb.heap.rows[*ix].rid)))
+                    .collect::<Vec<(Value, Option<u64>)>>();
+                tb.check_primary_keys(pager, &keys, txn.id())?;
+            }
+        }
+        let fits = row_ixs.iter().all(|ix| {
+            let row = tb
+                .columns
+                .iter()
+                .zip(tb.row_values(*ix))
+                .map(|(col, val)| assignments.get(&col.name).cloned().unwrap_or(val))
+                .collect::<Vec<Value>>();
+            HeapState::fits(&row)
+        });
+        if !fits {
+            return Err(Errors::RowTooLarge(tb_name));
+        }
+        for ix in row_ixs {
+            txn.undo.push(Undo::Deleted {
+                table: tb_name.to_string(),
+                rid: tb.heap.rows[*ix].rid,
+            });
+        }
+        for rid in tb.update_rows(row_ixs, &assignments, txn.id()) {
+            txn.undo.push(Undo::Created {
+                table: tb_name.to_string(),
+                rid,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn delete_rows(
+        &mut self,
+        tb_name: String,
+        row_ixs: &[usize],
+        txn: &mut Transaction,
+    ) -> Result<(), Errors> {
+        self.lock_file(LockMode::Exclusive)?;
+        let tb = self.get_table_mut(tb_name.to_string())?;
+        tb.check_write_conflicts(row_ixs, txn.id())?;
+        for ix in row_ixs {
+            let row = &mut tb.heap.rows[*ix];
+            row.xmax = txn.id();
+            txn.undo.push(Undo::Deleted {
+                table: tb_name.to_string(),
+                rid: row.rid,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn file_path(&self) -> String {
+        format!("sql_files/{}.bin", self.db_name)
+    }
+
+    /// Locks the database file against other processes, shared for reading and
+    /// exclusive for writing. When this process held no lock, another one may have
+    /// changed the file in the meantime and the tables are loaded again.
+    pub fn lock_file(&mut self, mode: LockMode) -> Result<(), Errors> {
+        if self.lock.is_none() {
+            let lock =
+                FileLock::open(self.file_path().as_str()).map_err(|_| Errors::FileSystemError)?;
+            self.lock = Some(lock);
+        }
+        let lock = self.lock.as_mut().unwrap();
+        let unlocked = lock.mode().is_none();
+        lock.acquire(mode)
+            .map_err(|_| Errors::DatabaseLocked(self.db_name.to_string()))?;
+        if unlocked && self.pager.is_some() {
+            let pager = self.pager.as_mut().unwrap();
+            if pager.is_stale().map_err(|_| Errors::FileSystemError)? {
+                self.load_from_disk(self.file_path().as_str())
+                    .map_err(|_| Errors::FileSystemError)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Lets other processes write again once no transaction of this one is open.
+    pub fn unlock_file(&mut self) {
+        if !self.txns.is_idle() {
+            return;
+        }
+        if let Some(lock) = self.lock.as_mut() {
+            let _ = lock.release();
+        }
+    }
+
+    /// Records a change made outside of any transaction, like a schema change, and
+    /// persists it right away, see `save_disk`.
+    pub fn commit(&mut self, mutation: Mutation) -> io::Result<()> {
+        self.pending.push(mutation);
+        self.save_disk()
+    }
+
+    /// Makes the changes of `txn` visible to the transactions that start after it: its
+    /// versions are stamped with the next commit number and saved with one wal frame.
+    pub fn commit_transaction(&mut self, txn: Transaction) -> Result<(), Errors> {
+        self.txns.end(&txn);
+        if !txn.undo.is_empty() {
+            self.txns.csn += 1;
+            let csn = self.txns.csn;
+            self.stamp(&txn.undo, csn);
+            // what the save changes in memory, put back when it fails
+            let saved = self
+                .tables
+                .iter()
+                .map(|tb| (tb.heap.checkpoint(), tb.indexes.clone()))
+                .collect::<Vec<(HeapCheckpoint, Vec<IndexMeta>)>>();
+            let dropped = (self.dropped.clone(), self.dropped_indexes.clone());
+            let catalog = self.catalog.clone();
+            self.pending.extend(txn.mutations);
+            if self.save_disk().is_err() {
+                self.pending.clear();
+                if let Some(pager) = self.pager.as_mut() {
+                    pager.discard();
+                }
+                // the rows are neither committed nor on disk, the transaction is undone
+                for (tb, (heap, indexes)) in self.tables.iter_mut().zip(saved) {
+                    tb.heap.restore(heap);
+                    tb.indexes = indexes;
+                }
+                (self.dropped, self.dropped_indexes) = dropped;
+                self.catalog = catalog;
+                self.stamp(&txn.undo, PENDING);
+                self.txns.csn -= 1;
+                self.undo(&txn.undo);
+                return Err(Errors::DiskSaveError);
+            }
+        }
+        let horizon = self.txns.horizon();
+        for tb in &mut self.tables {
+            tb.vacuum(horizon);
+        }
+        Ok(())
+    }
+
+    // Gives the versions created and deleted by a transaction its commit number.
+    fn stamp(&mut self, undo: &[Undo], csn: Csn) {
+        for undo in undo {
+            match undo {
+                Undo::Created { table, rid } => {
+                    if let Some(row) = self.version_mut(table, *rid) {
+                        row.xmin_csn = csn;
+                    }
+                }
+                Undo::Deleted { table, rid } => {
+                    if let Some(row) = self.version_mut(table, *rid) {
+                        row.xmax_csn = csn;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn rollback_transaction(&mut self, txn: Transaction) {
+        self.txns.end(&txn);
+        self.undo(&txn.undo);
+    }
+
+    /// Undoes the changes `txn` made after it had `undo_len` undo entries and
+    /// `mutations` mutations, the transaction stays open.
+    pub fn rollback_to(&mut self, txn: &mut Transaction, undo_len: usize, mutations: usize) {
+        let undo = txn.undo.split_off(undo_len);
+        txn.mutations.truncate(mutations);
+        self.undo(&undo);
+    }
+
+    // Versions created by the transaction are dropped, versions it deleted are live again.
+    fn undo(&mut self, undo: &[Undo]) {
+        for undo in undo.iter().rev() {
+            match undo {
+                Undo::Created { table, rid } => {
+                    if let Ok(tb) = self.get_table_mut(table.to_string()) {
+                        if let Some(ix) = tb.heap.position(*rid) {
+                            tb.delete_rows(vec![ix]);
+                        }
+                    }
+                }
+                Undo::Deleted { table, rid } => {
+                    if let Some(row) = self.version_mut(table, *rid) {
+                        row.xmax = FROZEN;
+                    }
+                }
+            }
+        }
+    }
+
+    fn version_mut(&mut self, tb_name: &str, rid: u64) -> Option<&mut RowSlot> {
+        let tb = self.tables.iter_mut().find(|tb| tb.name == tb_name)?;
+        let ix = tb.heap.position(rid)?;
+        Some(&mut tb.heap.rows[ix])
     }
-    pub fn save_disk(&self) -> io::Result<()> {
-        let serialized_data = serde_json::to_string(&self)?;
-        let mut file =
-            File::create("sql_files/".to_owned() + self.db_name.to_string().as_str() + ".bin")?;
-        file.write_all(serialized_data.as_bytes())?;
+
+    /// Writes the pages touched since the last save: rows that were inserted, updated
+    /// or deleted, and the catalog when the schema changed. The pages are logged to
+    /// the wal together with the pending mutations before the database file is touched.
+    pub fn save_disk(&mut self) -> io::Result<()> {
+        self.stage()?;
+        let pager = self.pager.as_mut().unwrap();
+        pager.commit(mem::take(&mut self.pending))
+    }
+
+    // Brings the buffer pool in line with the tables without committing the pages.
+    fn stage(&mut self) -> io::Result<()> {
+        self.pager()?;
+        let pager = self.pager.as_mut().unwrap();
+        for first_page in self.dropped.drain(..) {
+            pager.free_chain(first_page)?;
+        }
+        for root in self.dropped_indexes.drain(..) {
+            BTree { root }.destroy(pager)?;
+        }
+        for tb in &mut self.tables {
+            tb.sync(pager)?;
+        }
+        let catalog = Catalog {
+            db_name: self.db_name.to_string(),
+            tables: self
+                .tables
+                .iter()
+                .map(|tb| TableMeta {
+                    name: tb.name.to_string(),
+                    columns: tb.columns.clone(),
+                    foreign_keys: tb.foreign_keys.clone(),
+                    first_page: tb.heap.first_page,
+                    indexes: tb.indexes.clone(),
+                })
+                .collect::<Vec<TableMeta>>(),
+        }
+        .encode()?;
+        if catalog != self.catalog {
+            Catalog::write(pager, &catalog)?;
+            self.catalog = catalog;
+        }
         Ok(())
     }
+
+    fn pager(&mut self) -> io::Result<&mut Pager> {
+        if self.pager.is_none() {
+            self.pager = Some(Pager::open(self.file_path().as_str())?);
+        }
+        Ok(self.pager.as_mut().unwrap())
+    }
+
+    /// Opens the database file, replaying the wal first when the last session did not
+    /// shut down cleanly. The replayed mutations are kept in `recovered`.
     pub fn load_from_disk(&mut self, filename: &str) -> io::Result<()> {
+        // a legacy json dump never has a wal next to it
+        if !Path::new(&Wal::path_for(filename)).exists() && !Pager::is_page_file(filename)? {
+            return self.load_legacy(filename);
+        }
+        let mut pager = Pager::open(filename)?;
+        let recovered = pager.take_recovered();
+        let (catalog, catalog_bytes) = Catalog::read(&mut pager)?;
+        let mut tables: Vec<Table> = vec![];
+        for meta in catalog.tables {
+            let mut tb = Table::new(CreateQuery {
+                tb_name: meta.name,
+                cols: meta.columns,
+                foreign_key: meta.foreign_keys.unwrap_or_default(),
+            });
+            tb.load(&mut pager, meta.first_page)?;
+            tb.indexes = meta.indexes;
+            // files written before primary keys were indexed get their index now
+            tb.add_primary_key_index(&mut pager)?;
+            tables.push(tb);
+        }
+        *self = Database {
+            db_name: catalog.db_name,
+            tables,
+            pager: Some(pager),
+            catalog: catalog_bytes,
+            dropped: vec![],
+            dropped_indexes: vec![],
+            pending: vec![],
+            recovered,
+            txns: mem::take(&mut self.txns),
+            lock: self.lock.take(),
+        };
+        self.save_disk()
+    }
+
+    // Databases written before the page format were a single json document. They are
+    // converted once, the original file is kept next to the new one with a `.legacy` suffix.
+    fn load_legacy(&mut self, filename: &str) -> io::Result<()> {
         let file = File::open(filename)?;
-        let database: Database = serde_json::from_reader(file)?;
+        let mut database: Database = serde_json::from_reader(file)?;
+        for tb in &mut database.tables {
+            tb.heap = HeapState::default();
+            for _ in 0..tb.row_count() {
+                tb.heap.push_row();
+            }
+        }
+        fs::rename(filename, format!("{}.legacy", filename))?;
+        database.pager()?;
+        let pager = database.pager.as_mut().unwrap();
+        for tb in &mut database.tables {
+            tb.add_primary_key_index(pager)?;
+        }
+        database.save_disk()?;
+        database.txns = mem::take(&mut self.txns);
+        // the lock went with the renamed file, it is taken again on the new one
+        let mode = self.lock.take().and_then(|lock| lock.mode());
         *self = database;
+        if let Some(mode) = mode {
+            self.lock_file(mode)
+                .map_err(|err| io::Error::new(io::ErrorKind::WouldBlock, err.to_string()))?;
+        }
         Ok(())
     }
 }
+
+#[test]
+fn test_primary_keys() {
+    use crate::connection::Connection;
+    use crate::system::utils::TempDb;
+
+    let temp = TempDb::new("pkey");
+    let db_name = temp.name.as_str();
+    let mut conn = Connection::new();
+    conn.execute(&format!("USE {}", db_name)).unwrap();
+    conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT)")
+        .unwrap();
+    conn.execute("INSERT INTO t (id, name) VALUES (1, 'a'), (2, 'b')")
+        .unwrap();
+
+    // a statement that fails adds none of its rows
+    assert!(matches!(
+        conn.execute("INSERT INTO t (id, name) VALUES (3, 'c'), (3, 'd')"),
+        Err(Errors::PrimaryKeyExisted(_))
+    ));
+    assert!(matches!(
+        conn.execute("UPDATE t SET id = 2 WHERE id = 1"),
+        Err(Errors::PrimaryKeyExisted(_))
+    ));
+    for sql in [
+        "INSERT INTO t (name) VALUES ('e')",
+        "INSERT INTO t (id, name) VALUES (NULL, 'e')",
+        "UPDATE t SET id = NULL WHERE id = 1",
+    ] {
+        assert!(matches!(conn.execute(sql), Err(Errors::NullValue(_))));
+    }
+    conn.execute("DELETE FROM t WHERE id = 2").unwrap();
+    conn.execute("INSERT INTO t (id, name) VALUES (2, 'c')")
+        .unwrap();
+    let names = conn.query("SELECT name FROM t WHERE id = 2").unwrap();
+    assert_eq!(names.rows, vec![vec![Value::Str("c".to_string())]]);
+    assert_eq!(conn.execute("SELECT id FROM t").unwrap(), 2);
+    drop(conn);
+
+    // the index is stored with the table
+    let mut db = Database::new();
+    db.set_dbname(db_name.to_string());
+    db.load_from_disk(&db.file_path()).unwrap();
+    let tb = db.get_table("t".to_string()).unwrap();
+    assert!(tb
+        .indexes
+        .iter()
+        .any(|idx| idx.name == "t_pkey" && idx.unique));
+    drop(db);
+    let mut conn = Connection::open(db_name).unwrap();
+    assert!(matches!(
+        conn.execute("INSERT INTO t (id, name) VALUES (1, 'x')"),
+        Err(Errors::PrimaryKeyExisted(_))
+    ));
+}
+
+#[test]
+fn test_failed_commit() {
+    use crate::connection::Connection;
+    use crate::system::utils::TempDb;
+
+    let temp = TempDb::new("failed_commit");
+    let db_name = temp.name.as_str();
+    let mut conn = Connection::new();
+    conn.execute(&format!("USE {}", db_name)).unwrap();
+    conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT)")
+        .unwrap();
+    conn.execute("INSERT INTO t (id, name) VALUES (1, 'a')")
+        .unwrap();
+
+    // the changes of a transaction whose save fails are undone
+    let fail = || crate::storage::pager::FAIL_COMMIT.with(|fail| fail.set(true));
+    fail();
+    assert!(matches!(
+        conn.execute("INSERT INTO t (id, name) VALUES (2, 'b'), (3, 'c')"),
+        Err(Errors::DiskSaveError)
+    ));
+    fail();
+    assert!(matches!(
+        conn.execute("UPDATE t SET name = 'z' WHERE id = 1"),
+        Err(Errors::DiskSaveError)
+    ));
+    conn.execute("BEGIN").unwrap();
+    conn.execute("DELETE FROM t WHERE id = 1").unwrap();
+    conn.execute("INSERT INTO t (id, name) VALUES (4, 'd')")
+        .unwrap();
+    fail();
+    assert!(matches!(conn.execute("COMMIT"), Err(Errors::DiskSaveError)));
+    let rows = conn.query("SELECT id, name FROM t").unwrap();
+    assert_eq!(
+        rows.rows,
+        vec![vec![Value::Int(1), Value::Str("a".to_string())]]
+    );
+
+    // the next commit saves its own changes only
+    conn.execute("INSERT INTO t (id, name) VALUES (2, 'b')")
+        .unwrap();
+    conn.execute("UPDATE t SET name = 'y' WHERE id = 1")
+        .unwrap();
+    drop(conn);
+    let mut conn = Connection::open(db_name).unwrap();
+    let rows = conn.query("SELECT id, name FROM t ORDER BY id").unwrap();
+    assert_eq!(
+        rows.rows,
+        vec![
+            vec![Value::Int(1), Value::Str("y".to_string())],
+            vec![Value::Int(2), Value::Str("b".to_string())]
+        ]
+    );
+}
+
+#[test]
+fn test_row_size_limit() {
+    use crate::connection::Connection;
+    use crate::system::utils::TempDb;
+
+    let temp = TempDb::new("row_size");
+    let mut conn = Connection::open(&temp.name).unwrap();
+    conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT)")
+        .unwrap();
+    conn.execute("INSERT INTO t (id, name) VALUES (1, 'a')")
+        .unwrap();
+
+    // a row is kept in one page, a larger one is refused before anything changes
+    let long = "x".repeat(5000);
+    let sql = format!("INSERT INTO t (id, name) VALUES (2, 'b'), (3, '{}')", long);
+    assert!(matches!(conn.execute(&sql), Err(Errors::RowTooLarge(_))));
+    let sql = format!("UPDATE t SET name = '{}' WHERE id = 1", long);
+    assert!(matches!(conn.execute(&sql), Err(Errors::RowTooLarge(_))));
+    let sql = format!("UPDATE t SET name = '{}' WHERE id = 1", "x".repeat(4000));
+    conn.execute(&sql).unwrap();
+    drop(conn);
+    let mut conn = Connection::open(&temp.name).unwrap();
+    assert_eq!(conn.execute("SELECT id FROM t").unwrap(), 1);
+}
+
+#[test]
+fn test_index_lookups() {
+    use crate::connection::Connection;
+    use crate::database::session::Session;
+    use crate::parser::select::SelectQuery;
+    use crate::parser::utils::parse_sql;
+    use crate::system::utils::TempDb;
+
+    let temp = TempDb::new("index_lookups");
+    let mut conn = Connection::open(&temp.name).unwrap();
+    conn.execute("CREATE TABLE t (id INT PRIMARY KEY, n INT, name TEXT)")
+        .unwrap();
+    let rows = (1..=40)
+        .map(|id| match id % 5 {
+            0 => format!("({}, NULL, 'r{}')", id, id),
+            _ => format!("({}, {}, 'r{}')", id, id % 7, id),
+        })
+        .collect::<Vec<String>>();
+    conn.execute(&format!(
+        "INSERT INTO t (id, n, name) VALUES {}",
+        rows.join(", ")
+    ))
+    .unwrap();
+    conn.execute("CREATE INDEX t_n ON t (n)").unwrap();
+    conn.execute("UPDATE t SET n = 100 WHERE id < 4").unwrap();
+    conn.execute("UPDATE t SET n = NULL WHERE n = 6").unwrap();
+    conn.execute("UPDATE t SET n = 3 WHERE id = 35").unwrap();
+    conn.execute("DELETE FROM t WHERE n = 4").unwrap();
+    conn.execute("DELETE FROM t WHERE id > 37").unwrap();
+    conn.execute("INSERT INTO t (id, n, name) VALUES (41, 2, 'r41'), (42, NULL, 'r42')")
+        .unwrap();
+
+    let mut session = Session::new();
+    session.use_db(&temp.name).unwrap();
+    let condition = |sql: &str| {
+        let query = SelectQuery::format_stat(parse_sql(sql).unwrap()).unwrap();
+        query.condition
+    };
+    let ids = |conn: &mut Connection, condition: &str| {
+        let sql = format!("SELECT id FROM t WHERE {} ORDER BY id", condition);
+        conn.query(&sql).unwrap().rows
+    };
+    // the same rows come out of the index and of a scan, which `n + 0` forces
+    for lookup in [
+        "n = 2",
+        "n < 3",
+        "n > 2",
+        "n <= 1",
+        "n IS NULL",
+        "n > 0 AND n < 3",
+        "n = 1 OR n = 100",
+        "n IS NULL OR n = 5",
+        "n = 2 AND id > 10",
+    ] {
+        let scan = lookup.replace("n ", "n + 0 ");
+        let expected = ids(&mut conn, &scan);
+        assert!(!expected.is_empty(), "{}", lookup);
+        assert_eq!(ids(&mut conn, lookup), expected, "{}", lookup);
+        let candidates = session
+            .run(|db, _| {
+                db.index_candidates(
+                    "t",
+                    &condition(&format!("SELECT id FROM t WHERE {}", lookup)),
+                )
+                .map_err(|_| Errors::FileSystemError)
+            })
+            .unwrap();
+        assert!(candidates.is_some(), "{}", lookup);
+    }
+
+    // an index that lost a row hides it from the statements that read through it
+    session
+        .run(|db, _| {
+            let pager = db.pager.as_mut().unwrap();
+            let tb = db.tables.iter_mut().find(|tb| tb.name == "t").unwrap();
+            let ix = (0..tb.row_count())
+                .find(|ix| tb.row_values(*ix)[0] == Value::Int(9) && tb.heap.rows[*ix].is_durable())
+                .unwrap();
+            let index = tb.indexes.iter_mut().find(|idx| idx.name == "t_n").unwrap();
+            let mut tree = BTree { root: index.root };
+            let key = (Value::Int(2), tb.heap.rows[ix].rid);
+            assert!(tree.delete(pager, &key).unwrap());
+            index.root = tree.root;
+            Ok(())
+        })
+        .unwrap();
+    let all = ids(&mut conn, "n + 0 = 2");
+    assert!(all.contains(&vec![Value::Int(9)]));
+    let indexed = ids(&mut conn, "n = 2");
+    assert_eq!(indexed.len(), all.len() - 1);
+    assert!(!indexed.contains(&vec![Value::Int(9)]));
+    let missed = all.len() as u64 - 1;
+    assert_eq!(
+        conn.execute("UPDATE t SET name = 'x' WHERE n = 2").unwrap(),
+        missed
+    );
+    assert_eq!(conn.execute("DELETE FROM t WHERE n = 2").unwrap(), missed);
+    assert_eq!(ids(&mut conn, "n + 0 = 2"), vec![vec![Value::Int(9)]]);
+}
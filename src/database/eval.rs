@@ -0,0 +1,370 @@
+use crate::database::base::{DataType, Value};
+use crate::database::function::{cast, lookup};
+use crate::parser::expr::{BinaryOp, Expression, UnaryOp};
+use crate::system::errors::Errors;
+use crate::system::utils::wildcard_match;
+use std::cmp::Ordering;
+
+impl Expression {
+    /// Evaluates the expression against a row, `row` gives the value of a column by
+    /// name. Comparisons and logic follow SQL: NULL makes a comparison unknown and
+    /// `AND`/`OR` only become unknown when the other side cannot decide them.
+    pub fn eval(&self, row: &dyn Fn(&str) -> Option<Value>) -> Result<Value, Errors> {
+        match self {
+            Expression::Column(name) => {
+                row(name).ok_or_else(|| Errors::ColumnNotExisted(name.to_string()))
+            }
+            Expression::Literal(value) => Ok(value.clone()),
+            Expression::Unary { op, expr } => {
+                let value = expr.eval(row)?;
+                match (op, value) {
+                    (_, Value::Null) => Ok(Value::Null),
+                    (UnaryOp::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+                    (UnaryOp::Minus, Value::Int(v)) => Ok(Value::Int(-v)),
+                    (UnaryOp::Minus, Value::Float(v)) => Ok(Value::Float(-v)),
+                    (UnaryOp::Plus, v @ (Value::Int(_) | Value::Float(_))) => Ok(v),
+                    (op, v) => Err(Errors::TypeMismatch(format!(
+                        "{} {}",
+                        match op {
+                            UnaryOp::Not => "NOT",
+                            UnaryOp::Minus => "-",
+                            UnaryOp::Plus => "+",
+                        },
+                        type_name(&v)
+                    ))),
+                }
+            }
+            Expression::Binary { left, op, right } => match op {
+                BinaryOp::And | BinaryOp::Or => {
+                    let left = truth(left.eval(row)?, op)?;
+                    // the right side is only needed when the left one does not decide
+                    match (op, left) {
+                        (BinaryOp::And, Some(false)) => return Ok(Value::Bool(false)),
+                        (BinaryOp::Or, Some(true)) => return Ok(Value::Bool(true)),
+                        _ => {}
+                    }
+                    let right = truth(right.eval(row)?, op)?;
+                    let result = match (op, left, right) {
+                        (BinaryOp::And, _, Some(false)) => Some(false),
+                        (BinaryOp::And, Some(true), Some(true)) => Some(true),
+                        (BinaryOp::Or, _, Some(true)) => Some(true),
+                        (BinaryOp::Or, Some(false), Some(false)) => Some(false),
+                        _ => None,
+                    };
+                    Ok(result.map_or(Value::Null, Value::Bool))
+                }
+                _ => binary(&left.eval(row)?, *op, &right.eval(row)?),
+            },
+            Expression::IsNull { expr, negated } => {
+                Ok(Value::Bool(expr.eval(row)?.is_null() != *negated))
+            }
+            Expression::Like {
+                expr,
+                pattern,
+                negated,
+                case_insensitive,
+                escape,
+            } => {
+                let (value, pattern) = match (expr.eval(row)?, pattern.eval(row)?) {
+                    (Value::Null, _) | (_, Value::Null) => return Ok(Value::Null),
+                    (Value::Str(v), Value::Str(p)) => (v, p),
+                    (v, p) => {
+                        return Err(Errors::TypeMismatch(format!(
+                            "{} LIKE {}",
+                            type_name(&v),
+                            type_name(&p)
+                        )))
+                    }
+                };
+                let matched = wildcard_match(&pattern, &value, *case_insensitive, *escape);
+                Ok(Value::Bool(matched != *negated))
+            }
+            Expression::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let value = expr.eval(row)?;
+                if value.is_null() {
+                    return Ok(Value::Null);
+                }
+                let mut unknown = false;
+                for item in list {
+                    match compare(&value, &item.eval(row)?, BinaryOp::Eq)? {
+                        Some(Ordering::Equal) => return Ok(Value::Bool(!*negated)),
+                        Some(_) => {}
+                        None => unknown = true,
+                    }
+                }
+                match unknown {
+                    true => Ok(Value::Null),
+                    false => Ok(Value::Bool(*negated)),
+                }
+            }
+            Expression::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => {
+                let value = expr.eval(row)?;
+                let above = binary(&value, BinaryOp::GtEq, &low.eval(row)?)?;
+                let below = binary(&value, BinaryOp::LtEq, &high.eval(row)?)?;
+                let result = match (above, below) {
+                    (Value::Bool(false), _) | (_, Value::Bool(false)) => Value::Bool(false),
+                    (Value::Bool(true), Value::Bool(true)) => Value::Bool(true),
+                    _ => Value::Null,
+                };
+                match (result, negated) {
+                    (Value::Bool(b), true) => Ok(Value::Bool(!b)),
+                    (result, _) => Ok(result),
+                }
+            }
+            Expression::Function { name, args } => {
+                let function =
+                    lookup(name).ok_or_else(|| Errors::FunctionNotExisted(name.to_string()))?;
+                let args = args
+                    .iter()
+                    .map(|arg| arg.eval(row))
+                    .collect::<Result<Vec<Value>, Errors>>()?;
+                function.call(&args)
+            }
+            Expression::Cast { expr, datatype } => cast(expr.eval(row)?, datatype),
+            // the database replaces subqueries by their results before rows are evaluated
+            Expression::Subquery(_) | Expression::Exists { .. } | Expression::InSubquery { .. } => {
+                Err(Errors::UnimplementedOperation)
+            }
+        }
+    }
+
+    /// Whether a row passes the expression as a filter, unknown does not pass.
+    pub fn is_true(&self, row: &dyn Fn(&str) -> Option<Value>) -> Result<bool, Errors> {
+        match self.eval(row)? {
+            Value::Bool(b) => Ok(b),
+            Value::Null => Ok(false),
+            v => Err(Errors::TypeMismatch(format!(
+                "{} used as a condition",
+                type_name(&v)
+            ))),
+        }
+    }
+
+    /// The type of the values the expression gives, `column` gives the type of a
+    /// column by name. A NULL literal has no type of its own.
+    pub fn data_type(&self, column: &dyn Fn(&str) -> Option<DataType>) -> Result<DataType, Errors> {
+        match self {
+            Expression::Column(name) => {
+                column(name).ok_or_else(|| Errors::ColumnNotExisted(name.to_string()))
+            }
+            Expression::Literal(value) => Ok(value_type(value)),
+            Expression::Unary {
+                op: UnaryOp::Not, ..
+            } => Ok(DataType::Bool),
+            Expression::Unary { expr, .. } => expr.data_type(column),
+            Expression::Binary { left, op, right } if !op.is_comparison() => {
+                let (left, right) = (left.data_type(column)?, right.data_type(column)?);
+                match (op, left, right) {
+                    (BinaryOp::And | BinaryOp::Or, _, _) => Ok(DataType::Bool),
+                    (BinaryOp::Concat, _, _) => Ok(DataType::String),
+                    (_, DataType::Invalid, t) | (_, t, DataType::Invalid) => Ok(t),
+                    (_, DataType::Int, DataType::Int) => Ok(DataType::Int),
+                    (_, DataType::Int | DataType::Float, DataType::Int | DataType::Float) => {
+                        Ok(DataType::Float)
+                    }
+                    (op, left, right) => {
+                        Err(Errors::TypeMismatch(format!("{} {} {}", left, op, right)))
+                    }
+                }
+            }
+            Expression::Function { name, args } => {
+                let function =
+                    lookup(name).ok_or_else(|| Errors::FunctionNotExisted(name.to_string()))?;
+                let types = args
+                    .iter()
+                    .map(|arg| arg.data_type(column))
+                    .collect::<Result<Vec<DataType>, Errors>>()?;
+                function.return_type(&types)
+            }
+            Expression::Cast { datatype, .. } => Ok(datatype.clone()),
+            Expression::Subquery(_) => Err(Errors::UnimplementedOperation),
+            _ => Ok(DataType::Bool),
+        }
+    }
+}
+
+pub fn value_type(value: &Value) -> DataType {
+    match value {
+        Value::Null => DataType::Invalid,
+        Value::Int(_) => DataType::Int,
+        Value::Float(_) => DataType::Float,
+        Value::Str(_) => DataType::String,
+        Value::Bool(_) => DataType::Bool,
+    }
+}
+
+pub fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Int(_) => "int",
+        Value::Float(_) => "float",
+        Value::Str(_) => "string",
+        Value::Bool(_) => "bool",
+    }
+}
+
+// the truth value of one side of `AND`/`OR`, `None` when unknown
+fn truth(value: Value, op: &BinaryOp) -> Result<Option<bool>, Errors> {
+    match value {
+        Value::Bool(b) => Ok(Some(b)),
+        Value::Null => Ok(None),
+        v => Err(Errors::TypeMismatch(format!("{} {}", op, type_name(&v)))),
+    }
+}
+
+pub fn binary(left: &Value, op: BinaryOp, right: &Value) -> Result<Value, Errors> {
+    if op.is_comparison() {
+        let result = compare(left, right, op)?.map(|ord| match op {
+            BinaryOp::Eq => ord == Ordering::Equal,
+            BinaryOp::NotEq => ord != Ordering::Equal,
+            BinaryOp::Lt => ord == Ordering::Less,
+            BinaryOp::LtEq => ord != Ordering::Greater,
+            BinaryOp::Gt => ord == Ordering::Greater,
+            _ => ord != Ordering::Less,
+        });
+        return Ok(result.map_or(Value::Null, Value::Bool));
+    }
+    let mismatch =
+        || Errors::TypeMismatch(format!("{} {} {}", type_name(left), op, type_name(right)));
+    match (left, right) {
+        (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+        _ if op == BinaryOp::Concat => Ok(Value::Str(format!("{}{}", left, right))),
+        (Value::Int(a), Value::Int(b)) => {
+            let (a, b) = (*a, *b);
+            let result = match op {
+                BinaryOp::Plus => a.checked_add(b),
+                BinaryOp::Minus => a.checked_sub(b),
+                BinaryOp::Multiply => a.checked_mul(b),
+                BinaryOp::Divide | BinaryOp::Modulo if b == 0 => {
+                    return Err(Errors::DivisionByZero)
+                }
+                BinaryOp::Divide => a.checked_div(b),
+                BinaryOp::Modulo => a.checked_rem(b),
+                _ => return Err(mismatch()),
+            };
+            result.map(Value::Int).ok_or(Errors::NumericOverflow)
+        }
+        (Value::Int(_) | Value::Float(_), Value::Int(_) | Value::Float(_)) => {
+            let (a, b) = (left.as_float().unwrap(), right.as_float().unwrap());
+            let result = match op {
+                BinaryOp::Plus => a + b,
+                BinaryOp::Minus => a - b,
+                BinaryOp::Multiply => a * b,
+                BinaryOp::Divide | BinaryOp::Modulo if b == 0.0 => {
+                    return Err(Errors::DivisionByZero)
+                }
+                BinaryOp::Divide => a / b,
+                BinaryOp::Modulo => a % b,
+                _ => return Err(mismatch()),
+            };
+            Ok(Value::Float(result))
+        }
+        _ => Err(mismatch()),
+    }
+}
+
+/// Orders two values for a comparison, `None` when either is NULL. Numbers compare
+/// with each other and with strings holding a number.
+pub fn compare(left: &Value, right: &Value, op: BinaryOp) -> Result<Option<Ordering>, Errors> {
+    let number = |s: &str| s.trim().parse::<f64>().ok().map(Value::Float);
+    let ord = match (left, right) {
+        (Value::Null, _) | (_, Value::Null) => None,
+        (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+        // columns store f32, floats are compared at that precision
+        (Value::Int(_) | Value::Float(_), Value::Int(_) | Value::Float(_)) => {
+            let (a, b) = (left.as_float().unwrap(), right.as_float().unwrap());
+            (a as f32).partial_cmp(&(b as f32))
+        }
+        (Value::Str(a), Value::Str(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::Str(s), Value::Int(_) | Value::Float(_)) if number(s).is_some() => {
+            return compare(&number(s).unwrap(), right, op)
+        }
+        (Value::Int(_) | Value::Float(_), Value::Str(s)) if number(s).is_some() => {
+            return compare(left, &number(s).unwrap(), op)
+        }
+        _ => {
+            return Err(Errors::TypeMismatch(format!(
+                "{} {} {}",
+                type_name(left),
+                op,
+                type_name(right)
+            )))
+        }
+    };
+    Ok(ord)
+}
+
+#[test]
+fn test_eval() {
+    use crate::parser::utils::parse_sql;
+    use sqlparser::ast::{SetExpr, Statement};
+
+    let eval = |sql: &str| {
+        let stat = parse_sql(&format!("SELECT * FROM t WHERE {}", sql)).unwrap();
+        let selection = match stat {
+            Statement::Query(query) => match *query.body {
+                SetExpr::Select(select) => select.selection.unwrap(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        let row = |name: &str| match name {
+            "id" | "t.id" => Some(Value::Int(7)),
+            "price" => Some(Value::Float(2.5)),
+            "name" => Some(Value::Str("a_b".to_string())),
+            "nothing" => Some(Value::Null),
+            _ => None,
+        };
+        Expression::from_expr(&selection)?.eval(&row)
+    };
+    let yes = Value::Bool(true);
+    let no = Value::Bool(false);
+
+    assert_eq!(eval("(id + 3) * 2 % 6").unwrap(), Value::Int(2));
+    assert_eq!(eval("id / 2 + price").unwrap(), Value::Float(5.5));
+    assert_eq!(
+        eval("name || '-' || id").unwrap(),
+        Value::Str("a_b-7".to_string())
+    );
+    assert_eq!(
+        eval("t.id >= 7 AND 3 < id AND id <> price AND NOT id != 7").unwrap(),
+        yes
+    );
+    assert_eq!(
+        eval("id IN (1, 7.0) AND id NOT BETWEEN 1 AND 5").unwrap(),
+        yes
+    );
+    assert_eq!(
+        eval("name LIKE 'a\\_%' ESCAPE '\\' AND name NOT LIKE \"A%\"").unwrap(),
+        yes
+    );
+    assert_eq!(eval("name ILIKE 'A%'").unwrap(), yes);
+    assert_eq!(eval("id = '7'").unwrap(), yes);
+    assert_eq!(
+        eval("UPPER(name) || CAST(id AS TEXT) = 'A_B7' AND TRIM(LEADING 'a' FROM name) = '_b'")
+            .unwrap(),
+        yes
+    );
+    // NULL makes comparisons unknown, AND/OR still decide when they can
+    assert_eq!(eval("nothing = 1").unwrap(), Value::Null);
+    assert_eq!(eval("nothing = 1 OR id = 7").unwrap(), yes);
+    assert_eq!(eval("nothing = 1 AND id = 8").unwrap(), no);
+    assert_eq!(eval("id IN (1, NULL)").unwrap(), Value::Null);
+    assert_eq!(eval("nothing IS NULL AND id IS NOT NULL").unwrap(), yes);
+    assert!(matches!(eval("id / 0"), Err(Errors::DivisionByZero)));
+    assert!(matches!(eval("name > 1"), Err(Errors::TypeMismatch(_))));
+    assert!(matches!(
+        eval("missing = 1"),
+        Err(Errors::ColumnNotExisted(_))
+    ));
+}
@@ -0,0 +1,401 @@
+use crate::database::base::{DataType, Value};
+use crate::database::eval::{binary, compare, value_type};
+use crate::parser::expr::BinaryOp;
+use crate::system::errors::Errors;
+use std::cmp::Ordering;
+
+/// The kind of value an argument takes, NULL fits every kind.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arg {
+    String,
+    Int,
+    Number,
+    Any,
+}
+
+impl Arg {
+    fn fits(&self, datatype: &DataType) -> bool {
+        matches!(
+            (self, datatype),
+            (Arg::Any, _)
+                | (_, DataType::Invalid)
+                | (Arg::String, DataType::String)
+                | (Arg::Int, DataType::Int)
+                | (Arg::Number, DataType::Int | DataType::Float)
+        )
+    }
+}
+
+/// A built-in scalar function. `eval` is only called with arguments that fit `args`
+/// and, for a strict function, none of them NULL.
+pub struct ScalarFunction {
+    pub name: &'static str,
+    /// the last kind repeats for a variadic function
+    pub args: &'static [Arg],
+    /// how many of the last `args` can be left out
+    pub optional: usize,
+    pub variadic: bool,
+    /// a NULL argument makes the result NULL
+    pub strict: bool,
+    /// the result type, `None` when the argument types have nothing in common
+    pub returns: fn(&[DataType]) -> Option<DataType>,
+    pub eval: fn(&[Value]) -> Result<Value, Errors>,
+}
+
+impl ScalarFunction {
+    const fn new(
+        name: &'static str,
+        args: &'static [Arg],
+        returns: fn(&[DataType]) -> Option<DataType>,
+        eval: fn(&[Value]) -> Result<Value, Errors>,
+    ) -> ScalarFunction {
+        ScalarFunction {
+            name,
+            args,
+            optional: 0,
+            variadic: false,
+            strict: true,
+            returns,
+            eval,
+        }
+    }
+
+    const fn optional(mut self, optional: usize) -> ScalarFunction {
+        self.optional = optional;
+        self
+    }
+
+    // takes any number of the last argument and handles NULLs itself
+    const fn variadic(mut self) -> ScalarFunction {
+        self.variadic = true;
+        self.lenient()
+    }
+
+    const fn lenient(mut self) -> ScalarFunction {
+        self.strict = false;
+        self
+    }
+
+    pub fn accepts(&self, count: usize) -> bool {
+        count + self.optional >= self.args.len() && (self.variadic || count <= self.args.len())
+    }
+
+    pub fn return_type(&self, types: &[DataType]) -> Result<DataType, Errors> {
+        let fits = types
+            .iter()
+            .enumerate()
+            .all(|(ix, t)| self.args[ix.min(self.args.len() - 1)].fits(t));
+        fits.then(|| (self.returns)(types))
+            .flatten()
+            .ok_or_else(|| {
+                let types = types.iter().map(|t| t.to_string()).collect::<Vec<String>>();
+                Errors::TypeMismatch(format!("{}({})", self.name, types.join(", ")))
+            })
+    }
+
+    pub fn call(&self, args: &[Value]) -> Result<Value, Errors> {
+        if self.strict && args.iter().any(Value::is_null) {
+            return Ok(Value::Null);
+        }
+        self.return_type(&args.iter().map(value_type).collect::<Vec<DataType>>())?;
+        (self.eval)(args)
+    }
+}
+
+static FUNCTIONS: &[ScalarFunction] = &[
+    ScalarFunction::new("UPPER", &[Arg::String], string, |args| {
+        Ok(Value::Str(text(args, 0).to_uppercase()))
+    }),
+    ScalarFunction::new("LOWER", &[Arg::String], string, |args| {
+        Ok(Value::Str(text(args, 0).to_lowercase()))
+    }),
+    ScalarFunction::new("LENGTH", &[Arg::String], int, |args| {
+        Ok(Value::Int(text(args, 0).chars().count() as i64))
+    }),
+    ScalarFunction::new("SUBSTR", &[Arg::String, Arg::Int, Arg::Int], string, substr).optional(1),
+    ScalarFunction::new(
+        "SUBSTRING",
+        &[Arg::String, Arg::Int, Arg::Int],
+        string,
+        substr,
+    )
+    .optional(1),
+    ScalarFunction::new("TRIM", &[Arg::String, Arg::String], string, |args| {
+        let chars = trimmed_chars(args);
+        Ok(Value::Str(
+            text(args, 0).trim_matches(&chars[..]).to_string(),
+        ))
+    })
+    .optional(1),
+    ScalarFunction::new("LTRIM", &[Arg::String, Arg::String], string, |args| {
+        let chars = trimmed_chars(args);
+        Ok(Value::Str(
+            text(args, 0).trim_start_matches(&chars[..]).to_string(),
+        ))
+    })
+    .optional(1),
+    ScalarFunction::new("RTRIM", &[Arg::String, Arg::String], string, |args| {
+        let chars = trimmed_chars(args);
+        Ok(Value::Str(
+            text(args, 0).trim_end_matches(&chars[..]).to_string(),
+        ))
+    })
+    .optional(1),
+    ScalarFunction::new(
+        "REPLACE",
+        &[Arg::String, Arg::String, Arg::String],
+        string,
+        |args| match text(args, 1) {
+            "" => Ok(args[0].clone()),
+            from => Ok(Value::Str(text(args, 0).replace(from, text(args, 2)))),
+        },
+    ),
+    // NULLs are skipped, like in PostgreSQL
+    ScalarFunction::new("CONCAT", &[Arg::Any], string, |args| {
+        let parts = args.iter().filter(|v| !v.is_null()).map(|v| v.to_string());
+        Ok(Value::Str(parts.collect::<String>()))
+    })
+    .variadic(),
+    ScalarFunction::new("ABS", &[Arg::Number], first, |args| match &args[0] {
+        Value::Int(v) => v
+            .checked_abs()
+            .map(Value::Int)
+            .ok_or(Errors::NumericOverflow),
+        v => Ok(Value::Float(v.as_float().unwrap_or_default().abs())),
+    }),
+    ScalarFunction::new("ROUND", &[Arg::Number, Arg::Int], first, round).optional(1),
+    ScalarFunction::new("FLOOR", &[Arg::Number], first, |args| match &args[0] {
+        Value::Float(v) => Ok(Value::Float(v.floor())),
+        v => Ok(v.clone()),
+    }),
+    ScalarFunction::new("CEIL", &[Arg::Number], first, ceil),
+    ScalarFunction::new("CEILING", &[Arg::Number], first, ceil),
+    ScalarFunction::new("MOD", &[Arg::Number, Arg::Number], common, |args| {
+        binary(&args[0], BinaryOp::Modulo, &args[1])
+    }),
+    ScalarFunction::new("COALESCE", &[Arg::Any], common, coalesce).variadic(),
+    ScalarFunction::new("IFNULL", &[Arg::Any, Arg::Any], common, coalesce).lenient(),
+    ScalarFunction::new(
+        "NULLIF",
+        &[Arg::Any, Arg::Any],
+        common,
+        |args| match compare(&args[0], &args[1], BinaryOp::Eq)? {
+            Some(Ordering::Equal) => Ok(Value::Null),
+            _ => Ok(args[0].clone()),
+        },
+    )
+    .lenient(),
+];
+
+/// The built-in function called `name`, in any case.
+pub fn lookup(name: &str) -> Option<&'static ScalarFunction> {
+    let name = name.to_uppercase();
+    FUNCTIONS.iter().find(|f| f.name == name)
+}
+
+/// Converts `value` to `datatype` for `CAST`.
+pub fn cast(value: Value, datatype: &DataType) -> Result<Value, Errors> {
+    let invalid = |value: &Value| Errors::InvalidCast(format!("{} to {}", value, datatype));
+    let cast = match (&value, datatype) {
+        (Value::Null, _) => Some(Value::Null),
+        (Value::Int(_), DataType::Int)
+        | (Value::Float(_), DataType::Float)
+        | (Value::Str(_), DataType::String)
+        | (Value::Bool(_), DataType::Bool) => Some(value.clone()),
+        (Value::Float(v), DataType::Int) => {
+            let v = v.round();
+            if v < i64::MIN as f64 || v >= i64::MAX as f64 || v.is_nan() {
+                return Err(Errors::NumericOverflow);
+            }
+            Some(Value::Int(v as i64))
+        }
+        (Value::Str(s), DataType::Int) => s.trim().parse::<i64>().ok().map(Value::Int),
+        (Value::Bool(b), DataType::Int) => Some(Value::Int(*b as i64)),
+        (Value::Int(v), DataType::Float) => Some(Value::Float(*v as f64)),
+        (Value::Str(s), DataType::Float) => s.trim().parse::<f64>().ok().map(Value::Float),
+        (Value::Bool(b), DataType::Float) => Some(Value::Float(*b as i64 as f64)),
+        (_, DataType::String) => Some(Value::Str(value.to_string())),
+        (Value::Int(v), DataType::Bool) => Some(Value::Bool(*v != 0)),
+        (Value::Float(v), DataType::Bool) => Some(Value::Bool(*v != 0.0)),
+        (Value::Str(s), DataType::Bool) => match s.trim().to_lowercase().as_str() {
+            "true" | "t" | "yes" | "y" | "on" | "1" => Some(Value::Bool(true)),
+            "false" | "f" | "no" | "n" | "off" | "0" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        (_, DataType::Invalid) => None,
+    };
+    cast.ok_or_else(|| invalid(&value))
+}
+
+fn string(_: &[DataType]) -> Option<DataType> {
+    Some(DataType::String)
+}
+
+fn int(_: &[DataType]) -> Option<DataType> {
+    Some(DataType::Int)
+}
+
+fn first(types: &[DataType]) -> Option<DataType> {
+    types.first().cloned()
+}
+
+// the type all the arguments fit, ints widen to floats
+fn common(types: &[DataType]) -> Option<DataType> {
+    types
+        .iter()
+        .try_fold(DataType::Invalid, |acc, t| match (&acc, t) {
+            (_, DataType::Invalid) => Some(acc),
+            (DataType::Invalid, _) => Some(t.clone()),
+            (DataType::Int | DataType::Float, DataType::Int) => Some(acc),
+            (DataType::Int | DataType::Float, DataType::Float) => Some(DataType::Float),
+            _ if acc == *t => Some(acc),
+            _ => None,
+        })
+}
+
+fn text(args: &[Value], ix: usize) -> &str {
+    args.get(ix).and_then(Value::as_str).unwrap_or_default()
+}
+
+fn trimmed_chars(args: &[Value]) -> Vec<char> {
+    match args.get(1) {
+        Some(v) => v.as_str().unwrap_or_default().chars().collect(),
+        None => vec![' '],
+    }
+}
+
+// 1-based like SQL, a start before the first character shortens the length
+fn substr(args: &[Value]) -> Result<Value, Errors> {
+    let start = args[1].as_int().unwrap_or_default();
+    let end = match args.get(2).and_then(Value::as_int) {
+        Some(len) if len < 0 => return Err(Errors::InvalidExpression),
+        Some(len) => start.saturating_add(len),
+        None => i64::MAX,
+    };
+    let result = text(args, 0)
+        .chars()
+        .zip(1..)
+        .filter(|(_, pos)| *pos >= start && *pos < end)
+        .map(|(c, _)| c)
+        .collect::<String>();
+    Ok(Value::Str(result))
+}
+
+// halves round away from zero, negative digits round to tens, hundreds...
+fn round(args: &[Value]) -> Result<Value, Errors> {
+    let digits = args.get(1).and_then(Value::as_int).unwrap_or(0);
+    match &args[0] {
+        Value::Int(v) if digits >= 0 => Ok(Value::Int(*v)),
+        Value::Int(v) => {
+            let unit = 10i64
+                .checked_pow(digits.unsigned_abs().try_into().unwrap_or(u32::MAX))
+                .ok_or(Errors::NumericOverflow)?;
+            let half = v.signum() * (unit / 2);
+            v.checked_add(half)
+                .map(|v| v / unit * unit)
+                .map(Value::Int)
+                .ok_or(Errors::NumericOverflow)
+        }
+        v => {
+            let unit = 10f64.powi(digits.clamp(-308, 308) as i32);
+            Ok(Value::Float(
+                (v.as_float().unwrap_or_default() * unit).round() / unit,
+            ))
+        }
+    }
+}
+
+fn ceil(args: &[Value]) -> Result<Value, Errors> {
+    match &args[0] {
+        Value::Float(v) => Ok(Value::Float(v.ceil())),
+        v => Ok(v.clone()),
+    }
+}
+
+fn coalesce(args: &[Value]) -> Result<Value, Errors> {
+    let value = args.iter().find(|v| !v.is_null());
+    Ok(value.cloned().unwrap_or(Value::Null))
+}
+
+#[test]
+fn test_scalar_functions() {
+    let call = |name: &str, args: Vec<Value>| lookup(name).unwrap().call(&args);
+    let str = |s: &str| Value::Str(s.to_string());
+
+    assert_eq!(call("upper", vec![str("abc")]).unwrap(), str("ABC"));
+    assert_eq!(call("LENGTH", vec![str("héllo")]).unwrap(), Value::Int(5));
+    let substr = |args: Vec<Value>| call("SUBSTR", args).unwrap();
+    assert_eq!(
+        substr(vec![str("hello"), Value::Int(2), Value::Int(3)]),
+        str("ell")
+    );
+    assert_eq!(
+        substr(vec![str("hello"), Value::Int(0), Value::Int(2)]),
+        str("h")
+    );
+    assert_eq!(substr(vec![str("hello"), Value::Int(4)]), str("lo"));
+    assert_eq!(
+        call("TRIM", vec![str("xxaxx"), str("x")]).unwrap(),
+        str("a")
+    );
+    assert_eq!(call("RTRIM", vec![str(" a ")]).unwrap(), str(" a"));
+    assert_eq!(
+        call("REPLACE", vec![str("a-b-c"), str("-"), str("+")]).unwrap(),
+        str("a+b+c")
+    );
+    assert_eq!(
+        call("CONCAT", vec![str("a"), Value::Null, Value::Int(1)]).unwrap(),
+        str("a1")
+    );
+    assert_eq!(call("ABS", vec![Value::Int(-3)]).unwrap(), Value::Int(3));
+    assert_eq!(
+        call("ROUND", vec![Value::Float(2.345), Value::Int(2)]).unwrap(),
+        Value::Float(2.35)
+    );
+    assert_eq!(
+        call("ROUND", vec![Value::Int(-1250), Value::Int(-2)]).unwrap(),
+        Value::Int(-1300)
+    );
+    assert_eq!(
+        call("CEIL", vec![Value::Float(1.2)]).unwrap(),
+        Value::Float(2.0)
+    );
+    assert_eq!(
+        call("MOD", vec![Value::Int(7), Value::Int(3)]).unwrap(),
+        Value::Int(1)
+    );
+    assert_eq!(
+        call("COALESCE", vec![Value::Null, Value::Int(2), Value::Int(3)]).unwrap(),
+        Value::Int(2)
+    );
+    assert_eq!(
+        call("NULLIF", vec![Value::Int(2), Value::Float(2.0)]).unwrap(),
+        Value::Null
+    );
+    assert_eq!(call("UPPER", vec![Value::Null]).unwrap(), Value::Null);
+    assert!(matches!(
+        call("UPPER", vec![Value::Int(1)]),
+        Err(Errors::TypeMismatch(_))
+    ));
+    assert!(matches!(
+        call("COALESCE", vec![Value::Int(1), str("a")]),
+        Err(Errors::TypeMismatch(_))
+    ));
+    assert!(lookup("SUBSTR").unwrap().accepts(2) && !lookup("SUBSTR").unwrap().accepts(4));
+    assert!(lookup("COALESCE").unwrap().accepts(5) && !lookup("COALESCE").unwrap().accepts(0));
+
+    assert_eq!(cast(str(" 42 "), &DataType::Int).unwrap(), Value::Int(42));
+    assert_eq!(
+        cast(Value::Float(2.5), &DataType::Int).unwrap(),
+        Value::Int(3)
+    );
+    assert_eq!(cast(Value::Int(1), &DataType::String).unwrap(), str("1"));
+    assert_eq!(
+        cast(str("yes"), &DataType::Bool).unwrap(),
+        Value::Bool(true)
+    );
+    assert!(matches!(
+        cast(str("abc"), &DataType::Float),
+        Err(Errors::InvalidCast(_))
+    ));
+}
@@ -0,0 +1,680 @@
+use crate::database::base::{ColumnAttr, DataType, Value};
+use crate::database::mvcc::Snapshot;
+use crate::database::table::{Rows, Table};
+use crate::parser::expr::{BinaryOp, Expression};
+use crate::parser::join::JoinType;
+use crate::system::errors::Errors;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How a join on equal columns matches its rows. Joins without such columns compare
+/// every pair of rows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinStrategy {
+    /// builds a hash table of the smaller side and probes it with the other one
+    Hash,
+    /// sorts both sides by the join columns and merges them
+    Merge,
+}
+
+static STRATEGY: AtomicU8 = AtomicU8::new(JoinStrategy::Hash as u8);
+
+pub fn set_strategy(strategy: JoinStrategy) {
+    STRATEGY.store(strategy as u8, Ordering::Relaxed);
+}
+
+fn strategy() -> JoinStrategy {
+    match STRATEGY.load(Ordering::Relaxed) {
+        v if v == JoinStrategy::Merge as u8 => JoinStrategy::Merge,
+        _ => JoinStrategy::Hash,
+    }
+}
+
+/// The rows of a join kept as the row every table gives to them, `None` where an
+/// outer join pads a table with NULLs. A select reads the values from the tables.
+pub struct JoinRows<'a> {
+    /// the tables with the name their columns are qualified with
+    tables: Vec<(String, &'a Table)>,
+    /// one row index per table for each joint row
+    rows: Vec<Option<usize>>,
+    /// the columns of every table, named `<table>.<column>` once there are several
+    columns: Vec<ColumnAttr>,
+    /// the table and the column every one of `columns` reads
+    sources: Vec<Source<'a>>,
+    /// the names of the tables joined by `-`
+    name: String,
+}
+
+// a column of the joint rows, the position of its table and its name there
+type Source<'a> = (usize, &'a str);
+
+// a join column value normalized so that equal values compare and hash the same,
+// numbers of a float column are compared at its f32 precision
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum KeyPart {
+    Int(i64),
+    Float(u32),
+    Str(String),
+    Bool(bool),
+}
+
+impl<'a> JoinRows<'a> {
+    /// The rows of `tb` visible to `snapshot`, its columns also read as `<name>.<column>`.
+    pub fn new(name: &str, tb: &'a Table, snapshot: &Snapshot) -> JoinRows<'a> {
+        let rows = tb.scan_rows(&None, None, snapshot);
+        JoinRows::with_columns(
+            vec![(name.to_string(), tb)],
+            rows.flatten().map(Some).collect(),
+        )
+    }
+
+    // the columns of `tables`, nullable where an outer join padded the table
+    fn with_columns(tables: Vec<(String, &'a Table)>, rows: Vec<Option<usize>>) -> JoinRows<'a> {
+        let width = tables.len();
+        let mut columns = vec![];
+        let mut sources = vec![];
+        for (ix, (name, tb)) in tables.iter().enumerate() {
+            let padded = rows.iter().skip(ix).step_by(width).any(Option::is_none);
+            for col in &tb.columns {
+                columns.push(ColumnAttr {
+                    name: match width {
+                        1 => col.name.to_string(),
+                        _ => format!("{}.{}", name, col.name),
+                    },
+                    is_nullable: col.is_nullable || padded,
+                    ..col.clone()
+                });
+                sources.push((ix, col.name.as_str()));
+            }
+        }
+        let names = tables.iter().map(|(name, _)| name.as_str());
+        JoinRows {
+            name: names.collect::<Vec<&str>>().join("-"),
+            tables,
+            rows,
+            columns,
+            sources,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len() / self.tables.len()
+    }
+
+    fn row(&self, pos: usize) -> &[Option<usize>] {
+        let width = self.tables.len();
+        &self.rows[pos * width..(pos + 1) * width]
+    }
+
+    // `<table>.<column>`, or a column only one of the tables has
+    fn resolve(&self, name: &str) -> Option<Source<'a>> {
+        if let Some((tb, col)) = name.split_once('.') {
+            if let Some(ix) = self.tables.iter().position(|(n, _)| n == tb) {
+                return self.tables[ix].1.resolve_column(col).map(|c| (ix, c));
+            }
+        }
+        let mut found = self.unqualified(name);
+        match (found.next(), found.next()) {
+            (Some(v), None) => Some(v),
+            _ => None,
+        }
+    }
+
+    // the columns of any of the tables `name` is without a table qualifying it,
+    // derived tables may have dots in the names of their columns
+    fn unqualified<'n>(&'n self, name: &'n str) -> impl Iterator<Item = Source<'a>> + 'n {
+        self.tables
+            .iter()
+            .enumerate()
+            .filter_map(move |(ix, (_, tb))| {
+                let tb: &'a Table = tb;
+                match name.contains('.') {
+                    true => tb
+                        .columns
+                        .iter()
+                        .find(|c| c.name == name)
+                        .map(|c| c.name.as_str()),
+                    false => tb.resolve_column(name),
+                }
+                .map(|c| (ix, c))
+            })
+    }
+
+    fn value(&self, row: &[Option<usize>], (tb, col): Source) -> Value {
+        match row[tb] {
+            Some(ix) => self.tables[tb].1.col_map.get(col).unwrap().get_value(ix),
+            None => Value::Null,
+        }
+    }
+
+    fn datatype(&self, (tb, col): Source) -> Option<DataType> {
+        self.tables[tb].1.column_type(col)
+    }
+
+    /// Joins the rows of `right` that satisfy `on`, all of them without it. Equal
+    /// columns of the two sides are matched by the configured `JoinStrategy` and the
+    /// rest of `on` is checked for the matching pairs only.
+    pub fn join(
+        self,
+        right: JoinRows<'a>,
+        join_type: &JoinType,
+        on: &Option<Expression>,
+    ) -> Result<JoinRows<'a>, Errors> {
+        if let Some((name, _)) = right
+            .tables
+            .iter()
+            .find(|(name, _)| self.tables.iter().any(|(n, _)| n == name))
+        {
+            return Err(Errors::TableExisted(name.to_string()));
+        }
+        let split = self.tables.len();
+        let left = self;
+        let tables = left.tables.iter().chain(&right.tables).cloned().collect();
+        let joint = JoinRows::with_columns(tables, vec![]);
+
+        let mut conditions = vec![];
+        if let Some(on) = on {
+            conjuncts(on, &mut conditions);
+        }
+        let mut keys = vec![];
+        let mut rest = vec![];
+        for cond in conditions {
+            match joint.join_key(cond, split) {
+                Some(key) => keys.push(key),
+                None => rest.push(cond),
+            }
+        }
+        // `rest` reads a column from the side it comes from
+        let value = |l: usize, r: usize, name: &str| {
+            joint.resolve(name).map(|(tb, col)| match tb < split {
+                true => left.value(left.row(l), (tb, col)),
+                false => right.value(right.row(r), (tb - split, col)),
+            })
+        };
+        let mut pairs = vec![];
+        let mut check = |l: usize, r: usize| -> Result<(), Errors> {
+            for cond in &rest {
+                let matched = cond.is_true(&|name| value(l, r, name));
+                if !matched.map_err(|err| joint.column_error(err))? {
+                    return Ok(());
+                }
+            }
+            pairs.push((l, r));
+            Ok(())
+        };
+
+        if keys.is_empty() {
+            for l in 0..left.len() {
+                for r in 0..right.len() {
+                    check(l, r)?;
+                }
+            }
+        } else {
+            let left_keys = (0..left.len())
+                .map(|l| key_of(&left, l, keys.iter().map(|(s, _, kind)| (*s, kind))))
+                .collect::<Vec<Option<Vec<KeyPart>>>>();
+            let right_keys = (0..right.len())
+                .map(|r| {
+                    let sources = keys
+                        .iter()
+                        .map(|(_, (tb, col), kind)| ((tb - split, *col), kind));
+                    key_of(&right, r, sources)
+                })
+                .collect::<Vec<Option<Vec<KeyPart>>>>();
+            match strategy() {
+                JoinStrategy::Hash => hash_join(&left_keys, &right_keys, &mut check)?,
+                JoinStrategy::Merge => merge_join(left_keys, right_keys, &mut check)?,
+            }
+            pairs.sort_unstable();
+        }
+
+        let keep_left = matches!(join_type, JoinType::Left | JoinType::FullOuter);
+        let keep_right = matches!(join_type, JoinType::Right | JoinType::FullOuter);
+        let right_nulls = vec![None; right.tables.len()];
+        let mut right_matched = vec![false; right.len()];
+        let mut rows = vec![];
+        let mut pairs = pairs.into_iter().peekable();
+        for l in 0..left.len() {
+            let mut matched = false;
+            while let Some((_, r)) = pairs.next_if(|(pl, _)| *pl == l) {
+                matched = true;
+                right_matched[r] = true;
+                rows.extend_from_slice(left.row(l));
+                rows.extend_from_slice(right.row(r));
+            }
+            if keep_left && !matched {
+                rows.extend_from_slice(left.row(l));
+                rows.extend_from_slice(&right_nulls);
+            }
+        }
+        if keep_right {
+            for r in (0..right.len()).filter(|r| !right_matched[*r]) {
+                rows.extend(std::iter::repeat_n(None, split));
+                rows.extend_from_slice(right.row(r));
+            }
+        }
+        Ok(JoinRows::with_columns(joint.tables, rows))
+    }
+
+    // `left = right` of columns of either side whose values can be matched as keys
+    fn join_key(
+        &self,
+        cond: &Expression,
+        split: usize,
+    ) -> Option<(Source<'a>, Source<'a>, DataType)> {
+        let (a, b) = match cond {
+            Expression::Binary {
+                left,
+                op: BinaryOp::Eq,
+                right,
+            } => match (left.as_ref(), right.as_ref()) {
+                (Expression::Column(a), Expression::Column(b)) => {
+                    (self.resolve(a)?, self.resolve(b)?)
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let (a, b) = match (a.0 < split, b.0 < split) {
+            (true, false) => (a, b),
+            (false, true) => (b, a),
+            _ => return None,
+        };
+        let kind = match (self.datatype(a)?, self.datatype(b)?) {
+            (DataType::Int, DataType::Int) => DataType::Int,
+            (DataType::Int | DataType::Float, DataType::Int | DataType::Float) => DataType::Float,
+            (DataType::String, DataType::String) => DataType::String,
+            (DataType::Bool, DataType::Bool) => DataType::Bool,
+            // strings holding numbers and the like are compared pair by pair
+            _ => return None,
+        };
+        Some((a, b, kind))
+    }
+}
+
+impl Rows for JoinRows<'_> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn columns(&self) -> &[ColumnAttr] {
+        &self.columns
+    }
+
+    fn resolve_column(&self, name: &str) -> Option<&str> {
+        let source = self.resolve(name)?;
+        let ix = self.sources.iter().position(|s| *s == source)?;
+        Some(&self.columns[ix].name)
+    }
+
+    fn value_of(&self, row_ix: usize, name: &str) -> Option<Value> {
+        let source = self.resolve(name)?;
+        Some(self.value(self.row(row_ix), source))
+    }
+
+    fn is_ambiguous(&self, name: &str) -> bool {
+        self.resolve(name).is_none() && self.unqualified(name).nth(1).is_some()
+    }
+
+    // the rows of the tables were taken for the snapshot of the join
+    fn visible_rows<'b>(
+        &'b self,
+        candidates: Option<Vec<usize>>,
+        _: &'b Snapshot,
+    ) -> Box<dyn Iterator<Item = usize> + 'b> {
+        Box::new(
+            candidates
+                .unwrap_or_else(|| (0..self.len()).collect())
+                .into_iter(),
+        )
+    }
+
+    fn row_values(&self, row_ix: usize) -> Vec<Value> {
+        let row = self.row(row_ix);
+        self.sources
+            .iter()
+            .map(|source| self.value(row, *source))
+            .collect::<Vec<Value>>()
+    }
+}
+
+// the conditions `expr` makes with `AND`
+fn conjuncts<'e>(expr: &'e Expression, conditions: &mut Vec<&'e Expression>) {
+    match expr {
+        Expression::Binary {
+            left,
+            op: BinaryOp::And,
+            right,
+        } => {
+            conjuncts(left, conditions);
+            conjuncts(right, conditions);
+        }
+        _ => conditions.push(expr),
+    }
+}
+
+// the key of row `pos`, `None` when a part is NULL as NULLs never match
+fn key_of<'s>(
+    rows: &JoinRows,
+    pos: usize,
+    sources: impl Iterator<Item = (Source<'s>, &'s DataType)>,
+) -> Option<Vec<KeyPart>> {
+    sources
+        .map(
+            |(source, kind)| match (rows.value(rows.row(pos), source), kind) {
+                (Value::Int(v), DataType::Int) => Some(KeyPart::Int(v)),
+                (v @ (Value::Int(_) | Value::Float(_)), DataType::Float) => {
+                    let v = v.as_float()? as f32;
+                    // -0 equals 0, the bits are flipped so that they sort like the numbers
+                    let bits = match v {
+                        v if v.is_nan() => return None,
+                        0.0 => 0f32.to_bits(),
+                        v => v.to_bits(),
+                    };
+                    Some(KeyPart::Float(match bits >> 31 {
+                        1 => !bits,
+                        _ => bits | 1 << 31,
+                    }))
+                }
+                (Value::Str(v), _) => Some(KeyPart::Str(v)),
+                (Value::Bool(v), _) => Some(KeyPart::Bool(v)),
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+type Keys = Vec<Option<Vec<KeyPart>>>;
+
+fn hash_join(
+    left: &Keys,
+    right: &Keys,
+    check: &mut impl FnMut(usize, usize) -> Result<(), Errors>,
+) -> Result<(), Errors> {
+    let build_left = left.len() <= right.len();
+    let (build, probe) = match build_left {
+        true => (left, right),
+        false => (right, left),
+    };
+    let mut table: HashMap<&Vec<KeyPart>, Vec<usize>> = HashMap::new();
+    for (pos, key) in build.iter().enumerate() {
+        if let Some(key) = key {
+            table.entry(key).or_default().push(pos);
+        }
+    }
+    for (pos, key) in probe.iter().enumerate() {
+        let matches = match key.as_ref().and_then(|key| table.get(key)) {
+            Some(v) => v,
+            None => continue,
+        };
+        for other in matches {
+            match build_left {
+                true => check(*other, pos)?,
+                false => check(pos, *other)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+fn merge_join(
+    left: Keys,
+    right: Keys,
+    check: &mut impl FnMut(usize, usize) -> Result<(), Errors>,
+) -> Result<(), Errors> {
+    let sorted = |keys: Keys| {
+        let mut keys = keys
+            .into_iter()
+            .enumerate()
+            .filter_map(|(pos, key)| key.map(|key| (key, pos)))
+            .collect::<Vec<(Vec<KeyPart>, usize)>>();
+        keys.sort_unstable();
+        keys
+    };
+    let (left, right) = (sorted(left), sorted(right));
+    let (mut l, mut r) = (0, 0);
+    while l < left.len() && r < right.len() {
+        match left[l].0.cmp(&right[r].0) {
+            std::cmp::Ordering::Less => l += 1,
+            std::cmp::Ordering::Greater => r += 1,
+            std::cmp::Ordering::Equal => {
+                let l_end = l + left[l..].iter().take_while(|k| k.0 == left[l].0).count();
+                let r_end = r + right[r..].iter().take_while(|k| k.0 == right[r].0).count();
+                for (_, lp) in &left[l..l_end] {
+                    for (_, rp) in &right[r..r_end] {
+                        check(*lp, *rp)?;
+                    }
+                }
+                (l, r) = (l_end, r_end);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_join_rows() {
+    use crate::parser::create::CreateQuery;
+    use crate::parser::utils::parse_sql;
+    use sqlparser::ast::{SetExpr, Statement};
+
+    let table = |sql: &str, rows: Vec<Vec<Value>>| {
+        let mut tb = Table::new(CreateQuery::format_stat(parse_sql(sql).unwrap()).unwrap());
+        rows.into_iter().for_each(|row| tb.push_values(row));
+        tb
+    };
+    let (int, null) = (Value::Int, Value::Null);
+    let users = table(
+        "CREATE TABLE users (id INT PRIMARY KEY, name TEXT)",
+        vec![
+            vec![int(1), Value::Str("ann".to_string())],
+            vec![int(2), Value::Str("bob".to_string())],
+        ],
+    );
+    let orders = table(
+        "CREATE TABLE orders (id INT PRIMARY KEY, user_id INT)",
+        vec![
+            vec![int(10), int(1)],
+            vec![int(11), int(3)],
+            vec![int(12), null.clone()],
+        ],
+    );
+    let prices = table(
+        "CREATE TABLE prices (id FLOAT, price INT)",
+        vec![
+            vec![Value::Float(1.0), int(5)],
+            vec![Value::Float(2.5), int(6)],
+        ],
+    );
+    let snapshot = Snapshot { txn: 1, csn: 0 };
+    let on = |sql: &str| {
+        let stat = parse_sql(&format!("SELECT * FROM t WHERE {}", sql)).unwrap();
+        match stat {
+            Statement::Query(query) => match *query.body {
+                SetExpr::Select(select) => Expression::from_expr(&select.selection.unwrap()).ok(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    };
+    let join = |right: (&str, &Table), join_type: JoinType, cond: &str| {
+        let joint = JoinRows::new("u", &users, &snapshot)
+            .join(
+                JoinRows::new(right.0, right.1, &snapshot),
+                &join_type,
+                &on(cond),
+            )
+            .unwrap();
+        (0..joint.len())
+            .map(|pos| {
+                (
+                    joint.row_values(pos)[0].clone(),
+                    joint.row_values(pos)[2].clone(),
+                )
+            })
+            .collect::<Vec<(Value, Value)>>()
+    };
+
+    for strategy in [JoinStrategy::Merge, JoinStrategy::Hash] {
+        set_strategy(strategy);
+        let cond = "u.id = user_id";
+        assert_eq!(
+            join(("o", &orders), JoinType::Inner, cond),
+            vec![(int(1), int(10))]
+        );
+        assert_eq!(
+            join(("o", &orders), JoinType::Left, cond),
+            vec![(int(1), int(10)), (int(2), null.clone())]
+        );
+        assert_eq!(
+            join(("o", &orders), JoinType::Right, cond),
+            vec![
+                (int(1), int(10)),
+                (null.clone(), int(11)),
+                (null.clone(), int(12))
+            ]
+        );
+        assert_eq!(
+            join(("o", &orders), JoinType::FullOuter, cond),
+            vec![
+                (int(1), int(10)),
+                (int(2), null.clone()),
+                (null.clone(), int(11)),
+                (null.clone(), int(12))
+            ]
+        );
+        assert_eq!(
+            join(("o", &orders), JoinType::Inner, "o.id > 10 AND u.id = 2").len(),
+            2
+        );
+        // keys are compared by value, not by how they are written
+        assert_eq!(
+            join(("p", &prices), JoinType::Left, "p.id = u.id"),
+            vec![(int(1), Value::Float(1.0)), (int(2), null.clone())]
+        );
+    }
+
+    // a chained join reads the columns of every table before it
+    let tags = table(
+        "CREATE TABLE tags (id INT PRIMARY KEY, tag TEXT)",
+        vec![vec![int(10), Value::Str("new".to_string())]],
+    );
+    let result = JoinRows::new("u", &users, &snapshot)
+        .join(
+            JoinRows::new("o", &orders, &snapshot),
+            &JoinType::Left,
+            &on("u.id = user_id"),
+        )
+        .and_then(|joint| {
+            joint.join(
+                JoinRows::new("tags", &tags, &snapshot),
+                &JoinType::Inner,
+                &on("o.id = tags.id"),
+            )
+        })
+        .unwrap();
+    assert_eq!(result.columns().len(), 6);
+    assert_eq!(result.row_values(0)[5], Value::Str("new".to_string()));
+    assert!(matches!(
+        JoinRows::new("u", &users, &snapshot).join(
+            JoinRows::new("u", &users, &snapshot),
+            &JoinType::Inner,
+            &None
+        ),
+        Err(Errors::TableExisted(_))
+    ));
+}
+
+#[test]
+fn test_join_tbs() {
+    use crate::database::db::Database;
+    use crate::parser::create::CreateQuery;
+    use crate::parser::select::SelectQuery;
+    use crate::parser::utils::parse_sql;
+
+    let table = |sql: &str, rows: Vec<Vec<Value>>| {
+        let mut tb = Table::new(CreateQuery::format_stat(parse_sql(sql).unwrap()).unwrap());
+        rows.into_iter().for_each(|row| tb.push_values(row));
+        tb
+    };
+    let (int, null) = (Value::Int, Value::Null);
+    let mut db = Database::new();
+    db.tables.push(table(
+        "CREATE TABLE users (id INT PRIMARY KEY, name TEXT)",
+        vec![
+            vec![int(1), Value::Str("ann".to_string())],
+            vec![int(2), Value::Str("bob".to_string())],
+        ],
+    ));
+    db.tables.push(table(
+        "CREATE TABLE orders (id INT PRIMARY KEY, user_id INT)",
+        vec![
+            vec![int(10), int(1)],
+            vec![int(11), int(3)],
+            vec![int(12), null.clone()],
+        ],
+    ));
+    db.tables.push(table(
+        "CREATE TABLE tags (id INT PRIMARY KEY, tag TEXT)",
+        vec![vec![int(10), Value::Str("new".to_string())]],
+    ));
+    let snapshot = Snapshot { txn: 1, csn: 0 };
+    let from = |sql: &str| {
+        let sql = format!("SELECT * FROM {}", sql);
+        SelectQuery::format_stat(parse_sql(&sql).unwrap())
+            .unwrap()
+            .from
+    };
+    let join = |sql: &str| {
+        let joint = db.join_tables(&from(sql), &[], &snapshot).unwrap();
+        (0..joint.len())
+            .map(|pos| {
+                (
+                    joint.row_values(pos)[0].clone(),
+                    joint.row_values(pos)[2].clone(),
+                )
+            })
+            .collect::<Vec<(Value, Value)>>()
+    };
+
+    assert_eq!(
+        join("users u JOIN orders o ON u.id = user_id"),
+        vec![(int(1), int(10))]
+    );
+    assert_eq!(
+        join("users u LEFT JOIN orders o ON u.id = user_id"),
+        vec![(int(1), int(10)), (int(2), null.clone())]
+    );
+    assert_eq!(
+        join("users u RIGHT JOIN orders o ON u.id = user_id"),
+        vec![
+            (int(1), int(10)),
+            (null.clone(), int(11)),
+            (null.clone(), int(12))
+        ]
+    );
+    assert_eq!(
+        join("users u FULL JOIN orders o ON u.id = user_id"),
+        vec![
+            (int(1), int(10)),
+            (int(2), null.clone()),
+            (null.clone(), int(11)),
+            (null.clone(), int(12))
+        ]
+    );
+    assert_eq!(
+        join("users u JOIN orders o ON o.id > 10 AND u.id = 2").len(),
+        2
+    );
+
+    // a chained join reads the columns of every table before it
+    let from_list =
+        from("users u LEFT JOIN orders o ON u.id = user_id JOIN tags ON o.id = tags.id");
+    let result = db.join_tables(&from_list, &[], &snapshot).unwrap();
+    assert_eq!(result.columns().len(), 6);
+    assert_eq!(result.row_values(0)[5], Value::Str("new".to_string()));
+    assert!(matches!(
+        db.join_tables(&from("users JOIN users ON true"), &[], &snapshot),
+        Err(Errors::TableExisted(_))
+    ));
+}
@@ -1,3 +1,11 @@
+pub(crate) mod aggregate;
 pub(crate) mod base;
 pub(crate) mod db;
+pub(crate) mod eval;
+pub(crate) mod function;
+pub(crate) mod join;
+pub(crate) mod mvcc;
+pub(crate) mod query;
+pub(crate) mod result;
+pub(crate) mod session;
 pub(crate) mod table;
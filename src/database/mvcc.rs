@@ -0,0 +1,160 @@
+use crate::storage::heap::RowSlot;
+use crate::storage::wal::Mutation;
+use std::collections::HashMap;
+
+/// Transaction id stamped on the row versions a transaction creates or deletes.
+pub type TxnId = u64;
+/// Commit sequence number, increases by one with every commit.
+pub type Csn = u64;
+
+/// Creator of the rows read from disk, visible to every snapshot.
+pub const FROZEN: TxnId = 0;
+/// Commit number of a version whose creator or deleter has not committed.
+pub const PENDING: Csn = Csn::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum IsolationLevel {
+    /// Every statement sees the rows committed before it started.
+    #[default]
+    ReadCommitted,
+    /// Every statement of a transaction sees the rows committed before it began.
+    Snapshot,
+}
+
+/// What a statement is allowed to see: the rows committed up to `csn` and the
+/// transaction's own changes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Snapshot {
+    pub txn: TxnId,
+    pub csn: Csn,
+}
+
+impl Snapshot {
+    pub fn sees(&self, row: &RowSlot) -> bool {
+        let created = row.xmin == self.txn || row.xmin_csn <= self.csn;
+        let deleted = row.xmax != FROZEN && (row.xmax == self.txn || row.xmax_csn <= self.csn);
+        created && !deleted
+    }
+}
+
+/// A change made by an open transaction, undone on rollback.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Undo {
+    Created { table: String, rid: u64 },
+    Deleted { table: String, rid: u64 },
+}
+
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub isolation: IsolationLevel,
+    pub snapshot: Snapshot,
+    // started by BEGIN, otherwise it only spans the current statement
+    pub explicit: bool,
+    pub undo: Vec<Undo>,
+    pub mutations: Vec<Mutation>,
+    // name, undo length and mutation count at the time the savepoint was taken
+    pub savepoints: Vec<(String, usize, usize)>,
+}
+
+impl Transaction {
+    pub fn id(&self) -> TxnId {
+        self.snapshot.txn
+    }
+}
+
+/// Transaction bookkeeping shared by every session of a database.
+#[derive(Debug)]
+pub struct Transactions {
+    next_id: TxnId,
+    pub csn: Csn,
+    // open transactions and the commit number their current snapshot reads at
+    active: HashMap<TxnId, Csn>,
+}
+
+impl Default for Transactions {
+    fn default() -> Self {
+        Transactions {
+            next_id: FROZEN + 1,
+            csn: 0,
+            active: HashMap::new(),
+        }
+    }
+}
+
+impl Transactions {
+    pub fn begin(&mut self, isolation: IsolationLevel, explicit: bool) -> Transaction {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.active.insert(id, self.csn);
+        Transaction {
+            isolation,
+            snapshot: Snapshot {
+                txn: id,
+                csn: self.csn,
+            },
+            explicit,
+            undo: vec![],
+            mutations: vec![],
+            savepoints: vec![],
+        }
+    }
+
+    /// Moves the snapshot of a READ COMMITTED transaction to the latest commit, called
+    /// before each of its statements.
+    pub fn refresh(&mut self, txn: &mut Transaction) {
+        if txn.isolation == IsolationLevel::ReadCommitted {
+            txn.snapshot.csn = self.csn;
+            self.active.insert(txn.id(), self.csn);
+        }
+    }
+
+    pub fn end(&mut self, txn: &Transaction) {
+        self.active.remove(&txn.id());
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    /// Versions deleted at or before this commit number are invisible to every open
+    /// transaction and can be dropped.
+    pub fn horizon(&self) -> Csn {
+        self.active.values().copied().min().unwrap_or(self.csn)
+    }
+}
+
+#[test]
+fn test_snapshot_visibility() {
+    let mut txns = Transactions::default();
+    let writer = txns.begin(IsolationLevel::ReadCommitted, true);
+    let reader = txns.begin(IsolationLevel::Snapshot, true);
+    let mut row = RowSlot {
+        rid: 1,
+        loc: None,
+        dirty: true,
+        xmin: writer.id(),
+        xmin_csn: PENDING,
+        xmax: FROZEN,
+        xmax_csn: PENDING,
+    };
+    assert!(writer.snapshot.sees(&row));
+    assert!(!reader.snapshot.sees(&row));
+
+    txns.csn += 1;
+    row.xmin_csn = txns.csn;
+    txns.end(&writer);
+    assert!(!reader.snapshot.sees(&row));
+    let deleter = txns.begin(IsolationLevel::ReadCommitted, false);
+    let mut other = txns.begin(IsolationLevel::ReadCommitted, false);
+    assert!(deleter.snapshot.sees(&row));
+
+    row.xmax = deleter.id();
+    assert!(!deleter.snapshot.sees(&row));
+    assert!(other.snapshot.sees(&row));
+    txns.csn += 1;
+    row.xmax_csn = txns.csn;
+    txns.end(&deleter);
+    txns.refresh(&mut other);
+    assert!(!other.snapshot.sees(&row));
+    assert_eq!(txns.horizon(), 0);
+}
@@ -0,0 +1,785 @@
+use crate::database::base::{ColumnAttr, DataType, Value};
+use crate::database::db::Database;
+use crate::database::mvcc::Snapshot;
+use crate::database::result::{ResultSet, RowKey};
+use crate::database::table::{Rows, Table};
+use crate::parser::expr::Expression;
+use crate::parser::join::FromType;
+use crate::parser::select::{
+    CommonTable, OrderBy, OrderKey, Projection, SelectQuery, SetOp, SetOperation,
+};
+use crate::system::errors::Errors;
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::iter;
+use std::mem;
+use std::sync::atomic::{self, AtomicUsize};
+
+// rounds of a recursive query that may add rows, like `cte_max_recursion_depth` of MySQL
+static MAX_RECURSION: AtomicUsize = AtomicUsize::new(1000);
+
+pub fn set_max_recursion(rounds: usize) {
+    MAX_RECURSION.store(rounds, atomic::Ordering::Relaxed);
+}
+
+impl Database {
+    /// Runs a select query against the rows visible to `snapshot`, an index of its
+    /// table narrows down the rows it scans.
+    pub fn select(&mut self, query: SelectQuery, snapshot: &Snapshot) -> Result<ResultSet, Errors> {
+        let candidates = match query.from.as_slice() {
+            [FromType::String { tb, alias: None }]
+                if !query.derived.iter().any(|(name, _)| name == tb)
+                    && !query.with.iter().any(|cte| &cte.name == tb) =>
+            {
+                self.index_candidates(tb, &query.condition).unwrap_or(None)
+            }
+            _ => None,
+        };
+        self.run_select(query, candidates, &|_| None, &[], snapshot)
+    }
+
+    // `outer` gives the columns of the rows a subquery is run for, `named` the tables
+    // of the `WITH` of the enclosing queries
+    fn run_select(
+        &self,
+        mut query: SelectQuery,
+        candidates: Option<Vec<usize>>,
+        outer: &dyn Fn(&str) -> Option<Value>,
+        named: &[&Table],
+        snapshot: &Snapshot,
+    ) -> Result<ResultSet, Errors> {
+        // a query of `WITH` runs once, it reads the ones before it
+        let mut ctes = vec![];
+        for cte in mem::take(&mut query.with) {
+            let visible = ctes.iter().rev().chain(named.iter().copied());
+            let tb = self.common_table(cte, outer, &visible.collect::<Vec<&Table>>(), snapshot)?;
+            ctes.push(tb);
+        }
+        let visible = ctes.iter().rev().chain(named.iter().copied());
+        let named = visible.collect::<Vec<&Table>>();
+        if let Some(set) = query.set_operation.take() {
+            let rows = self.combine(*set, outer, &named, snapshot)?;
+            return order_and_limit(rows, &query);
+        }
+        let mut derived = vec![];
+        for (name, subquery) in &query.derived {
+            let rows = self.run_select(subquery.clone(), None, outer, &named, snapshot)?;
+            derived.push(Table::from_rows(name, rows));
+        }
+        let tables = derived.iter().chain(named.iter().copied());
+        let tables = tables.collect::<Vec<&Table>>();
+        let joint;
+        let empty;
+        let source: &dyn Rows = match query.from.as_slice() {
+            [FromType::String { tb, alias: None }] => self.query_table(tb, &tables)?,
+            // a query without `FROM` gives one row
+            [] => {
+                let mut tb = Table::derived("");
+                tb.push_values(vec![]);
+                empty = tb;
+                &empty
+            }
+            // a single table keeps its column names under an alias
+            from => {
+                joint = self.join_tables(from, &tables, snapshot)?;
+                &joint
+            }
+        };
+
+        // the columns of the enclosing rows are constants of this query
+        let constant = |name: &str| match source.resolve_column(name) {
+            Some(_) => None,
+            None if source.is_ambiguous(name) => None,
+            None => outer(name),
+        };
+        let mut correlated = vec![];
+        let exprs = query.projection.iter_mut().filter_map(|proj| match proj {
+            Projection::Expr { expr, .. } => Some(expr),
+            Projection::Wildcard(_) => None,
+        });
+        for expr in query.condition.iter_mut().chain(exprs) {
+            substitute(expr, &constant);
+            self.bind(expr, source, outer, &named, &mut correlated, snapshot)?;
+        }
+        if let Some(having) = &mut query.having {
+            substitute(having, &constant);
+            let mut grouped = vec![];
+            self.bind(having, source, outer, &named, &mut grouped, snapshot)?;
+            if !grouped.is_empty() {
+                return Err(Errors::UnimplementedOperation);
+            }
+        }
+        if correlated.is_empty() {
+            return source
+                .select_data(query, candidates, snapshot)
+                .map_err(|err| source.column_error(err));
+        }
+
+        // a correlated subquery is run for every row, its results become a column
+        query.projection = source
+            .output_columns(&query.projection)?
+            .into_iter()
+            .map(|(name, expr)| Projection::Expr { expr, name })
+            .collect();
+        let row_ixs = source.matching_rows(&None, None, snapshot)?;
+        let mut types = vec![DataType::Invalid; correlated.len()];
+        let mut rows = vec![];
+        for ix in row_ixs {
+            let row = |name: &str| source.value_of(ix, name).or_else(|| outer(name));
+            let mut values = source.row_values(ix);
+            for (subquery, datatype) in correlated.iter().zip(types.iter_mut()) {
+                let (result, result_type) =
+                    self.subquery_result(subquery, &row, &named, snapshot)?;
+                values.push(result.eval(&row)?);
+                if *datatype == DataType::Invalid {
+                    *datatype = result_type;
+                }
+            }
+            rows.push(values);
+        }
+        let mut tb = Table::derived(source.name());
+        source
+            .columns()
+            .iter()
+            .for_each(|col| tb.add_column(col.clone()));
+        for (ix, datatype) in types.into_iter().enumerate() {
+            tb.add_column(ColumnAttr {
+                name: subquery_column(ix),
+                datatype,
+                is_pk: false,
+                is_nullable: true,
+                default: None,
+            });
+        }
+        rows.into_iter().for_each(|row| tb.push_values(row));
+        tb.select_data(query, None, snapshot)
+            .map_err(|err| source.column_error(err))
+    }
+
+    // replaces the subqueries of `expr` by their results, the ones reading the rows of
+    // `source` by a column named after their position in `correlated`
+    fn bind(
+        &self,
+        expr: &mut Expression,
+        source: &dyn Rows,
+        outer: &dyn Fn(&str) -> Option<Value>,
+        named: &[&Table],
+        correlated: &mut Vec<Expression>,
+        snapshot: &Snapshot,
+    ) -> Result<(), Errors> {
+        for child in expr.children_mut() {
+            self.bind(child, source, outer, named, correlated, snapshot)?;
+        }
+        if !matches!(
+            expr,
+            Expression::Subquery(_) | Expression::Exists { .. } | Expression::InSubquery { .. }
+        ) {
+            return Ok(());
+        }
+        // a subquery is correlated when it reads the rows, it runs once otherwise
+        let reads_rows = Cell::new(false);
+        let probe = |name: &str| match source.resolve_column(name) {
+            Some(_) => {
+                reads_rows.set(true);
+                Some(Value::Null)
+            }
+            None => outer(name),
+        };
+        let result = self.subquery_result(expr, &probe, named, snapshot);
+        if reads_rows.get() {
+            let column = Expression::Column(subquery_column(correlated.len()));
+            correlated.push(mem::replace(expr, column));
+        } else {
+            *expr = result?.0;
+        }
+        Ok(())
+    }
+
+    // the expression subquery `expr` amounts to for the rows `outer`, with its type
+    fn subquery_result(
+        &self,
+        expr: &Expression,
+        outer: &dyn Fn(&str) -> Option<Value>,
+        named: &[&Table],
+        snapshot: &Snapshot,
+    ) -> Result<(Expression, DataType), Errors> {
+        match expr {
+            Expression::Subquery(query) => {
+                let rows = self.single_column(query, outer, named, snapshot)?;
+                if rows.len() > 1 {
+                    return Err(Errors::InvalidSubquery(
+                        "returns more than one row".to_string(),
+                    ));
+                }
+                let datatype = rows.types[0].clone();
+                let value = rows
+                    .into_iter()
+                    .next()
+                    .map_or(Value::Null, |row| row[0].clone());
+                Ok((Expression::Literal(value), datatype))
+            }
+            Expression::Exists { query, negated } => {
+                // one row decides it
+                let query = SelectQuery {
+                    limit: Some(query.limit.map_or(1, |limit| limit.min(1))),
+                    ..query.as_ref().clone()
+                };
+                let rows = self.run_select(query, None, outer, named, snapshot)?;
+                let exists = Value::Bool(rows.is_empty() == *negated);
+                Ok((Expression::Literal(exists), DataType::Bool))
+            }
+            Expression::InSubquery {
+                expr,
+                query,
+                negated,
+            } => {
+                let rows = self.single_column(query, outer, named, snapshot)?;
+                let list = rows
+                    .into_iter()
+                    .map(|row| Expression::Literal(row[0].clone()))
+                    .collect::<Vec<Expression>>();
+                let in_list = Expression::InList {
+                    expr: expr.clone(),
+                    list,
+                    negated: *negated,
+                };
+                Ok((in_list, DataType::Bool))
+            }
+            expr => Ok((expr.clone(), DataType::Invalid)),
+        }
+    }
+
+    // the rows of a query of `WITH` as a table of its name
+    fn common_table(
+        &self,
+        cte: CommonTable,
+        outer: &dyn Fn(&str) -> Option<Value>,
+        named: &[&Table],
+        snapshot: &Snapshot,
+    ) -> Result<Table, Errors> {
+        let CommonTable {
+            name,
+            columns,
+            mut query,
+            recursive,
+        } = cte;
+        let mut rows = match query.set_operation.take() {
+            Some(set) if recursive && set.op == SetOp::Union && set.right.reads_table(&name) => {
+                let rows = self.recurse(&name, &columns, *set, outer, named, snapshot)?;
+                order_and_limit(rows, &query)?
+            }
+            set => {
+                query.set_operation = set;
+                self.run_select(query, None, outer, named, snapshot)?
+            }
+        };
+        rename_columns(&mut rows, &columns)?;
+        Ok(Table::from_rows(&name, rows))
+    }
+
+    // the rows of a recursive `UNION`, its right query reads the rows the round
+    // before added as table `name` until a round adds none
+    fn recurse(
+        &self,
+        name: &str,
+        columns: &[String],
+        set: SetOperation,
+        outer: &dyn Fn(&str) -> Option<Value>,
+        named: &[&Table],
+        snapshot: &Snapshot,
+    ) -> Result<ResultSet, Errors> {
+        let mut rows = self.run_select(set.left, None, outer, named, snapshot)?;
+        rename_columns(&mut rows, columns)?;
+        if !set.all {
+            rows.dedup();
+        }
+        let mut added = rows.clone();
+        let max_rounds = MAX_RECURSION.load(atomic::Ordering::Relaxed);
+        for round in 1.. {
+            let working = Table::from_rows(name, added);
+            let visible = iter::once(&working).chain(named.iter().copied());
+            let visible = visible.collect::<Vec<&Table>>();
+            let right = self.run_select(set.right.clone(), None, outer, &visible, snapshot)?;
+            let len = rows.len();
+            rows = merge(SetOp::Union, set.all, rows, right)?;
+            if rows.len() == len {
+                break;
+            }
+            if round > max_rounds {
+                return Err(Errors::RecursionLimitExceeded(name.to_string(), max_rounds));
+            }
+            added = ResultSet {
+                columns: rows.columns.clone(),
+                types: rows.types.clone(),
+                rows: rows.rows[len..].to_vec(),
+            };
+        }
+        Ok(rows)
+    }
+
+    // the rows of a set operation
+    fn combine(
+        &self,
+        set: SetOperation,
+        outer: &dyn Fn(&str) -> Option<Value>,
+        named: &[&Table],
+        snapshot: &Snapshot,
+    ) -> Result<ResultSet, Errors> {
+        let left = self.run_select(set.left, None, outer, named, snapshot)?;
+        let right = self.run_select(set.right, None, outer, named, snapshot)?;
+        merge(set.op, set.all, left, right)
+    }
+
+    // the rows of a subquery that gives a single column
+    fn single_column(
+        &self,
+        query: &SelectQuery,
+        outer: &dyn Fn(&str) -> Option<Value>,
+        named: &[&Table],
+        snapshot: &Snapshot,
+    ) -> Result<ResultSet, Errors> {
+        let rows = self.run_select(query.clone(), None, outer, named, snapshot)?;
+        match rows.columns.len() {
+            1 => Ok(rows),
+            n => Err(Errors::InvalidSubquery(format!("returns {} columns", n))),
+        }
+    }
+}
+
+// the rows of two queries combined by `op`, named like the columns of the left one
+fn merge(op: SetOp, all: bool, left: ResultSet, right: ResultSet) -> Result<ResultSet, Errors> {
+    if left.columns.len() != right.columns.len() {
+        return Err(Errors::ColumnCountMismatch(op.to_string()));
+    }
+    let types = left
+        .types
+        .iter()
+        .zip(&right.types)
+        .map(|(l, r)| match (l, r) {
+            (l, r) if l == r => Ok(l.clone()),
+            (DataType::Invalid, t) | (t, DataType::Invalid) => Ok(t.clone()),
+            (DataType::Int | DataType::Float, DataType::Int | DataType::Float) => {
+                Ok(DataType::Float)
+            }
+            (l, r) => Err(Errors::TypeMismatch(format!("{} {} {}", l, op, r))),
+        })
+        .collect::<Result<Vec<DataType>, Errors>>()?;
+    // the ints of a column that also holds floats become floats
+    let unify = |row: Vec<Value>| {
+        row.into_iter()
+            .zip(&types)
+            .map(|(value, datatype)| match (value, datatype) {
+                (Value::Int(v), DataType::Float) => Value::Float(v as f64),
+                (value, _) => value,
+            })
+            .collect::<Vec<Value>>()
+    };
+    let mut rows = ResultSet {
+        columns: left.columns,
+        types: types.clone(),
+        rows: left.rows.into_iter().map(unify).collect(),
+    };
+    if !all {
+        rows.dedup();
+    }
+    match op {
+        SetOp::Union => {
+            rows.rows.extend(right.rows.into_iter().map(unify));
+            if !all {
+                rows.dedup();
+            }
+        }
+        SetOp::Intersect | SetOp::Except => {
+            let mut counts = BTreeMap::new();
+            for row in right.rows.into_iter().map(unify) {
+                *counts.entry(RowKey(row)).or_insert(0usize) += 1;
+            }
+            // with `ALL` every row of the right query matches one row of the left one
+            rows.rows.retain(|row| {
+                let matched = match counts.get_mut(&RowKey(row.clone())) {
+                    Some(count) if *count > 0 => {
+                        if all {
+                            *count -= 1;
+                        }
+                        true
+                    }
+                    _ => false,
+                };
+                matched == (op == SetOp::Intersect)
+            });
+        }
+    }
+    Ok(rows)
+}
+
+// the rows of a set operation or of a recursive query sorted and cut by its
+// `ORDER BY`, `OFFSET` and `LIMIT`
+fn order_and_limit(mut rows: ResultSet, query: &SelectQuery) -> Result<ResultSet, Errors> {
+    sort_result(&mut rows, &query.order_by)?;
+    let limit = query.limit.unwrap_or(usize::MAX);
+    rows.rows = rows
+        .rows
+        .into_iter()
+        .skip(query.offset)
+        .take(limit)
+        .collect();
+    Ok(rows)
+}
+
+// names the columns of a query of `WITH` by the names it gives them
+fn rename_columns(rows: &mut ResultSet, columns: &[String]) -> Result<(), Errors> {
+    match columns.len() {
+        0 => Ok(()),
+        n if n == rows.columns.len() => {
+            rows.columns = columns.to_vec();
+            Ok(())
+        }
+        _ => Err(Errors::InvalidExpression),
+    }
+}
+
+// sorts the rows of a set operation by `ORDER BY` items naming or counting its columns
+fn sort_result(rows: &mut ResultSet, order_by: &[OrderBy]) -> Result<(), Errors> {
+    let mut keys = vec![];
+    for item in order_by {
+        let key = match &item.key {
+            OrderKey::Column(name) => rows
+                .column_index(name)
+                .ok_or_else(|| Errors::ColumnNotExisted(name.to_string()))?,
+            OrderKey::Position(pos) if *pos <= rows.columns.len() => pos - 1,
+            OrderKey::Position(pos) => return Err(Errors::InvalidOrderPosition(pos.to_string())),
+        };
+        keys.push((key, item));
+    }
+    rows.rows.sort_by(|a, b| {
+        keys.iter()
+            .map(|(ix, item)| a[*ix].sort_cmp(&b[*ix], item.asc, item.nulls_first))
+            .find(|ord| ord.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    Ok(())
+}
+
+// replaces the columns `constant` gives a value to by that value
+fn substitute(expr: &mut Expression, constant: &dyn Fn(&str) -> Option<Value>) {
+    if let Expression::Column(name) = expr {
+        if let Some(value) = constant(name) {
+            *expr = Expression::Literal(value);
+        }
+        return;
+    }
+    for child in expr.children_mut() {
+        substitute(child, constant);
+    }
+}
+
+// the column holding the results of the `ix`th correlated subquery
+fn subquery_column(ix: usize) -> String {
+    format!("#subquery{}", ix + 1)
+}
+
+#[test]
+fn test_subqueries() {
+    use crate::parser::create::CreateQuery;
+    use crate::parser::utils::parse_sql;
+
+    let table = |sql: &str, rows: Vec<Vec<Value>>| {
+        let mut tb = Table::new(CreateQuery::format_stat(parse_sql(sql).unwrap()).unwrap());
+        rows.into_iter().for_each(|row| tb.push_values(row));
+        tb
+    };
+    let (int, str) = (Value::Int, |s: &str| Value::Str(s.to_string()));
+    let mut db = Database::new();
+    db.tables.push(table(
+        "CREATE TABLE users (id INT PRIMARY KEY, name TEXT)",
+        vec![
+            vec![int(1), str("ann")],
+            vec![int(2), str("bob")],
+            vec![int(3), str("cat")],
+        ],
+    ));
+    db.tables.push(table(
+        "CREATE TABLE orders (id INT PRIMARY KEY, user_id INT)",
+        vec![
+            vec![int(10), int(1)],
+            vec![int(11), int(1)],
+            vec![int(12), int(3)],
+        ],
+    ));
+    let snapshot = Snapshot { txn: 1, csn: 0 };
+    let mut select = |sql: &str| {
+        let query = SelectQuery::format_stat(parse_sql(sql).unwrap()).unwrap();
+        db.select(query, &snapshot).map(|rows| rows.rows)
+    };
+
+    let names = |names: &[&str]| names.iter().map(|n| vec![str(n)]).collect::<Vec<_>>();
+    assert_eq!(
+        select("SELECT name FROM users WHERE id IN (SELECT user_id FROM orders)").unwrap(),
+        names(&["ann", "cat"])
+    );
+    assert_eq!(
+        select("SELECT name FROM users WHERE id NOT IN (SELECT user_id FROM orders)").unwrap(),
+        names(&["bob"])
+    );
+    assert_eq!(
+        select("SELECT name FROM users u WHERE EXISTS (SELECT * FROM orders WHERE user_id = u.id AND id > 10)").unwrap(),
+        names(&["ann", "cat"])
+    );
+    assert_eq!(
+        select("SELECT name FROM users WHERE NOT EXISTS (SELECT * FROM orders WHERE user_id = users.id)").unwrap(),
+        names(&["bob"])
+    );
+    assert_eq!(
+        select("SELECT name FROM users WHERE id > (SELECT MIN(user_id) FROM orders)").unwrap(),
+        names(&["bob", "cat"])
+    );
+    // a correlated subquery of the select list runs for every row
+    assert_eq!(
+        select("SELECT name, (SELECT COUNT(*) FROM orders WHERE user_id = users.id) AS n FROM users ORDER BY n DESC").unwrap(),
+        vec![
+            vec![str("ann"), int(2)],
+            vec![str("cat"), int(1)],
+            vec![str("bob"), int(0)]
+        ]
+    );
+    assert_eq!(
+        select("SELECT u.name, o.id FROM (SELECT * FROM users WHERE id > 1) AS u JOIN orders o ON o.user_id = u.id").unwrap(),
+        vec![vec![str("cat"), int(12)]]
+    );
+    assert!(matches!(
+        select("SELECT name FROM users WHERE id = (SELECT user_id FROM orders)"),
+        Err(Errors::InvalidSubquery(_))
+    ));
+    assert!(matches!(
+        select("SELECT name FROM users WHERE id IN (SELECT * FROM orders)"),
+        Err(Errors::InvalidSubquery(_))
+    ));
+}
+
+#[test]
+fn test_set_operations() {
+    use crate::parser::create::CreateQuery;
+    use crate::parser::utils::parse_sql;
+
+    let table = |sql: &str, rows: Vec<Vec<Value>>| {
+        let mut tb = Table::new(CreateQuery::format_stat(parse_sql(sql).unwrap()).unwrap());
+        rows.into_iter().for_each(|row| tb.push_values(row));
+        tb
+    };
+    let int = Value::Int;
+    let mut db = Database::new();
+    db.tables.push(table(
+        "CREATE TABLE a (id INT, name TEXT)",
+        [1, 2, 2, 3]
+            .iter()
+            .map(|id| vec![int(*id), Value::Str(format!("a{}", id))])
+            .collect(),
+    ));
+    db.tables.push(table(
+        "CREATE TABLE b (id INT, price FLOAT)",
+        vec![
+            vec![int(2), Value::Float(2.5)],
+            vec![int(4), Value::Float(4.5)],
+            vec![int(2), Value::Null],
+        ],
+    ));
+    let snapshot = Snapshot { txn: 1, csn: 0 };
+    let mut ids = |sql: &str| {
+        let query = SelectQuery::format_stat(parse_sql(sql).unwrap()).unwrap();
+        db.select(query, &snapshot).map(|rows| {
+            rows.rows
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<Value>>()
+        })
+    };
+
+    assert_eq!(
+        ids("SELECT id FROM a UNION SELECT id FROM b").unwrap(),
+        vec![int(1), int(2), int(3), int(4)]
+    );
+    assert_eq!(
+        ids("SELECT id FROM a UNION ALL SELECT id FROM b ORDER BY id DESC LIMIT 3").unwrap(),
+        vec![int(4), int(3), int(2)]
+    );
+    assert_eq!(
+        ids("SELECT id FROM a INTERSECT SELECT id FROM b").unwrap(),
+        vec![int(2)]
+    );
+    assert_eq!(
+        ids("SELECT id FROM a INTERSECT ALL SELECT id FROM b").unwrap(),
+        vec![int(2), int(2)]
+    );
+    assert_eq!(
+        ids("SELECT id FROM a EXCEPT SELECT id FROM b").unwrap(),
+        vec![int(1), int(3)]
+    );
+    assert_eq!(
+        ids("SELECT id FROM b EXCEPT ALL SELECT id FROM a WHERE id > 2").unwrap(),
+        vec![int(2), int(4), int(2)]
+    );
+    // ints meet floats as floats, NULLs are duplicates of each other
+    assert_eq!(
+        ids("SELECT price FROM b UNION SELECT id FROM a WHERE id = 2 UNION SELECT NULL FROM a ORDER BY 1").unwrap(),
+        vec![Value::Float(2.0), Value::Float(2.5), Value::Float(4.5), Value::Null]
+    );
+    assert!(matches!(
+        ids("SELECT id, name FROM a UNION SELECT id FROM b"),
+        Err(Errors::ColumnCountMismatch(_))
+    ));
+    assert!(matches!(
+        ids("SELECT name FROM a EXCEPT SELECT id FROM b"),
+        Err(Errors::TypeMismatch(_))
+    ));
+}
+
+#[test]
+fn test_common_tables() {
+    use crate::parser::create::CreateQuery;
+    use crate::parser::utils::parse_sql;
+
+    let mut tb = Table::new(
+        CreateQuery::format_stat(
+            parse_sql("CREATE TABLE categories (id INT PRIMARY KEY, parent INT, name TEXT)")
+                .unwrap(),
+        )
+        .unwrap(),
+    );
+    let (int, str) = (Value::Int, |s: &str| Value::Str(s.to_string()));
+    for (id, parent, name) in [(1, None, "root"), (2, Some(1), "a"), (3, Some(2), "b")] {
+        tb.push_values(vec![int(id), parent.map_or(Value::Null, int), str(name)]);
+    }
+    let mut db = Database::new();
+    db.tables.push(tb);
+    let snapshot = Snapshot { txn: 1, csn: 0 };
+    let mut select = |sql: &str| {
+        let query = SelectQuery::format_stat(parse_sql(sql).unwrap()).unwrap();
+        db.select(query, &snapshot).map(|rows| rows.rows)
+    };
+
+    assert_eq!(
+        select("WITH top AS (SELECT * FROM categories WHERE parent IS NULL), kids (name) AS (SELECT c.name FROM categories c JOIN top ON c.parent = top.id) SELECT * FROM kids").unwrap(),
+        vec![vec![str("a")]]
+    );
+    // a subquery reads the queries of the `WITH` around it
+    assert_eq!(
+        select("WITH top AS (SELECT id FROM categories WHERE parent IS NULL) SELECT name FROM categories WHERE parent IN (SELECT id FROM top)").unwrap(),
+        vec![vec![str("a")]]
+    );
+    assert_eq!(
+        select("WITH RECURSIVE tree (id, depth) AS (SELECT id, 0 FROM categories WHERE parent IS NULL UNION ALL SELECT c.id, tree.depth + 1 FROM categories c JOIN tree ON c.parent = tree.id) SELECT * FROM tree ORDER BY id DESC").unwrap(),
+        vec![
+            vec![int(3), int(2)],
+            vec![int(2), int(1)],
+            vec![int(1), int(0)]
+        ]
+    );
+    // `UNION` stops once a round gives no new rows
+    assert_eq!(
+        select("WITH RECURSIVE n (v) AS (SELECT 1 UNION SELECT v % 3 + 1 FROM n) SELECT COUNT(*) FROM n").unwrap(),
+        vec![vec![int(3)]]
+    );
+    assert_eq!(
+        select("WITH RECURSIVE n AS (SELECT 1 UNION ALL SELECT 2) SELECT * FROM n").unwrap(),
+        vec![vec![int(1)], vec![int(2)]]
+    );
+    assert!(matches!(
+        select("WITH RECURSIVE n (v) AS (SELECT 1 UNION ALL SELECT v + 1 FROM n) SELECT * FROM n"),
+        Err(Errors::RecursionLimitExceeded(_, 1000))
+    ));
+    assert!(matches!(
+        select("WITH n (a, b) AS (SELECT 1) SELECT * FROM n"),
+        Err(Errors::InvalidExpression)
+    ));
+}
+
+#[test]
+fn test_joins() {
+    use crate::parser::create::CreateQuery;
+    use crate::parser::utils::parse_sql;
+
+    let table = |sql: &str, rows: Vec<Vec<Value>>| {
+        let mut tb = Table::new(CreateQuery::format_stat(parse_sql(sql).unwrap()).unwrap());
+        rows.into_iter().for_each(|row| tb.push_values(row));
+        tb
+    };
+    let (int, str) = (Value::Int, |s: &str| Value::Str(s.to_string()));
+    let mut db = Database::new();
+    db.tables.push(table(
+        "CREATE TABLE users (id INT PRIMARY KEY, name TEXT, boss INT)",
+        vec![
+            vec![int(1), str("ann"), Value::Null],
+            vec![int(2), str("bob"), int(1)],
+            vec![int(3), str("cat"), int(1)],
+        ],
+    ));
+    db.tables.push(table(
+        "CREATE TABLE orders (id INT PRIMARY KEY, user_id INT)",
+        vec![vec![int(10), int(2)], vec![int(11), int(3)]],
+    ));
+    db.tables.push(table(
+        "CREATE TABLE items (order_id INT, item TEXT)",
+        vec![
+            vec![int(10), str("pen")],
+            vec![int(10), str("ink")],
+            vec![int(11), str("cup")],
+        ],
+    ));
+    let snapshot = Snapshot { txn: 1, csn: 0 };
+    let mut select = |sql: &str| {
+        let query = SelectQuery::format_stat(parse_sql(sql).unwrap()).unwrap();
+        db.select(query, &snapshot).map(|rows| rows.rows)
+    };
+
+    assert_eq!(
+        select("SELECT u.name, i.item FROM users u JOIN orders o ON o.user_id = u.id JOIN items i ON i.order_id = o.id ORDER BY i.item").unwrap(),
+        vec![
+            vec![str("cat"), str("cup")],
+            vec![str("bob"), str("ink")],
+            vec![str("bob"), str("pen")]
+        ]
+    );
+    assert_eq!(
+        select("SELECT COUNT(*) FROM users CROSS JOIN items").unwrap(),
+        vec![vec![int(9)]]
+    );
+    assert_eq!(
+        select("SELECT COUNT(*) FROM users, orders, items").unwrap(),
+        vec![vec![int(18)]]
+    );
+    assert_eq!(
+        select("SELECT e.name, b.name FROM users e JOIN users b ON e.boss = b.id ORDER BY e.id")
+            .unwrap(),
+        vec![vec![str("bob"), str("ann")], vec![str("cat"), str("ann")]]
+    );
+    assert_eq!(
+        select("SELECT x.name FROM users AS x WHERE x.id = 2").unwrap(),
+        vec![vec![str("bob")]]
+    );
+    assert!(matches!(
+        select("SELECT v.name FROM users u JOIN orders o ON o.user_id = u.id"),
+        Err(Errors::ColumnNotExisted(_))
+    ));
+    assert!(matches!(
+        select("SELECT u.name FROM users u JOIN orders o ON o.user_id = v.id"),
+        Err(Errors::ColumnNotExisted(_))
+    ));
+    assert!(matches!(
+        select("SELECT v.* FROM users u JOIN orders o ON o.user_id = u.id"),
+        Err(Errors::TableNotExisted(_))
+    ));
+    // a column of several tables has to be qualified
+    assert!(matches!(
+        select("SELECT id FROM users JOIN orders ON user_id = users.id"),
+        Err(Errors::AmbiguousColumn(_))
+    ));
+    assert!(matches!(
+        select("SELECT name FROM users u JOIN orders o ON o.id = id"),
+        Err(Errors::AmbiguousColumn(_))
+    ));
+    assert!(matches!(
+        select("SELECT o.id FROM users u JOIN orders o ON o.user_id = u.id ORDER BY id"),
+        Err(Errors::AmbiguousColumn(_))
+    ));
+}
@@ -0,0 +1,104 @@
+use crate::database::base::{DataType, Value};
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+/// Rows produced by a query, in the order of its projection.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    pub types: Vec<DataType>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl ResultSet {
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Position of column `name` in the rows.
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column == name)
+    }
+
+    /// Value of column `name` in row `row`.
+    pub fn get(&self, row: usize, name: &str) -> Option<&Value> {
+        self.rows.get(row)?.get(self.column_index(name)?)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Vec<Value>> {
+        self.rows.iter()
+    }
+
+    /// Drops the rows equal to an earlier one, see `RowKey`.
+    pub fn dedup(&mut self) {
+        let mut seen = BTreeSet::new();
+        self.rows.retain(|row| seen.insert(RowKey(row.clone())));
+    }
+}
+
+/// A row as the key of a set, ordered by `Value::total_cmp` column by column. NULLs
+/// are equal to each other here, unlike in conditions.
+#[derive(Debug, Clone)]
+pub struct RowKey(pub Vec<Value>);
+
+impl Ord for RowKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .map(|(a, b)| a.total_cmp(b))
+            .find(|ord| ord.is_ne())
+            .unwrap_or_else(|| self.0.len().cmp(&other.0.len()))
+    }
+}
+
+impl PartialOrd for RowKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for RowKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for RowKey {}
+
+impl IntoIterator for ResultSet {
+    type Item = Vec<Value>;
+    type IntoIter = std::vec::IntoIter<Vec<Value>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.rows.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a ResultSet {
+    type Item = &'a Vec<Value>;
+    type IntoIter = std::slice::Iter<'a, Vec<Value>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.rows.iter()
+    }
+}
+
+/// What running a statement produced, rendered by the REPL or sent by a server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    Rows(ResultSet),
+    Affected(usize),
+    /// Columns of a table, with its foreign keys and indexes as `details`.
+    Schema {
+        table: String,
+        columns: ResultSet,
+        details: Vec<(String, Vec<String>)>,
+    },
+    Message(String),
+    Done,
+}
@@ -0,0 +1,362 @@
+use crate::database::db::Database;
+use crate::database::mvcc::{IsolationLevel, Transaction};
+use crate::storage::lock::LockMode;
+use crate::storage::wal::Mutation;
+use crate::system::errors::Errors;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, Weak};
+
+/// Databases currently opened by a session. Sessions using the same database share
+/// one `Database` value, the registry only keeps it while a session holds it.
+fn open_databases() -> &'static Mutex<HashMap<String, Weak<Mutex<Database>>>> {
+    static OPEN: OnceLock<Mutex<HashMap<String, Weak<Mutex<Database>>>>> = OnceLock::new();
+    OPEN.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// a session that panicked while holding the lock may have left rows half changed,
+// no other session can trust them afterwards
+fn lock(db: &Mutex<Database>) -> MutexGuard<'_, Database> {
+    db.lock()
+        .expect("the database was left by a session that panicked")
+}
+
+/// One client of the engine: the database it uses and its open transaction. Statements
+/// run outside of `BEGIN` get a transaction of their own that commits when they succeed.
+#[derive(Default)]
+pub struct Session {
+    db: Option<Arc<Mutex<Database>>>,
+    db_name: String,
+    // level of the transactions started by this session
+    pub isolation: IsolationLevel,
+    // set by `SET TRANSACTION` before `BEGIN`, used by the next transaction only
+    next_isolation: Option<IsolationLevel>,
+    txn: Option<Transaction>,
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session::default()
+    }
+
+    pub fn db_name(&self) -> &str {
+        self.db_name.as_str()
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.txn.is_some()
+    }
+
+    /// Whether a session other than this one has `db_name` open.
+    pub fn used_by_others(&self, db_name: &str) -> bool {
+        let open = open_databases()
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let holders = open.get(db_name).map_or(0, |db| db.strong_count());
+        holders > usize::from(self.db.is_some() && self.db_name == db_name)
+    }
+
+    /// Leaves the database, once it was dropped.
+    pub fn close_db(&mut self) {
+        self.db = None;
+        self.db_name.clear();
+    }
+
+    /// Switches to `db_name`, loading it unless another session already has it open.
+    /// Returns the mutations replayed from the wal when it was loaded.
+    pub fn use_db(&mut self, db_name: &str) -> Result<Vec<Mutation>, Errors> {
+        if self.in_transaction() {
+            return Err(Errors::TransactionStarted);
+        }
+        let mut open = open_databases()
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        open.retain(|_, db| db.strong_count() > 0);
+        let mut recovered = vec![];
+        let db = match open.get(db_name).and_then(|db| db.upgrade()) {
+            Some(db) => db,
+            None => {
+                let mut database = Database::new();
+                database.set_dbname(db_name.to_string());
+                database.lock_file(LockMode::Shared)?;
+                let loaded = database.load_from_disk(database.file_path().as_str());
+                database.unlock_file();
+                loaded.map_err(|_| Errors::FileSystemError)?;
+                recovered = std::mem::take(&mut database.recovered);
+                let db = Arc::new(Mutex::new(database));
+                open.insert(db_name.to_string(), Arc::downgrade(&db));
+                db
+            }
+        };
+        self.db = Some(db);
+        self.db_name = db_name.to_string();
+        Ok(recovered)
+    }
+
+    /// Runs a statement inside the open transaction, or inside one of its own. A
+    /// statement that fails leaves no change behind.
+    pub fn run<T>(
+        &mut self,
+        statement: impl FnOnce(&mut Database, &mut Transaction) -> Result<T, Errors>,
+    ) -> Result<T, Errors> {
+        let db = self.db.clone().ok_or(Errors::DatabaseNotExisted)?;
+        let mut db = lock(&db);
+        db.lock_file(LockMode::Shared)?;
+        let mut txn = match self.txn.take() {
+            Some(mut txn) => {
+                db.txns.refresh(&mut txn);
+                txn
+            }
+            None => db.txns.begin(self.isolation, false),
+        };
+        let (undo_len, mutations) = (txn.undo.len(), txn.mutations.len());
+        let result = statement(&mut db, &mut txn);
+        if txn.explicit {
+            if result.is_err() {
+                db.rollback_to(&mut txn, undo_len, mutations);
+            }
+            self.txn = Some(txn);
+            return result;
+        }
+        let result = match result {
+            Ok(v) => db.commit_transaction(txn).map(|_| v),
+            Err(err) => {
+                db.rollback_transaction(txn);
+                Err(err)
+            }
+        };
+        db.unlock_file();
+        result
+    }
+
+    /// Runs a schema change. They are not versioned, so they are refused inside a
+    /// transaction and saved as soon as they are applied.
+    pub fn run_schema<T>(
+        &mut self,
+        statement: impl FnOnce(&mut Database) -> Result<T, Errors>,
+    ) -> Result<T, Errors> {
+        if self.in_transaction() {
+            return Err(Errors::SchemaChangeInTransaction);
+        }
+        let db = self.db.clone().ok_or(Errors::DatabaseNotExisted)?;
+        let mut db = lock(&db);
+        db.lock_file(LockMode::Exclusive)?;
+        let result = statement(&mut db);
+        db.unlock_file();
+        result
+    }
+
+    pub fn begin(&mut self, isolation: Option<IsolationLevel>) -> Result<(), Errors> {
+        if self.in_transaction() {
+            return Err(Errors::TransactionStarted);
+        }
+        let db = self.db.clone().ok_or(Errors::DatabaseNotExisted)?;
+        let isolation = isolation
+            .or(self.next_isolation.take())
+            .unwrap_or(self.isolation);
+        self.txn = Some(lock(&db).txns.begin(isolation, true));
+        Ok(())
+    }
+
+    pub fn commit(&mut self) -> Result<(), Errors> {
+        let txn = self.txn.take().ok_or(Errors::TransactionNotStarted)?;
+        let db = self.db.clone().ok_or(Errors::DatabaseNotExisted)?;
+        let mut db = lock(&db);
+        let result = db.commit_transaction(txn);
+        db.unlock_file();
+        result
+    }
+
+    pub fn rollback(&mut self) -> Result<(), Errors> {
+        let txn = self.txn.take().ok_or(Errors::TransactionNotStarted)?;
+        let db = self.db.clone().ok_or(Errors::DatabaseNotExisted)?;
+        let mut db = lock(&db);
+        db.rollback_transaction(txn);
+        db.unlock_file();
+        Ok(())
+    }
+
+    pub fn savepoint(&mut self, name: String) -> Result<(), Errors> {
+        let txn = self.txn.as_mut().ok_or(Errors::TransactionNotStarted)?;
+        let savepoint = (name, txn.undo.len(), txn.mutations.len());
+        txn.savepoints.push(savepoint);
+        Ok(())
+    }
+
+    /// Undoes the statements run after savepoint `name`, which stays defined.
+    pub fn rollback_to(&mut self, name: String) -> Result<(), Errors> {
+        let ix = self.find_savepoint(name)?;
+        let db = self.db.clone().ok_or(Errors::DatabaseNotExisted)?;
+        let txn = self.txn.as_mut().unwrap();
+        txn.savepoints.truncate(ix + 1);
+        let (_, undo_len, mutations) = txn.savepoints[ix];
+        lock(&db).rollback_to(txn, undo_len, mutations);
+        Ok(())
+    }
+
+    pub fn release(&mut self, name: String) -> Result<(), Errors> {
+        let ix = self.find_savepoint(name)?;
+        self.txn.as_mut().unwrap().savepoints.truncate(ix);
+        Ok(())
+    }
+
+    // the latest savepoint with that name wins
+    fn find_savepoint(&self, name: String) -> Result<usize, Errors> {
+        let txn = self.txn.as_ref().ok_or(Errors::TransactionNotStarted)?;
+        match txn.savepoints.iter().rposition(|sp| sp.0 == name) {
+            Some(ix) => Ok(ix),
+            None => Err(Errors::SavepointNotExisted(name)),
+        }
+    }
+
+    /// `SET TRANSACTION` changes the open transaction, or the next one when none is
+    /// open. A transaction moved to SNAPSHOT keeps reading at its current snapshot.
+    pub fn set_isolation(&mut self, level: IsolationLevel, session: bool) {
+        if session {
+            self.isolation = level;
+        } else if let Some(txn) = self.txn.as_mut() {
+            txn.isolation = level;
+        } else {
+            self.next_isolation = Some(level);
+        }
+    }
+}
+
+impl Drop for Session {
+    // a session that goes away never commits its transaction
+    fn drop(&mut self) {
+        if let (Some(txn), Some(db)) = (self.txn.take(), self.db.as_ref()) {
+            let mut db = lock(db);
+            db.rollback_transaction(txn);
+            db.unlock_file();
+        }
+    }
+}
+
+#[test]
+fn test_sessions_share_database() {
+    use crate::database::table::{Rows, Table};
+    use crate::parser::create::CreateQuery;
+    use crate::parser::utils::parse_sql;
+    use crate::system::utils::TempDb;
+
+    let temp = TempDb::new("session");
+    let db_name = temp.name.as_str();
+
+    let mut writer = Session::new();
+    let mut reader = Session::new();
+    writer.use_db(db_name).unwrap();
+    reader.use_db(db_name).unwrap();
+    writer
+        .run_schema(|db| {
+            let state = parse_sql("CREATE TABLE t (id INT PRIMARY KEY, name TEXT)")?;
+            db.create_table(Table::new(CreateQuery::format_stat(state)?))
+        })
+        .unwrap();
+    reader.set_isolation(IsolationLevel::Snapshot, true);
+    reader.begin(None).unwrap();
+    writer.begin(None).unwrap();
+    let insert = |db: &mut Database, txn: &mut Transaction| {
+        let row = vec!["1".to_string(), "a".to_string()];
+        let cols = vec!["id".to_string(), "name".to_string()];
+        db.insert_row("t".to_string(), cols, vec![row], txn)
+    };
+    writer.run(insert).unwrap();
+    let count = |db: &mut Database, txn: &mut Transaction| {
+        let tb = db.get_table("t".to_string())?;
+        Ok(tb.matching_rows(&None, None, &txn.snapshot)?.len())
+    };
+    assert_eq!(writer.run(count).unwrap(), 1);
+    assert_eq!(reader.run(count).unwrap(), 0);
+    writer.commit().unwrap();
+    assert_eq!(reader.run(count).unwrap(), 0);
+    reader.commit().unwrap();
+    assert_eq!(reader.run(count).unwrap(), 1);
+
+    // both delete the same row, the second one conflicts
+    writer.begin(None).unwrap();
+    reader.begin(None).unwrap();
+    let delete = |db: &mut Database, txn: &mut Transaction| {
+        let ixs = db
+            .get_table("t".to_string())?
+            .matching_rows(&None, None, &txn.snapshot)?;
+        db.delete_rows("t".to_string(), &ixs, txn)
+    };
+    writer.run(delete).unwrap();
+    assert!(matches!(reader.run(delete), Err(Errors::WriteConflict(_))));
+    writer.rollback().unwrap();
+    reader.run(delete).unwrap();
+    reader.commit().unwrap();
+    assert_eq!(writer.run(count).unwrap(), 0);
+}
+
+#[test]
+fn test_transactions() {
+    use crate::connection::Connection;
+    use crate::database::base::Value;
+    use crate::database::table::Rows;
+    use crate::storage::wal::Wal;
+    use crate::system::utils::TempDb;
+
+    let temp = TempDb::new("transactions");
+    let mut conn = Connection::open(&temp.name).unwrap();
+    conn.execute("CREATE TABLE t (id INT PRIMARY KEY, name TEXT)")
+        .unwrap();
+    conn.execute("INSERT INTO t (id, name) VALUES (1, 'a'), (2, 'b')")
+        .unwrap();
+    let rows = |conn: &mut Connection| {
+        conn.query("SELECT id, name FROM t ORDER BY id")
+            .unwrap()
+            .rows
+    };
+    let row = |id: i64, name: &str| vec![Value::Int(id), Value::Str(name.to_string())];
+    let committed = rows(&mut conn);
+    assert_eq!(committed, vec![row(1, "a"), row(2, "b")]);
+
+    // ROLLBACK puts back every row the transaction changed
+    conn.execute("BEGIN").unwrap();
+    conn.execute("UPDATE t SET name = 'z' WHERE id = 1")
+        .unwrap();
+    conn.execute("DELETE FROM t WHERE id = 2").unwrap();
+    conn.execute("INSERT INTO t (id, name) VALUES (3, 'c')")
+        .unwrap();
+    assert_eq!(rows(&mut conn), vec![row(1, "z"), row(3, "c")]);
+    conn.execute("ROLLBACK").unwrap();
+    assert_eq!(rows(&mut conn), committed);
+
+    // ROLLBACK TO undoes the statements after the savepoint only
+    let path = temp.path();
+    let files = || {
+        let read = |path: &str| std::fs::read(path).unwrap_or_default();
+        (read(&path), read(&Wal::path_for(&path)))
+    };
+    let saved = files();
+    conn.execute("BEGIN").unwrap();
+    conn.execute("INSERT INTO t (id, name) VALUES (3, 'c')")
+        .unwrap();
+    conn.execute("SAVEPOINT before_changes").unwrap();
+    conn.execute("UPDATE t SET name = 'z' WHERE id = 1")
+        .unwrap();
+    conn.execute("DELETE FROM t WHERE id = 2").unwrap();
+    conn.execute("INSERT INTO t (id, name) VALUES (4, 'd')")
+        .unwrap();
+    conn.execute("ROLLBACK TO SAVEPOINT before_changes")
+        .unwrap();
+    let changed = vec![row(1, "a"), row(2, "b"), row(3, "c")];
+    assert_eq!(rows(&mut conn), changed);
+
+    // nothing reaches the files before COMMIT, the database read from them again
+    // only has the committed rows
+    let reopen = || {
+        let mut db = Database::new();
+        db.set_dbname(temp.name.to_string());
+        db.load_from_disk(&path).unwrap();
+        let tb = db.get_table("t".to_string()).unwrap();
+        (0..tb.row_count())
+            .map(|ix| tb.row_values(ix))
+            .collect::<Vec<Vec<Value>>>()
+    };
+    assert_eq!(files(), saved);
+    assert_eq!(reopen(), committed);
+    conn.execute("COMMIT").unwrap();
+    assert_eq!(reopen(), changed);
+}
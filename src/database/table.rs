@@ -1,23 +1,32 @@
-use crate::database::base::{ColumnAttr, ColumnData, DataType, ForeignKeyAttr};
-use crate::parser::condition::Condition;
+use crate::database::aggregate::group_rows;
+use crate::database::base::{widen, ColumnAttr, ColumnData, DataType, ForeignKeyAttr, Value};
+use crate::database::mvcc::{Csn, Snapshot, TxnId, FROZEN, PENDING};
+use crate::database::result::{ResultSet, RowKey};
 use crate::parser::create::CreateQuery;
-use crate::parser::join::JoinInfo;
-use crate::parser::select::{BinaryOpCus, SelectQuery};
+use crate::parser::expr::{BinaryOp, Expression};
+use crate::parser::select::{Distinct, OrderBy, OrderKey, Projection, SelectQuery};
+use crate::storage::btree::BTree;
+use crate::storage::catalog::IndexMeta;
+use crate::storage::heap::HeapState;
+use crate::storage::page::PageId;
+use crate::storage::pager::Pager;
 use crate::system::errors::Errors;
-use crate::system::utils::{custom_strip, wildcard_match};
-use prettytable::Attr;
-use prettytable::{Cell, Row, Table as PTable};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
-use std::fmt;
-use std::fmt::Formatter;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Bound;
+use std::{io, mem};
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct Table {
     pub name: String,
     pub columns: Vec<ColumnAttr>,
     pub col_map: HashMap<String, ColumnData>,
     pub foreign_keys: Option<Vec<ForeignKeyAttr>>,
+    #[serde(skip)]
+    pub heap: HeapState,
+    #[serde(skip)]
+    pub indexes: Vec<IndexMeta>,
 }
 
 impl Table {
@@ -51,209 +60,97 @@ impl Table {
             columns: tb_cols,
             col_map: tb_col_map,
             foreign_keys: Some(cq.foreign_key),
+            heap: HeapState::default(),
+            indexes: vec![],
         }
     }
 
-    pub fn insert_row(&mut self, cols: Vec<String>, rows: Vec<Vec<String>>) {
-        let col_ix_map: HashMap<String, usize> = cols
-            .iter()
-            .enumerate()
-            .map(|(x, k)| (k.to_owned(), x))
-            .collect::<HashMap<String, usize>>();
-        for row in &rows {
-            let col_names: Vec<String> = self
-                .col_map
+    /// Appends `rows` as versions created by transaction `xmin`, returns their row ids.
+    /// The values `rows` give to every column of the table, in the order of its
+    /// columns and NULL where `cols` leaves one out. Without `cols` the rows give
+    /// all columns in that order.
+    pub fn parse_rows(
+        &self,
+        cols: &[String],
+        rows: &[Vec<String>],
+    ) -> Result<Vec<Vec<Value>>, Errors> {
+        let cols = match cols.is_empty() {
+            true => self
+                .columns
                 .iter()
-                .map(|(k, _)| k.to_owned())
-                .collect::<Vec<String>>();
-            for col_name in col_names {
-                let mut data = None;
-                if cols.contains(&col_name) {
-                    if let Some(ix) = col_ix_map.get(&col_name).to_owned() {
-                        let col_ix = ix.to_owned();
-                        let result = row.to_owned();
-                        data = Option::from(result.get(col_ix).unwrap().to_owned());
-                    }
-                } else {
-                    data = None;
-                }
-                if let Some(col_data) = self.col_map.get_mut(&col_name.to_string()) {
-                    match col_data {
-                        ColumnData::Int(v) => {
-                            if data.is_some() {
-                                v.push(Option::from(data.unwrap().parse::<i32>().unwrap()))
-                            } else {
-                                v.push(None)
-                            }
-                        }
-                        ColumnData::Float(v) => {
-                            if data.is_some() {
-                                v.push(Option::from(data.unwrap().parse::<f32>().unwrap()))
-                            } else {
-                                v.push(None)
-                            }
-                        }
-                        ColumnData::Str(v) => {
-                            if data.is_some() {
-                                v.push(Option::from(data.unwrap().to_string()))
-                            } else {
-                                v.push(None)
-                            }
-                        }
-                        ColumnData::Bool(v) => {
-                            if data.is_some() {
-                                v.push(Option::from(data.unwrap().parse::<bool>().unwrap()))
-                            } else {
-                                v.push(None)
-                            }
-                        }
-                        ColumnData::None => {}
-                    }
-                }
+                .map(|col| col.name.to_string())
+                .collect(),
+            false => cols.to_vec(),
+        };
+        let mut parsed = vec![];
+        for row in rows {
+            if row.len() != cols.len() {
+                return Err(Errors::InvalidExpression);
             }
+            let mut values = vec![];
+            for col in &self.columns {
+                let value = match cols.iter().position(|name| *name == col.name) {
+                    Some(ix) => Value::from_literal(&row[ix], &col.datatype)
+                        .ok_or(Errors::InvalidColumnType)?,
+                    None => Value::Null,
+                };
+                values.push(value);
+            }
+            parsed.push(values);
         }
+        Ok(parsed)
     }
 
-    pub fn select_data(&self, query: SelectQuery) {
-        let condition = &query.condition;
-        let mut projection = &query.projection;
-        let mut proj_set: HashSet<String> = HashSet::new();
-        let mut proj_loc: HashMap<String, usize> = HashMap::new();
-        let mut proj_loc_ix = 0;
-        for proj in projection {
-            if proj.eq("*") {
-                let all_cols = self
-                    .columns
-                    .iter()
-                    .map(|c| c.name.clone())
-                    .collect::<Vec<String>>();
-                for col in &all_cols {
-                    proj_loc.insert(col.to_string(), proj_loc_ix);
-                    proj_loc_ix += 1;
-                }
-                proj_set.extend(all_cols);
-            } else {
-                proj_set.insert(proj.to_string());
-                if proj_loc.get(proj).is_none() {
-                    proj_loc.insert(proj.to_string(), proj_loc_ix);
-                    proj_loc_ix += 1;
+    /// The values `assignments` of `UPDATE` give to their columns.
+    pub fn parse_assignments(
+        &self,
+        assignments: &HashMap<String, String>,
+    ) -> Result<HashMap<String, Value>, Errors> {
+        let mut parsed = HashMap::new();
+        for (name, literal) in assignments {
+            let col = self
+                .columns
+                .iter()
+                .find(|col| col.name == *name)
+                .ok_or_else(|| Errors::ColumnNotExisted(name.to_string()))?;
+            let value =
+                Value::from_literal(literal, &col.datatype).ok_or(Errors::InvalidColumnType)?;
+            parsed.insert(name.to_string(), value);
+        }
+        Ok(parsed)
+    }
+
+    /// Adds rows of values given by `parse_rows` as versions created by `xmin`.
+    pub fn insert_row(&mut self, rows: Vec<Vec<Value>>, xmin: TxnId) -> Vec<u64> {
+        let mut rids = vec![];
+        for values in rows {
+            for (col, val) in self.columns.iter().zip(values) {
+                if let Some(data) = self.col_map.get_mut(&col.name) {
+                    data.push_value(val);
                 }
             }
+            self.heap.push_version(xmin);
+            rids.push(self.heap.rows.last().unwrap().rid);
         }
-        let mut binding = proj_set
+        rids
+    }
+
+    /// Every column of the rows visible to `snapshot`, in declaration order.
+    pub fn all_data(&self, snapshot: &Snapshot) -> ResultSet {
+        let data = self
+            .columns
             .iter()
-            .map(|c| c.to_string())
-            .collect::<Vec<String>>();
-        binding.sort_by_key(|k| proj_loc.get(k));
-        projection = &binding;
-
-        let mut rows: Vec<HashMap<String, String>> = match self.get_rows() {
-            Ok(v) => v,
-            Err(err) => {
-                err.print();
-                return;
-            }
-        };
-        rows = self.filter_rows(condition, rows.clone(), Option::from(projection).cloned());
-
-        let pt = PrettyTable::create("".to_string(), projection.to_vec(), rows);
-        println!("{pt}");
-    }
-
-    pub fn evaluate_condition(&self, row: &HashMap<String, String>, condition: &Condition) -> bool {
-        match &condition {
-            Condition::Comparison { left, op, right } => {
-                let left_value = row.get(left).unwrap().as_str().to_owned();
-                let mut right_value = right.clone().unwrap_or("false".to_string());
-                right_value = custom_strip(right_value.as_str(), "\"").to_string();
-                match op {
-                    BinaryOpCus::Lt => match self.col_map.get(left) {
-                        None => {
-                            panic!("")
-                        }
-                        Some(x) => match x {
-                            ColumnData::Int(_) => {
-                                left_value.parse::<i32>().unwrap()
-                                    < right_value.parse::<i32>().unwrap()
-                            }
-                            ColumnData::Float(_) => {
-                                left_value.parse::<f32>().unwrap()
-                                    < right_value.parse::<f32>().unwrap()
-                            }
-                            ColumnData::Str(_) => left_value < right_value,
-                            ColumnData::Bool(_) => {
-                                left_value.parse::<bool>().unwrap()
-                                    < right_value.parse::<bool>().unwrap()
-                            }
-                            ColumnData::None => left_value < right_value,
-                        },
-                    },
-                    BinaryOpCus::Gt => match self.col_map.get(left) {
-                        None => {
-                            panic!("")
-                        }
-                        Some(x) => match x {
-                            ColumnData::Int(_) => {
-                                left_value.parse::<i32>().unwrap()
-                                    > right_value.parse::<i32>().unwrap()
-                            }
-                            ColumnData::Float(_) => {
-                                left_value.parse::<f32>().unwrap()
-                                    > right_value.parse::<f32>().unwrap()
-                            }
-                            ColumnData::Str(_) => left_value > right_value,
-                            ColumnData::Bool(_) => {
-                                left_value.parse::<bool>().unwrap()
-                                    > right_value.parse::<bool>().unwrap()
-                            }
-                            ColumnData::None => left_value > right_value,
-                        },
-                    },
-                    BinaryOpCus::Eq => match self.col_map.get(left) {
-                        None => {
-                            panic!("")
-                        }
-                        Some(x) => match x {
-                            ColumnData::Int(_) => {
-                                left_value.parse::<i32>().unwrap()
-                                    == right_value.parse::<i32>().unwrap()
-                            }
-                            ColumnData::Float(_) => {
-                                left_value.parse::<f32>().unwrap()
-                                    == right_value.parse::<f32>().unwrap()
-                            }
-                            ColumnData::Str(_) => left_value.eq(&right_value),
-                            ColumnData::Bool(_) => {
-                                left_value.parse::<bool>().unwrap()
-                                    == right_value.parse::<bool>().unwrap()
-                            }
-                            ColumnData::None => left_value == right_value,
-                        },
-                    },
-                    BinaryOpCus::IsNull => match self.col_map.get(left) {
-                        None => {
-                            panic!("")
-                        }
-                        Some(_) => left_value.is_empty(),
-                    },
-                    BinaryOpCus::Like => match self.col_map.get(left) {
-                        None => {
-                            panic!("")
-                        }
-                        Some(_) => wildcard_match(right_value.as_str(), left_value.as_str()),
-                    },
-                    _ => false,
-                }
-            }
-            Condition::Logical { left, op, right } => {
-                let left_result = self.evaluate_condition(row, &**left);
-                let right_result = self.evaluate_condition(row, &**right);
-                match op {
-                    BinaryOpCus::And => left_result && right_result,
-                    BinaryOpCus::Or => left_result || right_result,
-                    _ => false,
-                }
-            }
+            .map(|c| self.col_map.get(&c.name).unwrap())
+            .collect::<Vec<&ColumnData>>();
+        let rows = self
+            .scan_rows(&None, None, snapshot)
+            .flatten()
+            .map(|ix| data.iter().map(|col| col.get_value(ix)).collect())
+            .collect::<Vec<Vec<Value>>>();
+        ResultSet {
+            columns: self.columns.iter().map(|c| c.name.to_string()).collect(),
+            types: self.columns.iter().map(|c| c.datatype.clone()).collect(),
+            rows,
         }
     }
 
@@ -271,353 +168,1079 @@ impl Table {
         );
     }
 
-    pub fn add_row(&mut self, row: HashMap<String, String>) {
-        for (k, val) in row {
-            if let Some(col_data) = self.col_map.get_mut(k.as_str()) {
-                match col_data {
-                    ColumnData::Int(v) => {
-                        if val.is_empty() {
-                            v.push(None)
-                        } else {
-                            v.push(Option::from(val.parse::<i32>().unwrap()))
-                        }
-                    }
-                    ColumnData::Float(v) => {
-                        if val.is_empty() {
-                            v.push(None)
-                        } else {
-                            v.push(Option::from(val.parse::<f32>().unwrap()))
-                        }
-                    }
-                    ColumnData::Str(v) => {
-                        if val.is_empty() {
-                            v.push(None)
-                        } else {
-                            v.push(Option::from(val))
-                        }
-                    }
-                    ColumnData::Bool(v) => {
-                        if val.is_empty() {
-                            v.push(None)
-                        } else {
-                            v.push(Option::from(val.parse::<bool>().unwrap()))
-                        }
-                    }
-                    ColumnData::None => {}
+    /// Appends a row of typed values given in column order.
+    pub fn push_values(&mut self, values: Vec<Value>) {
+        self.heap.push_row();
+        for (col, val) in self.columns.iter().zip(values) {
+            self.col_map.get_mut(&col.name).unwrap().push_value(val);
+        }
+    }
+
+    /// An empty table for rows a query derives, like joint or grouped rows.
+    pub fn derived(name: &str) -> Table {
+        Table {
+            name: name.to_string(),
+            columns: vec![],
+            col_map: Default::default(),
+            foreign_keys: None,
+            heap: HeapState::default(),
+            indexes: vec![],
+        }
+    }
+
+    /// A derived table holding the rows of a query, with its columns named as in them.
+    pub fn from_rows(name: &str, rows: ResultSet) -> Table {
+        let mut tb = Table::derived(name);
+        for (column, datatype) in rows.columns.iter().zip(&rows.types) {
+            tb.add_column(ColumnAttr {
+                name: column.to_string(),
+                datatype: datatype.clone(),
+                is_pk: false,
+                is_nullable: true,
+                default: None,
+            });
+        }
+        rows.rows.into_iter().for_each(|row| tb.push_values(row));
+        tb
+    }
+
+    pub fn row_count(&self) -> usize {
+        // the rows of a query without `FROM` have no columns to count
+        self.col_map
+            .values()
+            .find_map(|col| col.count().ok())
+            .unwrap_or(self.heap.rows.len())
+    }
+
+    /// Replaces the versions at `row_ixs` with updated copies created by transaction
+    /// `txn`, returns the row ids of the copies. The old versions are only marked as
+    /// deleted, snapshots taken before the update keep reading them.
+    pub fn update_rows(
+        &mut self,
+        row_ixs: &[usize],
+        assignments: &HashMap<String, Value>,
+        txn: TxnId,
+    ) -> Vec<u64> {
+        let mut new_ixs = vec![];
+        for &row_ix in row_ixs {
+            let values = self.row_values(row_ix);
+            for (col, val) in self.columns.iter().zip(values) {
+                let val = assignments.get(&col.name).cloned().unwrap_or(val);
+                if let Some(data) = self.col_map.get_mut(&col.name) {
+                    data.push_value(val);
                 }
             }
+            self.heap.rows[row_ix].xmax = txn;
+            self.heap.push_version(txn);
+            new_ixs.push(self.heap.rows.len() - 1);
         }
+        new_ixs
+            .iter()
+            .map(|ix| self.heap.rows[*ix].rid)
+            .collect::<Vec<u64>>()
     }
 
-    pub fn get_rows(&self) -> Result<Vec<HashMap<String, String>>, Errors> {
-        let row_nums = match self
-            .col_map
-            .get(
-                &self
-                    .columns
-                    .iter()
-                    .filter(|row| row.is_pk)
-                    .map(|row| row.name.to_string())
-                    .collect::<Vec<String>>()
-                    .first()
-                    .unwrap()
-                    .to_owned(),
-            )
-            .unwrap()
-            .count()
-        {
-            Ok(v) => v,
-            Err(err) => {
-                return Err(err);
+    pub fn delete_rows(&mut self, row_ixs: Vec<usize>) {
+        self.heap.remove_rows(&row_ixs);
+        for val in self.col_map.values_mut() {
+            val.delete_val(row_ixs.clone());
+        }
+    }
+
+    /// Rejects changing rows that another transaction deleted or replaced after the
+    /// snapshot of `txn` was taken, or is about to.
+    pub fn check_write_conflicts(&self, row_ixs: &[usize], txn: TxnId) -> Result<(), Errors> {
+        for ix in row_ixs {
+            let row = &self.heap.rows[*ix];
+            if row.xmax != FROZEN && row.xmax != txn {
+                return Err(Errors::WriteConflict(self.name.to_string()));
             }
-        };
-        let rows = (0..row_nums)
-            .map(|rid| {
-                self.col_map
-                    .iter()
-                    .map(|(k, v)| {
-                        let data = v.get_data_by_ix(&vec![rid]);
-                        (
-                            k.to_owned(),
-                            data.expect(Errors::InvalidColumnType.to_string().as_str())
-                                .first()
-                                .unwrap()
-                                .to_owned(),
-                        )
-                    })
-                    .collect::<HashMap<String, String>>()
-            })
-            .collect::<Vec<HashMap<String, String>>>();
-        Ok(rows)
+        }
+        Ok(())
     }
 
-    pub fn filter_rows(
-        &self,
-        condition: &Option<Condition>,
-        rows: Vec<HashMap<String, String>>,
-        projection: Option<Vec<String>>,
-    ) -> Vec<HashMap<String, String>> {
-        match condition {
-            None => rows,
-            Some(con) => rows
-                .iter()
-                .filter(|&row| self.evaluate_condition(row, &con))
-                .clone()
-                .map(|row| {
-                    row.iter()
-                        .filter(|(col, _)| {
-                            if projection.is_some() {
-                                projection.clone().unwrap().contains(&col)
-                            } else {
-                                true
-                            }
-                        })
-                        .map(|(k, v)| (k.to_owned(), v.to_owned()))
-                        .collect::<HashMap<String, String>>()
-                })
-                .collect::<Vec<HashMap<String, String>>>(),
+    /// Removes the versions no open transaction can see anymore: those whose deletion
+    /// committed at or before `horizon`.
+    pub fn vacuum(&mut self, horizon: Csn) {
+        let dead = self
+            .heap
+            .rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| row.xmax_csn <= horizon)
+            .map(|(ix, _)| ix)
+            .collect::<Vec<usize>>();
+        if !dead.is_empty() {
+            self.delete_rows(dead);
         }
     }
 
-    pub fn join_tbs(tb1: &Table, tb2: &Table, join_info: JoinInfo) -> Table {
-        let mut joint_table = Table {
-            name: format!("{}-{}", tb1.name, tb2.name),
-            columns: vec![],
-            col_map: Default::default(),
-            foreign_keys: None,
+    /// Writes the rows changed since the last sync into the table's heap pages and
+    /// brings the table's indexes up to date with them.
+    pub fn sync(&mut self, pager: &mut Pager) -> io::Result<()> {
+        let mut heap = mem::take(&mut self.heap);
+        let result = heap.sync(pager, |ix| self.row_values(ix));
+        self.heap = heap;
+        let changes = result?;
+        for index in &mut self.indexes {
+            let col_ix = match self.columns.iter().position(|c| c.name == index.column) {
+                Some(v) => v,
+                None => continue,
+            };
+            let mut tree = BTree { root: index.root };
+            for change in &changes {
+                let old = change.old.as_ref().and_then(|v| v.get(col_ix).cloned());
+                let new = change.new.as_ref().and_then(|v| v.get(col_ix).cloned());
+                if old == new {
+                    continue;
+                }
+                if let Some(val) = old {
+                    tree.delete(pager, &(val, change.rid))?;
+                }
+                if let Some(val) = new {
+                    tree.insert(pager, (val, change.rid))?;
+                }
+            }
+            index.root = tree.root;
+        }
+        Ok(())
+    }
+
+    pub fn primary_key(&self) -> Option<&ColumnAttr> {
+        self.columns.iter().find(|col| col.is_pk)
+    }
+
+    /// Adds the unique index on the primary key column, unless the table already has
+    /// one or no primary key.
+    pub fn add_primary_key_index(&mut self, pager: &mut Pager) -> io::Result<()> {
+        if self.indexes.iter().any(|idx| idx.unique) {
+            return Ok(());
+        }
+        let column = match self.primary_key() {
+            Some(col) => col.name.to_string(),
+            None => return Ok(()),
         };
-        for col in &tb1.columns {
-            joint_table.add_column(ColumnAttr {
-                name: format!("{}.{}", tb1.name, col.name),
-                datatype: col.clone().datatype,
-                is_pk: col.is_pk,
-                is_nullable: col.is_nullable,
-                default: col.clone().default,
-            });
+        let tree = self.build_index(pager, &column)?;
+        self.indexes.push(IndexMeta {
+            name: format!("{}_pkey", self.name),
+            column,
+            root: tree.root,
+            unique: true,
+        });
+        Ok(())
+    }
+
+    /// Rejects NULL primary keys and key values that repeat within `keys` or already
+    /// belong to a row other than the one they are assigned to (`None` for a new
+    /// row). Rows that `txn` deleted and rows whose deletion committed no longer own
+    /// their key, rows of other open transactions do.
+    pub fn check_primary_keys(
+        &self,
+        pager: &mut Pager,
+        keys: &[(Value, Option<u64>)],
+        txn: TxnId,
+    ) -> Result<(), Errors> {
+        if let Some(pk) = self.primary_key() {
+            if keys.iter().any(|(key, _)| *key == Value::Null) {
+                return Err(Errors::NullValue(pk.name.to_string()));
+            }
         }
-        for col in &tb2.columns {
-            joint_table.add_column(ColumnAttr {
-                name: format!("{}.{}", tb2.name, col.name),
-                datatype: col.clone().datatype,
-                is_pk: col.is_pk,
-                is_nullable: col.is_nullable,
-                default: col.clone().default,
+        let index = match self.indexes.iter().find(|idx| idx.unique) {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        let col_data = match self.col_map.get(&index.column) {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        let tree = BTree { root: index.root };
+        let unsynced = self.heap.unsynced_rows();
+        for (ix, (key, rid)) in keys.iter().enumerate() {
+            if keys[..ix]
+                .iter()
+                .any(|(other, _)| other.total_cmp(key) == Ordering::Equal)
+            {
+                return Err(Errors::PrimaryKeyExisted(key.to_string()));
+            }
+            let mut owners = tree
+                .range(pager, Bound::Included(key), Bound::Included(key))
+                .map_err(|_| Errors::DiskSaveError)?
+                .iter()
+                .filter_map(|rid| self.heap.position(*rid))
+                .collect::<Vec<usize>>();
+            // versions that are not on disk yet are missing from the index
+            owners.extend(
+                unsynced
+                    .iter()
+                    .filter(|ix| col_data.get_value(**ix).total_cmp(key) == Ordering::Equal),
+            );
+            let taken = owners.iter().any(|ix| {
+                let row = &self.heap.rows[*ix];
+                let deleted = row.xmax != FROZEN && (row.xmax == txn || row.xmax_csn != PENDING);
+                !deleted && Some(row.rid) != *rid
             });
-        }
-        let left_col_data = tb1
-            .col_map
-            .get(&join_info.left_column)
-            .unwrap()
-            .get_all_data()
-            .unwrap();
-        let right_col_data = tb2
-            .col_map
-            .get(&join_info.right_column)
-            .unwrap()
-            .get_all_data()
-            .unwrap();
-        for (left_ix, left_row) in left_col_data.iter().enumerate() {
-            if left_row.is_empty() {
-                continue;
+            if taken {
+                return Err(Errors::PrimaryKeyExisted(key.to_string()));
             }
-            for (right_ix, right_row) in right_col_data.iter().enumerate() {
-                if right_row.is_empty() {
+        }
+        Ok(())
+    }
+
+    /// Builds a b-tree over `column` from the row versions that belong on disk, the
+    /// others are picked up by `sync` once their transaction commits.
+    pub fn build_index(&self, pager: &mut Pager, column: &str) -> io::Result<BTree> {
+        let mut tree = BTree::create(pager)?;
+        if let Some(data) = self.col_map.get(column) {
+            for (ix, row) in self.heap.rows.iter().enumerate() {
+                if !row.is_durable() {
                     continue;
                 }
-                if !right_row.eq(left_row) {
-                    continue;
+                tree.insert(pager, (data.get_value(ix), row.rid))?;
+            }
+        }
+        Ok(tree)
+    }
+
+    /// Uses the table's indexes to find the rows that may satisfy `condition`.
+    /// Comparisons, `BETWEEN` and `IN` of an indexed column with literals are answered
+    /// from its b-tree, `and` intersects the candidates and `or` merges them. Returns
+    /// `None` when the condition needs a full scan. Versions that are not on disk are
+    /// always candidates. The condition and visibility still have to be checked on
+    /// every candidate.
+    pub fn index_lookup(
+        &self,
+        pager: &mut Pager,
+        condition: &Expression,
+    ) -> io::Result<Option<Vec<usize>>> {
+        if let Expression::Binary {
+            left,
+            op: op @ (BinaryOp::And | BinaryOp::Or),
+            right,
+        } = condition
+        {
+            let left_ixs = self.index_lookup(pager, left)?;
+            let right_ixs = self.index_lookup(pager, right)?;
+            let row_ixs = match (op, left_ixs, right_ixs) {
+                (BinaryOp::And, Some(l), Some(r)) => l
+                    .into_iter()
+                    .filter(|ix| r.binary_search(ix).is_ok())
+                    .collect(),
+                (BinaryOp::And, Some(ixs), None) | (BinaryOp::And, None, Some(ixs)) => ixs,
+                (BinaryOp::Or, Some(mut l), Some(r)) => {
+                    l.extend(r);
+                    l.sort();
+                    l.dedup();
+                    l
+                }
+                _ => return Ok(None),
+            };
+            return Ok(Some(row_ixs));
+        }
+        let (column, ranges) = match index_ranges(condition) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let column = match self.resolve_column(column) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let index = match self.indexes.iter().find(|idx| idx.column == column) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let datatype = self
+            .columns
+            .iter()
+            .find(|c| c.name == column)
+            .unwrap()
+            .datatype
+            .clone();
+        let mut rids = vec![];
+        for (lo, hi) in ranges {
+            let key = |bound: Bound<&Value>| match bound {
+                Bound::Included(v) => index_key(v, &datatype).map(Bound::Included),
+                Bound::Excluded(v) => index_key(v, &datatype).map(Bound::Excluded),
+                Bound::Unbounded => Some(Bound::Unbounded),
+            };
+            let (lo, hi) = match (key(lo.as_ref()), key(hi.as_ref())) {
+                (Some(lo), Some(hi)) => (lo, hi),
+                _ => return Ok(None),
+            };
+            rids.extend(BTree { root: index.root }.range(pager, lo.as_ref(), hi.as_ref())?);
+        }
+        let mut row_ixs = rids
+            .iter()
+            .filter_map(|rid| self.heap.position(*rid))
+            .collect::<Vec<usize>>();
+        row_ixs.extend(self.heap.unsynced_rows());
+        row_ixs.sort();
+        row_ixs.dedup();
+        Ok(Some(row_ixs))
+    }
+
+    /// Replaces the table rows with the ones stored in the heap chain at `first_page`.
+    pub fn load(&mut self, pager: &mut Pager, first_page: PageId) -> io::Result<()> {
+        let (heap, rows) = HeapState::load(pager, first_page)?;
+        for values in rows {
+            for (col, val) in self.columns.iter().zip(values) {
+                if let Some(data) = self.col_map.get_mut(&col.name) {
+                    data.push_value(val);
                 }
-                let mut left_row_data = tb1.get_data_by_row(left_ix, true);
-                let right_row_data = tb2.get_data_by_row(right_ix, true);
-                left_row_data.extend(right_row_data);
-                joint_table.add_row(left_row_data);
             }
         }
-        joint_table
+        self.heap = heap;
+        Ok(())
     }
+}
 
-    pub fn get_data_by_row(&self, row_ix: usize, is_join: bool) -> HashMap<String, String> {
-        self.col_map
+/// The rows a select reads, those of a table or of a join. A row is known by its
+/// position, which only means something to the rows it comes from.
+pub trait Rows {
+    /// The name `<name>.*` selects the columns of.
+    fn name(&self) -> &str;
+
+    /// The columns `*` selects, in order.
+    fn columns(&self) -> &[ColumnAttr];
+
+    /// The column of `columns` that `name` refers to.
+    fn resolve_column(&self, name: &str) -> Option<&str>;
+
+    /// The value of column `name` in row `row_ix`, see `resolve_column`.
+    fn value_of(&self, row_ix: usize, name: &str) -> Option<Value>;
+
+    /// Positions of the rows visible to `snapshot`, of the `candidates` only when an
+    /// index already narrowed the search down.
+    fn visible_rows<'a>(
+        &'a self,
+        candidates: Option<Vec<usize>>,
+        snapshot: &'a Snapshot,
+    ) -> Box<dyn Iterator<Item = usize> + 'a>;
+
+    /// The type of column `name`, see `resolve_column`.
+    fn column_type(&self, name: &str) -> Option<DataType> {
+        let col = self.resolve_column(name)?;
+        self.columns()
             .iter()
-            .map(|(k, v)| {
-                let val = v
-                    .get_data_by_ix(vec![row_ix].as_ref())
-                    .expect(Errors::InvalidColumnType.to_string().as_str())
-                    .first()
-                    .unwrap()
-                    .to_owned();
-                let key = if is_join {
-                    format!("{}.{}", self.name, k.to_string())
-                } else {
-                    k.to_string()
-                };
-                (key, val)
-            })
-            .collect::<HashMap<String, String>>()
+            .find(|c| c.name == col)
+            .map(|c| c.datatype.clone())
     }
 
-    pub fn show_info(&self) {
-        let headers = vec!["name", "datatype", "is_pk", "is_nullable", "default"];
-        let rows = self
-            .columns
+    /// The values of row `row_ix` in the order of `columns`.
+    fn row_values(&self, row_ix: usize) -> Vec<Value> {
+        self.columns()
             .iter()
-            .map(|c| c.attr())
-            .collect::<Vec<HashMap<String, String>>>();
-        let mut pt = PrettyTable::create(
-            self.name.to_string(),
-            headers
-                .iter()
-                .map(|c| c.to_string())
-                .collect::<Vec<String>>(),
-            rows,
-        );
-        if self.foreign_keys.is_some() {
-            let foreign_keys = self.foreign_keys.as_ref().unwrap();
-            let fk_str = foreign_keys
-                .iter()
-                .map(|x| format!("{}---{}.{}", x.col_a, x.table, x.col_b))
-                .collect::<Vec<String>>();
-            pt.add_more("Foreign Keys".to_string(), fk_str);
+            .map(|col| self.value_of(row_ix, &col.name).unwrap_or(Value::Null))
+            .collect::<Vec<Value>>()
+    }
+
+    /// Whether `name` is a column of several of the tables the rows come from.
+    fn is_ambiguous(&self, _: &str) -> bool {
+        false
+    }
+
+    /// `err` telling a column several tables have apart from a missing one.
+    fn column_error(&self, err: Errors) -> Errors {
+        match err {
+            Errors::ColumnNotExisted(name) if self.is_ambiguous(&name) => {
+                Errors::AmbiguousColumn(name)
+            }
+            err => err,
         }
-        println!("{pt}");
     }
-    pub fn print_table_data(&self) {
-        let mut p_table = PTable::new();
 
-        let cnames = self
-            .columns
-            .iter()
-            .map(|col| col.name.to_string())
-            .collect::<Vec<String>>();
+    /// The `visible_rows` satisfying `condition`, checked only as they are taken.
+    fn scan_rows<'a>(
+        &'a self,
+        condition: &'a Option<Expression>,
+        candidates: Option<Vec<usize>>,
+        snapshot: &'a Snapshot,
+    ) -> Box<dyn Iterator<Item = Result<usize, Errors>> + 'a> {
+        let rows = self
+            .visible_rows(candidates, snapshot)
+            .filter_map(move |ix| match condition {
+                None => Some(Ok(ix)),
+                Some(con) => match con.is_true(&|name| self.value_of(ix, name)) {
+                    Ok(true) => Some(Ok(ix)),
+                    Ok(false) => None,
+                    Err(err) => Some(Err(err)),
+                },
+            });
+        Box::new(rows)
+    }
 
-        let header_row = Row::new(
-            cnames
-                .iter()
-                .map(|col| Cell::new(&col))
-                .collect::<Vec<Cell>>(),
-        );
+    /// Like `scan_rows`, but checks all the rows at once.
+    fn matching_rows(
+        &self,
+        condition: &Option<Expression>,
+        candidates: Option<Vec<usize>>,
+        snapshot: &Snapshot,
+    ) -> Result<Vec<usize>, Errors> {
+        self.scan_rows(condition, candidates, snapshot).collect()
+    }
 
-        let first_col_data = self
-            .col_map
-            .get(&self.columns.first().unwrap().name)
-            .unwrap();
-        let num_rows = match first_col_data.count() {
-            Ok(v) => v,
-            Err(err) => {
-                err.print();
-                return;
+    fn select_data(
+        &self,
+        query: SelectQuery,
+        candidates: Option<Vec<usize>>,
+        snapshot: &Snapshot,
+    ) -> Result<ResultSet, Errors> {
+        if !query.group_by.is_empty() || !query.aggregates.is_empty() {
+            // `LIMIT 0` only asks for the columns
+            let row_ixs = match query.limit {
+                Some(0) => vec![],
+                _ => self.matching_rows(&query.condition, candidates, snapshot)?,
+            };
+            let grouped = group_rows(
+                self,
+                &query.group_by,
+                &query.aggregates,
+                &query.projection,
+                &row_ixs,
+            )?;
+            // `HAVING` filters the groups like `WHERE` filters rows
+            let query = SelectQuery {
+                condition: query.having,
+                group_by: vec![],
+                having: None,
+                aggregates: vec![],
+                ..query
+            };
+            return grouped.select_data(query, None, snapshot);
+        }
+        let columns = self.output_columns(&query.projection)?;
+        let types = columns
+            .iter()
+            .map(|(_, expr)| expr.data_type(&|name| self.column_type(name)))
+            .collect::<Result<Vec<DataType>, Errors>>()?;
+        let limit = query.limit.unwrap_or(usize::MAX);
+        let row_ixs = if query.order_by.is_empty() && query.distinct.is_none() || limit == 0 {
+            // without a sort the scan stops once the page is full
+            self.scan_rows(&query.condition, candidates, snapshot)
+                .take(query.offset.saturating_add(limit))
+                .collect::<Result<Vec<usize>, Errors>>()?
+                .into_iter()
+                .skip(query.offset)
+                .collect::<Vec<usize>>()
+        } else {
+            let mut row_ixs = self.matching_rows(&query.condition, candidates, snapshot)?;
+            self.sort_rows(&mut row_ixs, &query.order_by, &columns)?;
+            if let Some(Distinct::On(exprs)) = &query.distinct {
+                self.distinct_on(&mut row_ixs, exprs, &columns)?;
+            }
+            // duplicates of `DISTINCT` are only known once the rows are projected
+            match query.distinct {
+                Some(Distinct::Rows) => row_ixs,
+                _ => row_ixs
+                    .into_iter()
+                    .skip(query.offset)
+                    .take(limit)
+                    .collect::<Vec<usize>>(),
             }
         };
-        let mut print_table_rows: Vec<Row> = vec![Row::new(vec![]); num_rows];
-
-        for col_name in &cnames {
-            let col_val = self
-                .col_map
-                .get(col_name)
-                .expect("Can't find any rows with the given column");
-            let columns: Vec<String> = match col_val.get_all_data() {
-                Ok(v) => v,
-                Err(err) => {
-                    err.print();
-                    return;
-                }
-            };
+        let rows = row_ixs
+            .into_iter()
+            .map(|ix| {
+                columns
+                    .iter()
+                    .map(|(_, expr)| expr.eval(&|name| self.value_of(ix, name)))
+                    .collect::<Result<Vec<Value>, Errors>>()
+            })
+            .collect::<Result<Vec<Vec<Value>>, Errors>>()?;
+        let mut result = ResultSet {
+            columns: columns.into_iter().map(|(name, _)| name).collect(),
+            types,
+            rows,
+        };
+        if let Some(Distinct::Rows) = query.distinct {
+            result.dedup();
+            result.rows = result
+                .rows
+                .into_iter()
+                .skip(query.offset)
+                .take(limit)
+                .collect();
+        }
+        Ok(result)
+    }
 
-            for i in 0..num_rows {
-                print_table_rows[i].add_cell(Cell::new(&columns[i]));
+    /// The select list as named expressions, wildcards are expanded to the columns
+    /// they cover.
+    fn output_columns(
+        &self,
+        projection: &[Projection],
+    ) -> Result<Vec<(String, Expression)>, Errors> {
+        let mut columns = vec![];
+        for proj in projection {
+            match proj {
+                Projection::Expr { expr, name } => columns.push((name.to_string(), expr.clone())),
+                Projection::Wildcard(table) => {
+                    // `t.*` of a joint table covers the columns named `t.<col>`
+                    let covered = self
+                        .columns()
+                        .iter()
+                        .filter(|c| match table {
+                            None => true,
+                            Some(t) => *t == self.name() || c.name.starts_with(&format!("{}.", t)),
+                        })
+                        .map(|c| (c.name.to_string(), Expression::Column(c.name.to_string())))
+                        .collect::<Vec<(String, Expression)>>();
+                    if let (Some(t), true) = (table, covered.is_empty()) {
+                        return Err(Errors::TableNotExisted(t.to_string()));
+                    }
+                    columns.extend(covered);
+                }
             }
         }
+        Ok(columns)
+    }
 
-        p_table.add_row(header_row);
-        for row in print_table_rows {
-            p_table.add_row(row);
+    /// Sorts `row_ixs` by the `ORDER BY` items. Names of the output `columns` come
+    /// before the table's own columns and positions refer to them.
+    fn sort_rows(
+        &self,
+        row_ixs: &mut [usize],
+        order_by: &[OrderBy],
+        columns: &[(String, Expression)],
+    ) -> Result<(), Errors> {
+        let mut keys = vec![];
+        for item in order_by {
+            let key = match &item.key {
+                OrderKey::Column(name) => match columns.iter().find(|(n, _)| n == name) {
+                    Some((_, expr)) => expr.clone(),
+                    None => Expression::Column(name.to_string()),
+                },
+                OrderKey::Position(pos) => match columns.get(pos - 1) {
+                    Some((_, expr)) => expr.clone(),
+                    None => return Err(Errors::InvalidOrderPosition(pos.to_string())),
+                },
+            };
+            key.data_type(&|name| self.column_type(name))?;
+            keys.push(key);
+        }
+        let mut sorted = row_ixs
+            .iter()
+            .map(|ix| {
+                keys.iter()
+                    .map(|key| key.eval(&|name| self.value_of(*ix, name)))
+                    .collect::<Result<Vec<Value>, Errors>>()
+                    .map(|values| (*ix, values))
+            })
+            .collect::<Result<Vec<(usize, Vec<Value>)>, Errors>>()?;
+        sorted.sort_by(|(_, a), (_, b)| {
+            a.iter()
+                .zip(b)
+                .zip(order_by)
+                .map(|((x, y), item)| x.sort_cmp(y, item.asc, item.nulls_first))
+                .find(|ord| ord.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        for (slot, (ix, _)) in row_ixs.iter_mut().zip(sorted) {
+            *slot = ix;
         }
+        Ok(())
+    }
 
-        p_table.printstd();
+    /// Keeps the first of the rows `row_ixs` with equal values of `exprs`, which name
+    /// output `columns` like `ORDER BY` items do.
+    fn distinct_on(
+        &self,
+        row_ixs: &mut Vec<usize>,
+        exprs: &[Expression],
+        columns: &[(String, Expression)],
+    ) -> Result<(), Errors> {
+        let mut keys = vec![];
+        for expr in exprs {
+            let key = match expr {
+                Expression::Column(name) => match columns.iter().find(|(n, _)| n == name) {
+                    Some((_, expr)) => expr,
+                    None => expr,
+                },
+                expr => expr,
+            };
+            key.data_type(&|name| self.column_type(name))?;
+            keys.push(key);
+        }
+        let mut seen = BTreeSet::new();
+        let mut kept = vec![];
+        for ix in row_ixs.iter() {
+            let values = keys
+                .iter()
+                .map(|key| key.eval(&|name| self.value_of(*ix, name)))
+                .collect::<Result<Vec<Value>, Errors>>()?;
+            if seen.insert(RowKey(values)) {
+                kept.push(*ix);
+            }
+        }
+        *row_ixs = kept;
+        Ok(())
     }
 }
 
-pub struct PrettyTable {
-    pub name: String,
-    pub header: Vec<String>,
-    pub values: HashMap<String, Vec<String>>,
-    pub rows: Vec<HashMap<String, String>>,
-    pub others: HashMap<String, Vec<String>>,
-}
+impl Rows for Table {
+    fn name(&self) -> &str {
+        &self.name
+    }
 
-impl PrettyTable {
-    pub fn new(
-        name: String,
-        header: Vec<String>,
-        values: HashMap<String, Vec<String>>,
-    ) -> PrettyTable {
-        return PrettyTable {
-            name,
-            header,
-            values,
-            rows: vec![],
-            others: HashMap::new(),
-        };
+    fn columns(&self) -> &[ColumnAttr] {
+        &self.columns
     }
-    pub fn create(
-        name: String,
-        header: Vec<String>,
-        rows: Vec<HashMap<String, String>>,
-    ) -> PrettyTable {
-        return PrettyTable {
-            name,
-            header,
-            values: HashMap::new(),
-            rows,
-            others: HashMap::new(),
-        };
+
+    // `name` as it is, qualified with the table's name or, in a table derived from
+    // joint rows, by the part after the table name when that is unambiguous
+    fn resolve_column(&self, name: &str) -> Option<&str> {
+        if let Some(col) = self.columns.iter().find(|c| c.name == name) {
+            return Some(&col.name);
+        }
+        if let Some(col) = name.strip_prefix(&format!("{}.", self.name)) {
+            return self
+                .columns
+                .iter()
+                .find(|c| c.name == col)
+                .map(|c| c.name.as_str());
+        }
+        let suffix = format!(".{}", name);
+        let mut found = self.columns.iter().filter(|c| c.name.ends_with(&suffix));
+        match (found.next(), found.next()) {
+            (Some(col), None) => Some(&col.name),
+            _ => None,
+        }
     }
-    pub fn add_more(&mut self, key: String, val: Vec<String>) {
-        self.others.insert(key, val);
+
+    fn value_of(&self, row_ix: usize, name: &str) -> Option<Value> {
+        self.resolve_column(name)
+            .map(|col| self.col_map.get(col).unwrap().get_value(row_ix))
+    }
+
+    fn visible_rows<'a>(
+        &'a self,
+        candidates: Option<Vec<usize>>,
+        snapshot: &'a Snapshot,
+    ) -> Box<dyn Iterator<Item = usize> + 'a> {
+        let rows = candidates
+            .unwrap_or_else(|| (0..self.row_count()).collect())
+            .into_iter()
+            .filter(move |ix| snapshot.sees(&self.heap.rows[*ix]));
+        Box::new(rows)
+    }
+
+    fn row_values(&self, row_ix: usize) -> Vec<Value> {
+        self.columns
+            .iter()
+            .map(|col| match self.col_map.get(&col.name) {
+                Some(data) => data.get_value(row_ix),
+                None => Value::Null,
+            })
+            .collect::<Vec<Value>>()
     }
 }
 
-impl fmt::Display for PrettyTable {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let mut pt = PTable::new();
-        let header_row = Row::new(
-            self.header
-                .iter()
-                .map(|col| Cell::new(&col).with_style(Attr::Bold))
-                .collect::<Vec<Cell>>(),
-        );
-        let mut pt_rows: Vec<Row> = vec![];
-        if !self.rows.is_empty() {
-            let num_rows = self.rows.len();
-            pt_rows = vec![Row::new(vec![]); num_rows];
-            for col in &self.header {
-                for i in 0..num_rows {
-                    pt_rows[i].add_cell(Cell::new(self.rows[i].get(col).unwrap()));
-                }
+type KeyRange = (Bound<Value>, Bound<Value>);
+
+// the column and the key ranges of a condition an index can answer on its own
+fn index_ranges(condition: &Expression) -> Option<(&str, Vec<KeyRange>)> {
+    let point = |v: &Value| (Bound::Included(v.clone()), Bound::Included(v.clone()));
+    match condition {
+        Expression::Binary { left, op, right } if op.is_comparison() => {
+            let (column, op, value) = match (left.as_ref(), right.as_ref()) {
+                (Expression::Column(c), Expression::Literal(v)) => (c, *op, v),
+                (Expression::Literal(v), Expression::Column(c)) => (c, op.flip(), v),
+                _ => return None,
+            };
+            if value.is_null() {
+                return None;
             }
-        } else if !self.values.is_empty() {
-            let num_rows = self.values.get(&self.header[0]).unwrap_or(&vec![]).len();
-            pt_rows = vec![Row::new(vec![]); num_rows];
-            for col in &self.header {
-                let col_vals = self.values.get(col).unwrap();
-                for i in 0..num_rows {
-                    pt_rows[i].add_cell(Cell::new(&col_vals[i]));
+            let range = match op {
+                BinaryOp::Eq => point(value),
+                BinaryOp::Lt => (Bound::Unbounded, Bound::Excluded(value.clone())),
+                BinaryOp::LtEq => (Bound::Unbounded, Bound::Included(value.clone())),
+                BinaryOp::Gt => (Bound::Excluded(value.clone()), Bound::Unbounded),
+                BinaryOp::GtEq => (Bound::Included(value.clone()), Bound::Unbounded),
+                _ => return None,
+            };
+            Some((column, vec![range]))
+        }
+        Expression::IsNull {
+            expr,
+            negated: false,
+        } => match expr.as_ref() {
+            Expression::Column(c) => Some((c, vec![point(&Value::Null)])),
+            _ => None,
+        },
+        Expression::Between {
+            expr,
+            low,
+            high,
+            negated: false,
+        } => match (expr.as_ref(), low.as_ref(), high.as_ref()) {
+            (Expression::Column(c), Expression::Literal(lo), Expression::Literal(hi))
+                if !lo.is_null() && !hi.is_null() =>
+            {
+                Some((
+                    c,
+                    vec![(Bound::Included(lo.clone()), Bound::Included(hi.clone()))],
+                ))
+            }
+            _ => None,
+        },
+        Expression::InList {
+            expr,
+            list,
+            negated: false,
+        } => {
+            let column = match expr.as_ref() {
+                Expression::Column(c) => c,
+                _ => return None,
+            };
+            let mut ranges = vec![];
+            for item in list {
+                match item {
+                    Expression::Literal(v) if !v.is_null() => ranges.push(point(v)),
+                    _ => return None,
                 }
             }
+            Some((column, ranges))
         }
-        pt.add_row(header_row);
-        for row in pt_rows {
-            pt.add_row(row);
-        }
-        if !self.others.is_empty() {
-            let col_num = self.header.len() - 1;
-            for (k, v) in &self.others {
-                pt.add_row(Row::new(vec![
-                    Cell::new(k.as_str()).style_spec("Fb"),
-                    Cell::new(v.join(" ").as_str()).style_spec(format!("H{}", col_num).as_str()),
-                ]));
-            }
+        _ => None,
+    }
+}
+
+// a literal as it is stored in an index over a column of `datatype`, `None` when the
+// index cannot be searched for it
+fn index_key(value: &Value, datatype: &DataType) -> Option<Value> {
+    match (datatype, value) {
+        (DataType::Int, Value::Int(v)) => i32::try_from(*v).ok().map(|v| Value::Int(v as i64)),
+        // float keys are the stored f32 widened to f64
+        (DataType::Float, Value::Int(_) | Value::Float(_)) => {
+            Some(Value::Float(widen(value.as_float().unwrap() as f32)))
         }
-        write!(f, "{}\n{}", self.name, pt)
+        // NULLs are indexed too, for `IS NULL`
+        (DataType::String, Value::Str(_)) | (DataType::Bool, Value::Bool(_)) | (_, Value::Null) => {
+            Some(value.clone())
+        }
+        _ => None,
+    }
+}
+
+#[test]
+fn test_select_projections() {
+    use crate::database::join::JoinRows;
+    use crate::parser::join::JoinType;
+    use crate::parser::select::SelectQuery;
+    use crate::parser::utils::parse_sql;
+
+    let table = |sql: &str, rows: Vec<Vec<Value>>| {
+        let mut tb = Table::new(CreateQuery::format_stat(parse_sql(sql).unwrap()).unwrap());
+        rows.into_iter().for_each(|row| tb.push_values(row));
+        tb
+    };
+    let str = |s: &str| Value::Str(s.to_string());
+    let items = table(
+        "CREATE TABLE items (id INT PRIMARY KEY, name TEXT, price FLOAT, qty INT)",
+        vec![
+            vec![Value::Int(1), str("pen"), Value::Float(1.5), Value::Int(4)],
+            vec![Value::Int(2), str("ink"), Value::Float(3.0), Value::Int(1)],
+            vec![Value::Int(3), Value::Null, Value::Null, Value::Int(2)],
+        ],
+    );
+    let snapshot = Snapshot { txn: 1, csn: 0 };
+    let select = |tb: &dyn Rows, sql: &str| {
+        let query = SelectQuery::format_stat(parse_sql(sql).unwrap()).unwrap();
+        tb.select_data(query, None, &snapshot)
+    };
+
+    let result = select(
+        &items,
+        "SELECT id, price * qty AS total, qty + 1, name || '!' FROM items ORDER BY total DESC",
+    )
+    .unwrap();
+    assert_eq!(
+        result.columns,
+        vec!["id", "total", "qty + 1", "name || '!'"]
+    );
+    assert_eq!(
+        result.types,
+        vec![
+            DataType::Int,
+            DataType::Float,
+            DataType::Int,
+            DataType::String
+        ]
+    );
+    assert_eq!(
+        result.rows,
+        vec![
+            vec![Value::Int(3), Value::Null, Value::Int(3), Value::Null],
+            vec![Value::Int(1), Value::Float(6.0), Value::Int(5), str("pen!")],
+            vec![Value::Int(2), Value::Float(3.0), Value::Int(2), str("ink!")],
+        ]
+    );
+    assert!(matches!(
+        select(&items, "SELECT name * 2 FROM items"),
+        Err(Errors::TypeMismatch(_))
+    ));
+
+    let orders = table(
+        "CREATE TABLE orders (id INT PRIMARY KEY, item INT)",
+        vec![vec![Value::Int(7), Value::Int(2)]],
+    );
+    let on = Expression::Binary {
+        left: Box::new(Expression::Column("orders.item".to_string())),
+        op: BinaryOp::Eq,
+        right: Box::new(Expression::Column("items.id".to_string())),
+    };
+    let joint = JoinRows::new("orders", &orders, &snapshot)
+        .join(
+            JoinRows::new("items", &items, &snapshot),
+            &JoinType::Inner,
+            &Some(on),
+        )
+        .unwrap();
+    let result = select(
+        &joint,
+        "SELECT items.*, orders.id AS order_id FROM orders JOIN items ON orders.item = items.id",
+    )
+    .unwrap();
+    assert_eq!(
+        result.columns,
+        vec![
+            "items.id",
+            "items.name",
+            "items.price",
+            "items.qty",
+            "order_id"
+        ]
+    );
+    assert_eq!(result.rows[0][4], Value::Int(7));
+    assert!(matches!(
+        select(
+            &joint,
+            "SELECT users.* FROM orders JOIN items ON orders.item = items.id"
+        ),
+        Err(Errors::TableNotExisted(_))
+    ));
+}
+
+#[test]
+fn test_select_distinct() {
+    use crate::parser::utils::parse_sql;
+
+    let create =
+        parse_sql("CREATE TABLE scores (id INT PRIMARY KEY, name TEXT, score INT)").unwrap();
+    let mut tb = Table::new(CreateQuery::format_stat(create).unwrap());
+    let (int, str) = (Value::Int, |s: &str| Value::Str(s.to_string()));
+    for (id, name, score) in [
+        (1, str("ann"), int(3)),
+        (2, str("bob"), int(5)),
+        (3, str("ann"), int(3)),
+        (4, str("ann"), int(7)),
+        (5, Value::Null, Value::Null),
+        (6, Value::Null, Value::Null),
+    ] {
+        tb.push_values(vec![int(id), name, score]);
+    }
+    let snapshot = Snapshot { txn: 1, csn: 0 };
+    let select = |sql: &str| {
+        let query = SelectQuery::format_stat(parse_sql(sql).unwrap()).unwrap();
+        tb.select_data(query, None, &snapshot).unwrap().rows
+    };
+
+    // NULLs are duplicates of each other
+    assert_eq!(
+        select("SELECT DISTINCT name, score FROM scores"),
+        vec![
+            vec![str("ann"), int(3)],
+            vec![str("bob"), int(5)],
+            vec![str("ann"), int(7)],
+            vec![Value::Null, Value::Null],
+        ]
+    );
+    assert_eq!(
+        select("SELECT DISTINCT score FROM scores ORDER BY score DESC LIMIT 2 OFFSET 1"),
+        vec![vec![int(7)], vec![int(5)]]
+    );
+    assert_eq!(
+        select("SELECT DISTINCT ON (name) name, id FROM scores ORDER BY name, score DESC"),
+        vec![
+            vec![str("ann"), int(4)],
+            vec![str("bob"), int(2)],
+            vec![Value::Null, int(5)],
+        ]
+    );
+    assert_eq!(
+        select("SELECT DISTINCT ON (n) UPPER(name) AS n FROM scores WHERE id < 5 ORDER BY id DESC"),
+        vec![vec![str("ANN")], vec![str("BOB")]]
+    );
+    assert_eq!(
+        select("SELECT COUNT(DISTINCT score), COUNT(score) FROM scores"),
+        vec![vec![int(3), int(4)]]
+    );
+}
+
+#[test]
+fn test_parse_rows() {
+    use crate::parser::utils::parse_sql;
+
+    let create = parse_sql("CREATE TABLE t (id INT PRIMARY KEY, n INT, f FLOAT, ok BOOLEAN)");
+    let tb = Table::new(CreateQuery::format_stat(create.unwrap()).unwrap());
+    let row = |values: &[&str]| {
+        values
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<String>>()
+    };
+    let cols = row(&["ok", "id", "n"]);
+    assert_eq!(
+        tb.parse_rows(&cols, &[row(&["true", "1", "NULL"])])
+            .unwrap(),
+        vec![vec![
+            Value::Int(1),
+            Value::Null,
+            Value::Null,
+            Value::Bool(true)
+        ]]
+    );
+    // a bad literal of a later row fails the whole insert
+    for bad in [
+        row(&["true", "2", "x"]),
+        row(&["yes", "2", "3"]),
+        row(&["true", "2"]),
+    ] {
+        assert!(tb
+            .parse_rows(&cols, &[row(&["true", "1", "2"]), bad])
+            .is_err());
     }
+    assert!(matches!(
+        tb.parse_rows(&[], &[row(&["1", "2", "2.5", "x"])]),
+        Err(Errors::InvalidColumnType)
+    ));
+
+    let assignments = |pairs: &[(&str, &str)]| {
+        tb.parse_assignments(
+            &pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        )
+    };
+    assert_eq!(
+        assignments(&[("f", "2.5")]).unwrap()["f"],
+        Value::Float(2.5)
+    );
+    assert!(matches!(
+        assignments(&[("n", "'x'")]),
+        Err(Errors::InvalidColumnType)
+    ));
+    assert!(matches!(
+        assignments(&[("m", "1")]),
+        Err(Errors::ColumnNotExisted(_))
+    ));
+}
+
+#[test]
+fn test_float_values() {
+    use crate::parser::utils::parse_sql;
+
+    let create = parse_sql("CREATE TABLE t (id INT PRIMARY KEY, f FLOAT)").unwrap();
+    let mut tb = Table::new(CreateQuery::format_stat(create).unwrap());
+    let rows = vec![vec!["1".to_string(), "0.1".to_string()]];
+    for row in tb.parse_rows(&[], &rows).unwrap() {
+        tb.push_values(row);
+    }
+    let snapshot = Snapshot { txn: 1, csn: 0 };
+    let query =
+        SelectQuery::format_stat(parse_sql("SELECT f, f * 3 FROM t WHERE f = 0.1").unwrap());
+    assert_eq!(
+        tb.select_data(query.unwrap(), None, &snapshot)
+            .unwrap()
+            .rows,
+        vec![vec![Value::Float(0.1), Value::Float(0.1 * 3.0)]]
+    );
+}
+
+#[test]
+fn test_select_order() {
+    use crate::parser::utils::parse_sql;
+
+    let create = parse_sql("CREATE TABLE t (id INT PRIMARY KEY, score INT)").unwrap();
+    let mut tb = Table::new(CreateQuery::format_stat(create).unwrap());
+    let int = Value::Int;
+    for (id, score) in [(1, int(5)), (2, Value::Null), (3, int(9)), (4, int(5))] {
+        tb.push_values(vec![int(id), score]);
+    }
+    let snapshot = Snapshot { txn: 1, csn: 0 };
+    let ids = |sql: &str| {
+        let query = SelectQuery::format_stat(parse_sql(sql).unwrap())?;
+        tb.select_data(query, None, &snapshot).map(|result| {
+            result
+                .rows
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<Value>>()
+        })
+    };
+
+    // NULLs come first with DESC unless told otherwise, and last with ASC
+    assert_eq!(
+        ids("SELECT id FROM t ORDER BY score DESC, id").unwrap(),
+        vec![int(2), int(3), int(1), int(4)]
+    );
+    assert_eq!(
+        ids("SELECT id FROM t ORDER BY score DESC NULLS LAST, id DESC").unwrap(),
+        vec![int(3), int(4), int(1), int(2)]
+    );
+    assert_eq!(
+        ids("SELECT id FROM t ORDER BY score NULLS FIRST, id").unwrap(),
+        vec![int(2), int(1), int(4), int(3)]
+    );
+    assert_eq!(
+        ids("SELECT id, score FROM t ORDER BY 2 DESC NULLS LAST, 1").unwrap(),
+        vec![int(3), int(1), int(4), int(2)]
+    );
+    // positions count from 1 up to the number of selected columns
+    for sql in [
+        "SELECT id, score FROM t ORDER BY 3",
+        "SELECT id FROM t ORDER BY 0",
+    ] {
+        assert!(matches!(ids(sql), Err(Errors::InvalidOrderPosition(_))));
+    }
+}
+
+#[test]
+fn test_select_pages() {
+    use crate::parser::utils::parse_sql;
+
+    let create = parse_sql("CREATE TABLE t (id INT PRIMARY KEY, score INT)").unwrap();
+    let mut tb = Table::new(CreateQuery::format_stat(create).unwrap());
+    let int = Value::Int;
+    for (id, score) in [(1, 30), (2, 10), (3, 50), (4, 20), (5, 40)] {
+        tb.push_values(vec![int(id), int(score)]);
+    }
+    let snapshot = Snapshot { txn: 1, csn: 0 };
+    let ids = |sql: &str| {
+        let query = SelectQuery::format_stat(parse_sql(sql).unwrap()).unwrap();
+        let result = tb.select_data(query, None, &snapshot).unwrap();
+        result
+            .rows
+            .into_iter()
+            .map(|row| row[0].clone())
+            .collect::<Vec<Value>>()
+    };
+
+    // an offset past the end gives no rows, with or without a sort
+    assert_eq!(ids("SELECT id FROM t LIMIT 2 OFFSET 5"), vec![]);
+    assert_eq!(ids("SELECT id FROM t ORDER BY score OFFSET 9"), vec![]);
+    assert_eq!(ids("SELECT id FROM t OFFSET 4"), vec![int(5)]);
+    // the page is cut from the sorted rows
+    assert_eq!(
+        ids("SELECT id FROM t ORDER BY score DESC FETCH FIRST 2 ROWS ONLY"),
+        vec![int(3), int(5)]
+    );
+    assert_eq!(
+        ids("SELECT id FROM t ORDER BY score OFFSET 1 ROWS FETCH FIRST 3 ROWS ONLY"),
+        vec![int(4), int(1), int(5)]
+    );
+    assert_eq!(
+        ids("SELECT id FROM t ORDER BY score FETCH FIRST ROW ONLY"),
+        vec![int(2)]
+    );
 }
@@ -0,0 +1,63 @@
+//! SimpleDB as a library: open a [`Connection`] to a database of `sql_files` and run
+//! statements on it, queries return their rows as typed [`Value`]s.
+//!
+//! ```no_run
+//! use simpledb::{Connection, Value};
+//!
+//! let mut conn = Connection::open("shop").unwrap();
+//! conn.execute("INSERT INTO items (id, name) VALUES (1, 'pen')").unwrap();
+//! let items = conn.query("SELECT id, name FROM items").unwrap();
+//! for row in &items {
+//!     assert_eq!(row[0], Value::Int(1));
+//! }
+//! ```
+
+mod connection;
+pub(crate) mod database;
+pub(crate) mod parser;
+pub(crate) mod server;
+pub(crate) mod storage;
+pub(crate) mod system;
+
+pub use crate::connection::Connection;
+pub use crate::database::base::{DataType, Value};
+pub use crate::database::join::{set_strategy as set_join_strategy, JoinStrategy};
+pub use crate::database::query::set_max_recursion;
+pub use crate::database::result::ResultSet;
+pub use crate::storage::lock::set_timeout as set_lock_timeout;
+pub use crate::system::errors::Errors as Error;
+
+/// What the `SimpleDB` binary runs on top of the library: the REPL and the servers.
+#[doc(hidden)]
+pub mod cli {
+    pub use crate::database::join::{set_strategy as set_join_strategy, JoinStrategy};
+    pub use crate::database::query::set_max_recursion;
+    pub use crate::database::session::Session;
+    pub use crate::server::{serve, Protocol};
+    pub use crate::storage::lock::set_timeout;
+    pub use crate::system::commands::parser::process_command;
+    pub use crate::system::dbs::DbSystem;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::utils::parse_sql;
+
+    #[test]
+    fn test_create_table_new() {
+        let sql = "CREATE TABLE employees (
+        id INT PRIMARY KEY,
+        name VARCHAR(100) NOT NULL DEFAULT Tom,
+        role VARCHAR(100),
+        department_id INT DEFAULT 0,
+        abcd_id INT DEFAULT 0,
+        abcd_x INT DEFAULT 0,
+        email VARCHAR(100) UNIQUE,
+        FOREIGN KEY (department_id) REFERENCES departments(id),
+        FOREIGN KEY (abcd_id) REFERENCES abcds(id),
+        FOREIGN KEY (abcd_x) REFERENCES abcds(x)
+    );";
+        let state = parse_sql(sql);
+        assert!(state.is_ok());
+    }
+}
@@ -1,14 +1,59 @@
+use simpledb::cli::{
+    process_command, serve, set_join_strategy, set_max_recursion, set_timeout, DbSystem,
+    JoinStrategy, Protocol, Session,
+};
 use std::io::{stdin, stdout, Write};
-use system::commands::parser::process_command;
-
-mod database;
-mod parser;
-mod system;
-
-use crate::system::dbs::DbSystem;
 
 fn main() {
+    let args = std::env::args().collect::<Vec<String>>();
+    if let Some(ix) = args.iter().position(|arg| arg == "--lock-timeout") {
+        match args.get(ix + 1).and_then(|ms| ms.parse::<u64>().ok()) {
+            Some(ms) => set_timeout(ms),
+            None => {
+                eprintln!("--lock-timeout expects a number of milliseconds.");
+                return;
+            }
+        }
+    }
+    if let Some(ix) = args.iter().position(|arg| arg == "--join") {
+        match args.get(ix + 1).map(|v| v.as_str()) {
+            Some("hash") => set_join_strategy(JoinStrategy::Hash),
+            Some("merge") => set_join_strategy(JoinStrategy::Merge),
+            _ => {
+                eprintln!("--join expects hash or merge.");
+                return;
+            }
+        }
+    }
+    if let Some(ix) = args.iter().position(|arg| arg == "--max-recursion") {
+        match args.get(ix + 1).and_then(|n| n.parse::<usize>().ok()) {
+            Some(n) => set_max_recursion(n),
+            None => {
+                eprintln!("--max-recursion expects a number of rounds.");
+                return;
+            }
+        }
+    }
     DbSystem::init_cfg();
+    if args.iter().any(|arg| arg == "--server") {
+        let option = |name: &str| {
+            let ix = args.iter().position(|arg| arg == name)?;
+            args.get(ix + 1).map(|v| v.to_string())
+        };
+        let protocol = option("--protocol").unwrap_or("mysql".to_string());
+        let protocol = match Protocol::new(&protocol) {
+            Some(v) => v,
+            None => {
+                eprintln!("Unknown protocol {}.", protocol);
+                return;
+            }
+        };
+        let addr = option("--listen").unwrap_or(protocol.default_addr().to_string());
+        if let Err(err) = serve(protocol, &addr) {
+            eprintln!("Failed to listen on {}: {}", addr, err);
+        }
+        return;
+    }
     let sys: DbSystem = DbSystem::new();
     let mut command = String::new();
     loop {
@@ -30,40 +75,23 @@ fn main() {
         command.clear()
     }
     command.clear();
-    let mut db = database::db::Database::new();
+    let mut session = Session::new();
     loop {
-        if db.db_name.is_empty() {
+        if session.db_name().is_empty() {
             print!("simple-db> ");
+        } else if session.in_transaction() {
+            print!("simple-db[{}*]> ", session.db_name());
         } else {
-            print!("simple-db[{}]> ", db.db_name);
+            print!("simple-db[{}]> ", session.db_name());
         }
         stdout().flush().unwrap();
-        stdin()
+        let read = stdin()
             .read_line(&mut command)
             .expect("Error while trying to read from stdin");
-        process_command(command.trim().to_string(), &mut db);
+        if read == 0 {
+            break;
+        }
+        process_command(command.trim().to_string(), &mut session);
         command.clear();
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use crate::parser::utils::parse_sql;
-
-    #[test]
-    fn test_create_table_new() {
-        let sql = "CREATE TABLE employees (
-        id INT PRIMARY KEY,
-        name VARCHAR(100) NOT NULL DEFAULT Tom,
-        role VARCHAR(100),
-        department_id INT DEFAULT 0,
-        abcd_id INT DEFAULT 0,
-        abcd_x INT DEFAULT 0,
-        email VARCHAR(100) UNIQUE,
-        FOREIGN KEY (department_id) REFERENCES departments(id),
-        FOREIGN KEY (abcd_id) REFERENCES abcds(id),
-        FOREIGN KEY (abcd_x) REFERENCES abcds(x)
-    );";
-        let state = parse_sql(sql);
-    }
-}
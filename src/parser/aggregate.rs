@@ -0,0 +1,130 @@
+use crate::system::errors::Errors;
+use sqlparser::ast::{Expr, Function, FunctionArg, FunctionArgExpr};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggFunc {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggFunc {
+    pub fn new(name: &str) -> Option<AggFunc> {
+        match name.to_lowercase().as_str() {
+            "count" => Some(AggFunc::Count),
+            "sum" => Some(AggFunc::Sum),
+            "avg" => Some(AggFunc::Avg),
+            "min" => Some(AggFunc::Min),
+            "max" => Some(AggFunc::Max),
+            _ => None,
+        }
+    }
+}
+
+/// An aggregate call such as `COUNT(*)` or `SUM(DISTINCT price)`. Its result is
+/// looked up by `name`, the call as it was written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregate {
+    pub func: AggFunc,
+    /// `None` for `COUNT(*)`
+    pub column: Option<String>,
+    pub distinct: bool,
+    pub name: String,
+}
+
+impl Aggregate {
+    pub fn from_function(function: &Function) -> Result<Option<Aggregate>, Errors> {
+        let func = match AggFunc::new(&function.name.to_string()) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        if function.over.is_some() || function.filter.is_some() || function.args.len() != 1 {
+            return Err(Errors::UnimplementedOperation);
+        }
+        let column = match &function.args[0] {
+            FunctionArg::Unnamed(FunctionArgExpr::Wildcard) if func == AggFunc::Count => None,
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(
+                expr @ (Expr::Identifier(_) | Expr::CompoundIdentifier(_)),
+            )) => Some(expr.to_string()),
+            _ => return Err(Errors::UnimplementedOperation),
+        };
+        Ok(Some(Aggregate {
+            func,
+            column,
+            distinct: function.distinct,
+            name: function.to_string(),
+        }))
+    }
+
+    /// Adds the aggregate calls found in `expr` to `aggregates`, once per name.
+    pub fn collect(expr: &Expr, aggregates: &mut Vec<Aggregate>) -> Result<(), Errors> {
+        match expr {
+            Expr::Function(function) => match Aggregate::from_function(function)? {
+                Some(aggregate) => {
+                    if !aggregates.iter().any(|a| a.name == aggregate.name) {
+                        aggregates.push(aggregate);
+                    }
+                }
+                None => {
+                    for arg in &function.args {
+                        if let FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) = arg {
+                            Aggregate::collect(expr, aggregates)?;
+                        }
+                    }
+                }
+            },
+            Expr::BinaryOp { left, right, .. } => {
+                Aggregate::collect(left, aggregates)?;
+                Aggregate::collect(right, aggregates)?;
+            }
+            Expr::Like { expr, pattern, .. } | Expr::ILike { expr, pattern, .. } => {
+                Aggregate::collect(expr, aggregates)?;
+                Aggregate::collect(pattern, aggregates)?;
+            }
+            Expr::InList { expr, list, .. } => {
+                Aggregate::collect(expr, aggregates)?;
+                for item in list {
+                    Aggregate::collect(item, aggregates)?;
+                }
+            }
+            Expr::Between {
+                expr, low, high, ..
+            } => {
+                Aggregate::collect(expr, aggregates)?;
+                Aggregate::collect(low, aggregates)?;
+                Aggregate::collect(high, aggregates)?;
+            }
+            Expr::Substring {
+                expr,
+                substring_from,
+                substring_for,
+                ..
+            } => {
+                Aggregate::collect(expr, aggregates)?;
+                for arg in [substring_from, substring_for].into_iter().flatten() {
+                    Aggregate::collect(arg, aggregates)?;
+                }
+            }
+            Expr::Trim {
+                expr, trim_what, ..
+            } => {
+                Aggregate::collect(expr, aggregates)?;
+                if let Some(what) = trim_what {
+                    Aggregate::collect(what, aggregates)?;
+                }
+            }
+            Expr::Nested(expr)
+            | Expr::Cast { expr, .. }
+            | Expr::Ceil { expr, .. }
+            | Expr::Floor { expr, .. }
+            | Expr::UnaryOp { expr, .. }
+            | Expr::IsNull(expr)
+            | Expr::IsNotNull(expr)
+            | Expr::InSubquery { expr, .. } => Aggregate::collect(expr, aggregates)?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,6 @@
 use crate::database::base::{ColumnAttr, DataType, ForeignKeyAttr};
+#[cfg(test)]
+use crate::parser::utils::parse_sql;
 use crate::system::errors::Errors;
 use sqlparser::ast::{ColumnOption, DataType as ParserDataType, Statement, TableConstraint};
 
@@ -10,6 +12,7 @@ pub struct CreateQuery {
 }
 
 impl CreateQuery {
+    #[allow(clippy::match_like_matches_macro)]
     pub fn format_stat(statement: Statement) -> Result<CreateQuery, Errors> {
         if let Statement::CreateTable {
             name,
@@ -97,6 +100,7 @@ impl CreateQuery {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_create_query_parsing() {
     let sql = "CREATE TABLE employees (
         id INT PRIMARY KEY,
@@ -1,37 +1,35 @@
-use crate::parser::condition::Condition;
+use crate::parser::expr::Expression;
 use crate::parser::join::FromType;
+#[cfg(test)]
+use crate::parser::utils::parse_sql;
 use crate::system::errors::Errors;
 use sqlparser::ast::Statement;
 
 #[derive(Debug)]
 pub struct DeleteQuery {
     pub tb_name: String,
-    pub condition: Option<Condition>,
+    pub condition: Option<Expression>,
 }
 
 impl DeleteQuery {
     pub fn format_stat(state: Statement) -> Result<DeleteQuery, Errors> {
         let mut tb_name: String = "".to_string();
-        let mut condition_data: Option<Condition> = None;
+        let mut condition_data: Option<Expression> = None;
         if let Statement::Delete {
             from, selection, ..
         } = state
         {
-            let from = match FromType::new(from) {
+            let from = match FromType::new(from, &mut vec![]) {
                 Ok(v) => v.first().unwrap().to_owned(),
                 Err(err) => {
                     return Err(err);
                 }
             };
-            if let FromType::String { tb } = from {
+            if let FromType::String { tb, .. } = from {
                 tb_name = tb.to_string();
             }
-            let condition = Condition::from_expr(&selection.unwrap());
-            match condition {
-                Ok(v) => condition_data = Option::from(v),
-                Err(e) => {
-                    return Err(e);
-                }
+            if let Some(expr) = selection {
+                condition_data = Some(Expression::from_expr(&expr)?);
             }
         }
         Ok(DeleteQuery {
@@ -1,3 +1,5 @@
+#[cfg(test)]
+use crate::parser::utils::parse_sql;
 use crate::system::errors::Errors;
 use sqlparser::ast::Statement;
 
@@ -6,6 +8,7 @@ pub struct DropQuery {
 }
 
 impl DropQuery {
+    #[allow(clippy::needless_return)]
     pub fn format_stat(state: Statement) -> Result<DropQuery, Errors> {
         if let Statement::Drop { names, .. } = state {
             let drop_tbs = names.iter().map(|x| x.to_string()).collect::<Vec<String>>();
@@ -0,0 +1,510 @@
+use crate::database::base::{DataType, Value};
+use crate::database::function::lookup;
+use crate::parser::aggregate::AggFunc;
+use crate::parser::select::SelectQuery;
+use crate::system::errors::Errors;
+use sqlparser::ast::{
+    BinaryOperator, DataType as SqlDataType, DateTimeField, Expr, FunctionArg, FunctionArgExpr,
+    TrimWhereField, UnaryOperator, Value as SqlValue,
+};
+use std::fmt;
+use std::fmt::Formatter;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Not,
+    Minus,
+    Plus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+    Concat,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Eq
+                | BinaryOp::NotEq
+                | BinaryOp::Lt
+                | BinaryOp::LtEq
+                | BinaryOp::Gt
+                | BinaryOp::GtEq
+        )
+    }
+
+    /// The comparison with its sides swapped, `1 < id` is `id > 1`.
+    pub fn flip(&self) -> BinaryOp {
+        match self {
+            BinaryOp::Lt => BinaryOp::Gt,
+            BinaryOp::LtEq => BinaryOp::GtEq,
+            BinaryOp::Gt => BinaryOp::Lt,
+            BinaryOp::GtEq => BinaryOp::LtEq,
+            op => *op,
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BinaryOp::Plus => "+",
+            BinaryOp::Minus => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Modulo => "%",
+            BinaryOp::Concat => "||",
+            BinaryOp::Eq => "=",
+            BinaryOp::NotEq => "<>",
+            BinaryOp::Lt => "<",
+            BinaryOp::LtEq => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::GtEq => ">=",
+            BinaryOp::And => "AND",
+            BinaryOp::Or => "OR",
+        })
+    }
+}
+
+/// A scalar expression of `WHERE`, `HAVING` and the select list, evaluated against
+/// one row by `Expression::eval`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Column(String),
+    Literal(Value),
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expression>,
+    },
+    Binary {
+        left: Box<Expression>,
+        op: BinaryOp,
+        right: Box<Expression>,
+    },
+    IsNull {
+        expr: Box<Expression>,
+        negated: bool,
+    },
+    Like {
+        expr: Box<Expression>,
+        pattern: Box<Expression>,
+        negated: bool,
+        case_insensitive: bool,
+        escape: Option<char>,
+    },
+    InList {
+        expr: Box<Expression>,
+        list: Vec<Expression>,
+        negated: bool,
+    },
+    Between {
+        expr: Box<Expression>,
+        low: Box<Expression>,
+        high: Box<Expression>,
+        negated: bool,
+    },
+    /// A call of a built-in scalar function, by its registered name.
+    Function {
+        name: String,
+        args: Vec<Expression>,
+    },
+    Cast {
+        expr: Box<Expression>,
+        datatype: DataType,
+    },
+    /// A subquery giving one value, NULL when it has no rows.
+    Subquery(Box<SelectQuery>),
+    Exists {
+        query: Box<SelectQuery>,
+        negated: bool,
+    },
+    InSubquery {
+        expr: Box<Expression>,
+        query: Box<SelectQuery>,
+        negated: bool,
+    },
+}
+
+impl Expression {
+    pub fn from_expr(expr: &Expr) -> Result<Expression, Errors> {
+        let boxed = |expr: &Expr| Expression::from_expr(expr).map(Box::new);
+        let case_insensitive = matches!(expr, Expr::ILike { .. });
+        match expr {
+            Expr::Identifier(ident) => match ident.quote_style {
+                // double quotes hold strings, like in MySQL
+                Some('"') => Ok(Expression::Literal(Value::Str(ident.value.to_string()))),
+                _ => Ok(Expression::Column(ident.value.to_string())),
+            },
+            Expr::CompoundIdentifier(idents) => Ok(Expression::Column(
+                idents
+                    .iter()
+                    .map(|ident| ident.value.as_str())
+                    .collect::<Vec<&str>>()
+                    .join("."),
+            )),
+            Expr::Value(value) => Ok(Expression::Literal(literal(value)?)),
+            Expr::Nested(expr) => Expression::from_expr(expr),
+            Expr::UnaryOp { op, expr } => {
+                let op = match op {
+                    UnaryOperator::Not => UnaryOp::Not,
+                    UnaryOperator::Minus => UnaryOp::Minus,
+                    UnaryOperator::Plus => UnaryOp::Plus,
+                    _ => return Err(Errors::UnimplementedOperation),
+                };
+                Ok(Expression::Unary {
+                    op,
+                    expr: boxed(expr)?,
+                })
+            }
+            Expr::BinaryOp { left, op, right } => {
+                let op = match op {
+                    BinaryOperator::Plus => BinaryOp::Plus,
+                    BinaryOperator::Minus => BinaryOp::Minus,
+                    BinaryOperator::Multiply => BinaryOp::Multiply,
+                    BinaryOperator::Divide => BinaryOp::Divide,
+                    BinaryOperator::Modulo => BinaryOp::Modulo,
+                    BinaryOperator::StringConcat => BinaryOp::Concat,
+                    BinaryOperator::Eq => BinaryOp::Eq,
+                    BinaryOperator::NotEq => BinaryOp::NotEq,
+                    BinaryOperator::Lt => BinaryOp::Lt,
+                    BinaryOperator::LtEq => BinaryOp::LtEq,
+                    BinaryOperator::Gt => BinaryOp::Gt,
+                    BinaryOperator::GtEq => BinaryOp::GtEq,
+                    BinaryOperator::And => BinaryOp::And,
+                    BinaryOperator::Or => BinaryOp::Or,
+                    _ => return Err(Errors::UnimplementedOperation),
+                };
+                Ok(Expression::Binary {
+                    left: boxed(left)?,
+                    op,
+                    right: boxed(right)?,
+                })
+            }
+            Expr::IsNull(expr) => Ok(Expression::IsNull {
+                expr: boxed(expr)?,
+                negated: false,
+            }),
+            Expr::IsNotNull(expr) => Ok(Expression::IsNull {
+                expr: boxed(expr)?,
+                negated: true,
+            }),
+            Expr::Like {
+                negated,
+                expr,
+                pattern,
+                escape_char,
+            }
+            | Expr::ILike {
+                negated,
+                expr,
+                pattern,
+                escape_char,
+            } => Ok(Expression::Like {
+                expr: boxed(expr)?,
+                pattern: boxed(pattern)?,
+                negated: *negated,
+                case_insensitive,
+                escape: *escape_char,
+            }),
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => Ok(Expression::InList {
+                expr: boxed(expr)?,
+                list: list
+                    .iter()
+                    .map(Expression::from_expr)
+                    .collect::<Result<Vec<Expression>, Errors>>()?,
+                negated: *negated,
+            }),
+            Expr::Between {
+                expr,
+                negated,
+                low,
+                high,
+            } => Ok(Expression::Between {
+                expr: boxed(expr)?,
+                low: boxed(low)?,
+                high: boxed(high)?,
+                negated: *negated,
+            }),
+            // aggregates are columns of the grouped rows
+            Expr::Function(function) if AggFunc::new(&function.name.to_string()).is_some() => {
+                Ok(Expression::Column(function.to_string()))
+            }
+            Expr::Function(function) => {
+                if function.distinct
+                    || function.over.is_some()
+                    || function.filter.is_some()
+                    || !function.order_by.is_empty()
+                {
+                    return Err(Errors::UnimplementedOperation);
+                }
+                let mut args = vec![];
+                for arg in &function.args {
+                    match arg {
+                        FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => {
+                            args.push(Expression::from_expr(expr)?)
+                        }
+                        _ => return Err(Errors::UnimplementedOperation),
+                    }
+                }
+                Expression::function(&function.name.to_string(), args)
+            }
+            // the special syntax of some functions
+            Expr::Ceil {
+                expr,
+                field: DateTimeField::NoDateTime,
+            } => Expression::function("CEIL", vec![Expression::from_expr(expr)?]),
+            Expr::Floor {
+                expr,
+                field: DateTimeField::NoDateTime,
+            } => Expression::function("FLOOR", vec![Expression::from_expr(expr)?]),
+            Expr::Substring {
+                expr,
+                substring_from,
+                substring_for,
+                ..
+            } => {
+                let mut args = vec![Expression::from_expr(expr)?];
+                args.push(match substring_from {
+                    Some(from) => Expression::from_expr(from)?,
+                    None => Expression::Literal(Value::Int(1)),
+                });
+                if let Some(len) = substring_for {
+                    args.push(Expression::from_expr(len)?);
+                }
+                Expression::function("SUBSTR", args)
+            }
+            Expr::Trim {
+                expr,
+                trim_where,
+                trim_what,
+                trim_characters: None,
+            } => {
+                let name = match trim_where {
+                    Some(TrimWhereField::Leading) => "LTRIM",
+                    Some(TrimWhereField::Trailing) => "RTRIM",
+                    _ => "TRIM",
+                };
+                let mut args = vec![Expression::from_expr(expr)?];
+                if let Some(what) = trim_what {
+                    args.push(Expression::from_expr(what)?);
+                }
+                Expression::function(name, args)
+            }
+            Expr::Cast {
+                expr,
+                data_type,
+                format: None,
+            } => Ok(Expression::Cast {
+                expr: boxed(expr)?,
+                datatype: cast_type(data_type)?,
+            }),
+            Expr::Subquery(query) => Ok(Expression::Subquery(Box::new(SelectQuery::new(query)?))),
+            Expr::Exists { subquery, negated } => Ok(Expression::Exists {
+                query: Box::new(SelectQuery::new(subquery)?),
+                negated: *negated,
+            }),
+            Expr::InSubquery {
+                expr,
+                subquery,
+                negated,
+            } => Ok(Expression::InSubquery {
+                expr: boxed(expr)?,
+                query: Box::new(SelectQuery::new(subquery)?),
+                negated: *negated,
+            }),
+            _ => Err(Errors::UnimplementedOperation),
+        }
+    }
+
+    // a call of the registered function `name`
+    fn function(name: &str, args: Vec<Expression>) -> Result<Expression, Errors> {
+        match lookup(name) {
+            Some(function) if function.accepts(args.len()) => Ok(Expression::Function {
+                name: function.name.to_string(),
+                args,
+            }),
+            Some(function) => Err(Errors::FunctionNotExisted(format!(
+                "{} with {} arguments",
+                function.name,
+                args.len()
+            ))),
+            None => Err(Errors::FunctionNotExisted(name.to_string())),
+        }
+    }
+
+    /// The expressions this one is made of, a subquery keeps its own.
+    pub fn children_mut(&mut self) -> Vec<&mut Expression> {
+        match self {
+            Expression::Column(_)
+            | Expression::Literal(_)
+            | Expression::Subquery(_)
+            | Expression::Exists { .. } => vec![],
+            Expression::Unary { expr, .. }
+            | Expression::IsNull { expr, .. }
+            | Expression::Cast { expr, .. }
+            | Expression::InSubquery { expr, .. } => vec![expr.as_mut()],
+            Expression::Binary { left, right, .. } => vec![left.as_mut(), right.as_mut()],
+            Expression::Like { expr, pattern, .. } => vec![expr.as_mut(), pattern.as_mut()],
+            Expression::InList { expr, list, .. } => {
+                std::iter::once(expr.as_mut()).chain(list).collect()
+            }
+            Expression::Function { args, .. } => args.iter_mut().collect(),
+            Expression::Between {
+                expr, low, high, ..
+            } => vec![expr.as_mut(), low.as_mut(), high.as_mut()],
+        }
+    }
+
+    /// Adds the names of the columns the expression reads to `columns`, subqueries
+    /// read their own.
+    pub fn columns<'a>(&'a self, columns: &mut Vec<&'a str>) {
+        match self {
+            Expression::Column(name) => columns.push(name),
+            Expression::Literal(_) | Expression::Subquery(_) | Expression::Exists { .. } => {}
+            Expression::Unary { expr, .. }
+            | Expression::IsNull { expr, .. }
+            | Expression::Cast { expr, .. }
+            | Expression::InSubquery { expr, .. } => expr.columns(columns),
+            Expression::Binary { left, right, .. } => {
+                left.columns(columns);
+                right.columns(columns);
+            }
+            Expression::Like { expr, pattern, .. } => {
+                expr.columns(columns);
+                pattern.columns(columns);
+            }
+            Expression::InList { expr, list, .. } => {
+                expr.columns(columns);
+                list.iter().for_each(|item| item.columns(columns));
+            }
+            Expression::Function { args, .. } => args.iter().for_each(|arg| arg.columns(columns)),
+            Expression::Between {
+                expr, low, high, ..
+            } => {
+                expr.columns(columns);
+                low.columns(columns);
+                high.columns(columns);
+            }
+        }
+    }
+}
+
+// the column type a `CAST` converts to
+fn cast_type(data_type: &SqlDataType) -> Result<DataType, Errors> {
+    match data_type {
+        SqlDataType::Int(_)
+        | SqlDataType::Integer(_)
+        | SqlDataType::SmallInt(_)
+        | SqlDataType::BigInt(_) => Ok(DataType::Int),
+        SqlDataType::Float(_)
+        | SqlDataType::Real
+        | SqlDataType::Double
+        | SqlDataType::DoublePrecision
+        | SqlDataType::Decimal(_)
+        | SqlDataType::Numeric(_) => Ok(DataType::Float),
+        SqlDataType::Boolean => Ok(DataType::Bool),
+        SqlDataType::Text
+        | SqlDataType::String(_)
+        | SqlDataType::Char(_)
+        | SqlDataType::Varchar(_) => Ok(DataType::String),
+        _ => Err(Errors::UnimplementedOperation),
+    }
+}
+
+fn literal(value: &SqlValue) -> Result<Value, Errors> {
+    match value {
+        SqlValue::Number(n, _) => match n.parse::<i64>() {
+            Ok(v) => Ok(Value::Int(v)),
+            Err(_) => n
+                .parse::<f64>()
+                .map(Value::Float)
+                .map_err(|_| Errors::InvalidExpression),
+        },
+        SqlValue::SingleQuotedString(s) | SqlValue::DoubleQuotedString(s) => {
+            Ok(Value::Str(s.to_string()))
+        }
+        SqlValue::Boolean(b) => Ok(Value::Bool(*b)),
+        SqlValue::Null => Ok(Value::Null),
+        _ => Err(Errors::UnimplementedOperation),
+    }
+}
+
+#[test]
+fn test_expression() {
+    use crate::parser::utils::parse_sql;
+    use sqlparser::ast::{SetExpr, Statement};
+
+    let stat = parse_sql("SELECT * FROM t WHERE (a + 2) * 3 >= b AND name NOT LIKE \"x%\" OR id IN (1, 2.5) AND c IS NOT NULL").unwrap();
+    let selection = match stat {
+        Statement::Query(query) => match *query.body {
+            SetExpr::Select(select) => select.selection.unwrap(),
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    };
+    let column = |name: &str| Box::new(Expression::Column(name.to_string()));
+    let literal = |value: Value| Box::new(Expression::Literal(value));
+    assert_eq!(
+        Expression::from_expr(&selection).unwrap(),
+        Expression::Binary {
+            left: Box::new(Expression::Binary {
+                left: Box::new(Expression::Binary {
+                    left: Box::new(Expression::Binary {
+                        left: Box::new(Expression::Binary {
+                            left: column("a"),
+                            op: BinaryOp::Plus,
+                            right: literal(Value::Int(2)),
+                        }),
+                        op: BinaryOp::Multiply,
+                        right: literal(Value::Int(3)),
+                    }),
+                    op: BinaryOp::GtEq,
+                    right: column("b"),
+                }),
+                op: BinaryOp::And,
+                right: Box::new(Expression::Like {
+                    expr: column("name"),
+                    pattern: literal(Value::Str("x%".to_string())),
+                    negated: true,
+                    case_insensitive: false,
+                    escape: None,
+                }),
+            }),
+            op: BinaryOp::Or,
+            right: Box::new(Expression::Binary {
+                left: Box::new(Expression::InList {
+                    expr: column("id"),
+                    list: vec![
+                        Expression::Literal(Value::Int(1)),
+                        Expression::Literal(Value::Float(2.5))
+                    ],
+                    negated: false,
+                }),
+                op: BinaryOp::And,
+                right: Box::new(Expression::IsNull {
+                    expr: column("c"),
+                    negated: true,
+                }),
+            }),
+        }
+    );
+}
@@ -0,0 +1,78 @@
+#[cfg(test)]
+use crate::parser::utils::parse_sql;
+use crate::system::errors::Errors;
+use sqlparser::ast::{ObjectType, Statement};
+
+#[derive(Debug)]
+pub struct CreateIndexQuery {
+    pub idx_name: String,
+    pub tb_name: String,
+    pub column: String,
+}
+
+impl CreateIndexQuery {
+    pub fn format_stat(state: Statement) -> Result<CreateIndexQuery, Errors> {
+        if let Statement::CreateIndex {
+            name,
+            table_name,
+            columns,
+            ..
+        } = state
+        {
+            // indexes cover a single column
+            if columns.len() != 1 {
+                return Err(Errors::UnimplementedOperation);
+            }
+            let tb_name = table_name.to_string();
+            let column = columns.first().unwrap().expr.to_string();
+            let idx_name = match name {
+                Some(v) => v.to_string(),
+                None => format!("{}_{}_idx", tb_name, column),
+            };
+            Ok(CreateIndexQuery {
+                idx_name,
+                tb_name,
+                column,
+            })
+        } else {
+            Err(Errors::InvalidExpression)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DropIndexQuery {
+    pub idx_names: Vec<String>,
+}
+
+impl DropIndexQuery {
+    pub fn format_stat(state: Statement) -> Result<DropIndexQuery, Errors> {
+        if let Statement::Drop {
+            object_type: ObjectType::Index,
+            names,
+            ..
+        } = state
+        {
+            let idx_names = names.iter().map(|x| x.to_string()).collect::<Vec<String>>();
+            Ok(DropIndexQuery { idx_names })
+        } else {
+            Err(Errors::InvalidExpression)
+        }
+    }
+}
+
+#[test]
+fn test_index_query() {
+    let state = parse_sql("CREATE INDEX users_age ON users (age)").unwrap();
+    let query = CreateIndexQuery::format_stat(state).unwrap();
+    assert_eq!(query.idx_name, "users_age");
+    assert_eq!(query.tb_name, "users");
+    assert_eq!(query.column, "age");
+
+    let state = parse_sql("CREATE INDEX ON users (age, name)").unwrap();
+    assert!(CreateIndexQuery::format_stat(state).is_err());
+
+    let state = parse_sql("DROP INDEX users_age").unwrap();
+    let query = DropIndexQuery::format_stat(state).unwrap();
+    assert_eq!(query.idx_names, vec!["users_age".to_string()]);
+}
@@ -1,5 +1,9 @@
+#[cfg(test)]
+use crate::parser::utils::parse_sql;
 use crate::system::errors::Errors;
 use sqlparser::ast::{Expr, Query, SetExpr, Statement, Value, Values};
+#[cfg(test)]
+use sqlparser::dialect::AnsiDialect;
 
 #[derive(Debug)]
 pub struct InsertQuery {
@@ -9,6 +13,7 @@ pub struct InsertQuery {
 }
 
 impl InsertQuery {
+    #[allow(clippy::match_single_binding, clippy::needless_return)]
     pub fn format_stat(statement: Statement) -> Result<InsertQuery, Errors> {
         let tb_name: Option<String>;
         let mut cols_data: Vec<String> = vec![];
@@ -1,15 +1,9 @@
+use crate::parser::expr::Expression;
+use crate::parser::select::SelectQuery;
 use crate::system::errors::Errors;
-use sqlparser::ast::{Expr, Join, JoinOperator, TableFactor, TableWithJoins};
+use sqlparser::ast::{JoinConstraint, JoinOperator, TableFactor, TableWithJoins};
 
-#[derive(Debug, Clone)]
-pub struct JoinInfo {
-    pub left_table: String,
-    pub left_column: String,
-    pub right_table: String,
-    pub right_column: String,
-}
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum JoinType {
     Inner,
     Left,
@@ -17,75 +11,103 @@ pub enum JoinType {
     FullOuter,
 }
 
-#[derive(Debug, Clone)]
+/// A table joined to the rows of the tables before it, `on` is `None` for a cross join.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoinItem {
+    pub join_type: JoinType,
+    pub tb: String,
+    pub alias: Option<String>,
+    pub on: Option<Expression>,
+}
+
+/// An item of the `FROM` list. Items separated by commas are cross joined, a
+/// subquery is named by its alias and kept in `derived`.
+#[derive(Debug, Clone, PartialEq)]
 pub enum FromType {
     Join {
-        join_type: JoinType,
-        join_info: JoinInfo,
+        tb: String,
+        alias: Option<String>,
+        joins: Vec<JoinItem>,
     },
     String {
         tb: String,
+        alias: Option<String>,
     },
 }
 
 impl FromType {
-    pub fn new(joins: Vec<TableWithJoins>) -> Result<Vec<FromType>, Errors> {
+    pub fn new(
+        joins: Vec<TableWithJoins>,
+        derived: &mut Vec<(String, SelectQuery)>,
+    ) -> Result<Vec<FromType>, Errors> {
         let mut join_data_vec: Vec<FromType> = Vec::new();
         for join in joins {
-            let relation = join.clone().relation;
-            let joins = join.clone().joins;
-            let main_table_name = match &relation {
-                TableFactor::Table { name, .. } => name.to_string(),
-                _ => "".to_string(),
-            };
-            if joins.len() == 0 {
-                if let TableFactor::Table { name, .. } = relation {
-                    join_data_vec.push(FromType::String {
-                        tb: name.to_string(),
-                    })
-                }
+            let (tb, alias) = table_name(&join.relation, derived)?;
+            if join.joins.is_empty() {
+                join_data_vec.push(FromType::String { tb, alias });
+                continue;
             }
-            for j in joins {
-                if let Join {
-                    relation: TableFactor::Table { name, .. },
-                    join_operator,
-                } = j
-                {
-                    let (join_type, join_constraint) = match join_operator {
-                        JoinOperator::Inner(constraint) => (JoinType::Inner, constraint),
-                        JoinOperator::LeftOuter(constraint) => (JoinType::Left, constraint),
-                        JoinOperator::RightOuter(constraint) => (JoinType::Right, constraint),
-                        JoinOperator::FullOuter(constraint) => (JoinType::FullOuter, constraint),
-                        _ => return Err(Errors::UnimplementedOperation),
-                    };
-
-                    if let sqlparser::ast::JoinConstraint::On(expr) = join_constraint {
-                        if let Expr::BinaryOp { left, op: _, right } = expr {
-                            if let (
-                                Expr::CompoundIdentifier(left_cols),
-                                Expr::CompoundIdentifier(right_cols),
-                            ) = (*left, *right)
-                            {
-                                if left_cols.len() == 2 && right_cols.len() == 2 {
-                                    let join_info = JoinInfo {
-                                        left_table: main_table_name.clone(),
-                                        left_column: left_cols[1].to_string(),
-                                        right_table: name.to_string(),
-                                        right_column: right_cols[1].to_string(),
-                                    };
-
-                                    let join_data = FromType::Join {
-                                        join_type,
-                                        join_info,
-                                    };
-                                    join_data_vec.push(join_data);
-                                }
-                            }
-                        }
-                    }
-                }
+            let mut items = vec![];
+            for j in join.joins {
+                let (join_type, join_constraint) = match j.join_operator {
+                    JoinOperator::Inner(constraint) => (JoinType::Inner, constraint),
+                    JoinOperator::LeftOuter(constraint) => (JoinType::Left, constraint),
+                    JoinOperator::RightOuter(constraint) => (JoinType::Right, constraint),
+                    JoinOperator::FullOuter(constraint) => (JoinType::FullOuter, constraint),
+                    JoinOperator::CrossJoin => (JoinType::Inner, JoinConstraint::None),
+                    _ => return Err(Errors::UnimplementedOperation),
+                };
+                let on = match join_constraint {
+                    JoinConstraint::On(expr) => Some(Expression::from_expr(&expr)?),
+                    JoinConstraint::None => None,
+                    _ => return Err(Errors::UnimplementedOperation),
+                };
+                let (tb, alias) = table_name(&j.relation, derived)?;
+                items.push(JoinItem {
+                    join_type,
+                    tb,
+                    alias,
+                    on,
+                });
             }
+            join_data_vec.push(FromType::Join {
+                tb,
+                alias,
+                joins: items,
+            });
         }
         Ok(join_data_vec)
     }
 }
+
+// the name and the alias of a table in `FROM`
+fn table_name(
+    relation: &TableFactor,
+    derived: &mut Vec<(String, SelectQuery)>,
+) -> Result<(String, Option<String>), Errors> {
+    match relation {
+        TableFactor::Table {
+            name,
+            alias,
+            args: None,
+            ..
+        } => match alias {
+            Some(alias) if !alias.columns.is_empty() => Err(Errors::UnimplementedOperation),
+            _ => Ok((
+                name.to_string(),
+                alias.as_ref().map(|a| a.name.value.to_string()),
+            )),
+        },
+        TableFactor::Derived {
+            lateral: false,
+            subquery,
+            alias: Some(alias),
+        } if alias.columns.is_empty() => {
+            derived.push((alias.name.value.to_string(), SelectQuery::new(subquery)?));
+            Ok((alias.name.value.to_string(), None))
+        }
+        // a subquery needs a name for its columns
+        TableFactor::Derived { alias: None, .. } => Err(Errors::InvalidExpression),
+        _ => Err(Errors::UnimplementedOperation),
+    }
+}
@@ -1,9 +1,12 @@
+pub(crate) mod aggregate;
 pub(crate) mod create;
+pub(crate) mod delete;
 pub(crate) mod drop;
+pub(crate) mod expr;
+pub(crate) mod index;
 pub(crate) mod insert;
-pub(crate) mod select;
 pub(crate) mod join;
+pub(crate) mod select;
+pub(crate) mod transaction;
 pub(crate) mod update;
 pub(crate) mod utils;
-pub(crate) mod condition;
-pub(crate) mod delete;
@@ -1,77 +1,455 @@
-use crate::parser::condition::Condition;
+use crate::parser::aggregate::Aggregate;
+use crate::parser::expr::Expression;
 use crate::parser::join::FromType;
+#[cfg(test)]
+use crate::parser::join::JoinType;
+#[cfg(test)]
+use crate::parser::utils::parse_sql;
 use crate::system::errors::Errors;
-use sqlparser::ast::{SetExpr, Statement};
+use sqlparser::ast::{
+    Distinct as SqlDistinct, Expr, GroupByExpr, OrderByExpr, Query, SelectItem, SetExpr,
+    SetOperator, SetQuantifier, Statement, Value,
+};
+use std::fmt;
+use std::fmt::Formatter;
 use std::option::Option;
 
-#[derive(Debug, PartialEq)]
-pub enum BinaryOpCus {
-    Lt,
-    Gt,
-    Eq,
-    And,
-    Or,
-    IsNull,
-    Like,
+/// What an `ORDER BY` item sorts on, a column or a 1-based projection position.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderKey {
+    Column(String),
+    Position(usize),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderBy {
+    pub key: OrderKey,
+    pub asc: bool,
+    pub nulls_first: bool,
+}
+
+impl OrderBy {
+    pub fn new(order_by: &OrderByExpr) -> Result<OrderBy, Errors> {
+        let key = match &order_by.expr {
+            // aggregates are columns of the grouped rows
+            Expr::Identifier(_) | Expr::CompoundIdentifier(_) | Expr::Function(_) => {
+                OrderKey::Column(order_by.expr.to_string())
+            }
+            Expr::Value(Value::Number(n, _)) => match n.parse::<usize>() {
+                Ok(pos) if pos > 0 => OrderKey::Position(pos),
+                _ => return Err(Errors::InvalidOrderPosition(n.to_string())),
+            },
+            _ => return Err(Errors::UnimplementedOperation),
+        };
+        let asc = order_by.asc.unwrap_or(true);
+        // NULLs are larger than any value, like in PostgreSQL
+        let nulls_first = order_by.nulls_first.unwrap_or(!asc);
+        Ok(OrderBy {
+            key,
+            asc,
+            nulls_first,
+        })
+    }
+}
+
+/// An item of the select list.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Projection {
+    /// `*`, or `table.*` with the table name
+    Wildcard(Option<String>),
+    /// An expression named by its alias or else by how it was written.
+    Expr { expr: Expression, name: String },
+}
+
+impl Projection {
+    pub fn new(item: &SelectItem) -> Result<Projection, Errors> {
+        match item {
+            SelectItem::UnnamedExpr(expr) => Ok(Projection::Expr {
+                expr: Expression::from_expr(expr)?,
+                name: expr.to_string(),
+            }),
+            SelectItem::ExprWithAlias { expr, alias } => Ok(Projection::Expr {
+                expr: Expression::from_expr(expr)?,
+                name: alias.value.to_string(),
+            }),
+            SelectItem::Wildcard(_) => Ok(Projection::Wildcard(None)),
+            SelectItem::QualifiedWildcard(name, _) => {
+                Ok(Projection::Wildcard(Some(name.to_string())))
+            }
+        }
+    }
+}
+
+/// Which rows `SELECT DISTINCT` keeps.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Distinct {
+    /// `DISTINCT`, the first of the rows with equal values in every column
+    Rows,
+    /// `DISTINCT ON (expressions)`, the first of the rows with equal values of the
+    /// expressions in the order of `ORDER BY`
+    On(Vec<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetOp {
+    Union,
+    Intersect,
+    Except,
+}
+
+impl fmt::Display for SetOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SetOp::Union => "UNION",
+            SetOp::Intersect => "INTERSECT",
+            SetOp::Except => "EXCEPT",
+        })
+    }
+}
+
+/// The rows of two queries combined by `UNION`, `INTERSECT` or `EXCEPT`, without
+/// the duplicates unless it is `ALL`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetOperation {
+    pub op: SetOp,
+    pub all: bool,
+    pub left: SelectQuery,
+    pub right: SelectQuery,
+}
+
+/// A query named by `WITH`, the names of `columns` replace the names of its columns.
+/// A recursive one is a `UNION` whose right query reads the rows the previous
+/// round gave.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommonTable {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub query: SelectQuery,
+    pub recursive: bool,
+}
+
+/// A `SELECT`, or a set operation when `set_operation` is given. `ORDER BY` and
+/// `LIMIT` of a set operation apply to the rows it combines.
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct SelectQuery {
+    pub distinct: Option<Distinct>,
     pub from: Vec<FromType>,
-    pub projection: Vec<String>,
-    pub condition: Option<Condition>,
+    pub projection: Vec<Projection>,
+    pub condition: Option<Expression>,
+    pub order_by: Vec<OrderBy>,
+    pub limit: Option<usize>,
+    pub offset: usize,
+    pub group_by: Vec<String>,
+    pub having: Option<Expression>,
+    /// Aggregate calls of the projection, `HAVING` and `ORDER BY`.
+    pub aggregates: Vec<Aggregate>,
+    /// Tables of `FROM` given by a subquery, by their alias.
+    pub derived: Vec<(String, SelectQuery)>,
+    pub set_operation: Option<Box<SetOperation>>,
+    /// Queries of `WITH`, tables of this query and of its subqueries.
+    pub with: Vec<CommonTable>,
 }
 
 impl SelectQuery {
     pub fn format_stat(statement: Statement) -> Result<SelectQuery, Errors> {
-        let mut select_from: Vec<FromType> = vec![];
-        let mut select_projections: Vec<String> = vec![];
-        let mut select_condition: Option<Condition> = None;
         match statement {
-            Statement::Query(bd) => match &*bd.body {
-                SetExpr::Select(select) => {
-                    let projects = &select.projection;
-                    let froms = &select.from;
-                    let exprs = &select.selection;
-                    if !exprs.is_none() {
-                        let condition = Condition::from_expr(&exprs.clone().unwrap());
-                        match condition {
-                            Ok(v) => select_condition = Some(v),
-                            Err(e) => {
-                                return Err(e);
-                            }
+            Statement::Query(query) => SelectQuery::new(&query),
+            _ => Err(Errors::InvalidExpression),
+        }
+    }
+
+    /// The query of a statement or of a subquery.
+    pub fn new(bd: &Query) -> Result<SelectQuery, Errors> {
+        let mut query = SelectQuery::with_order(bd)?;
+        if let Some(with) = &bd.with {
+            let mut ctes = vec![];
+            for cte in &with.cte_tables {
+                if cte.from.is_some() {
+                    return Err(Errors::UnimplementedOperation);
+                }
+                ctes.push(CommonTable {
+                    name: cte.alias.name.value.to_string(),
+                    columns: cte
+                        .alias
+                        .columns
+                        .iter()
+                        .map(|c| c.value.to_string())
+                        .collect(),
+                    query: SelectQuery::new(&cte.query)?,
+                    recursive: with.recursive,
+                });
+            }
+            // the queries of an inner `WITH` come after and hide the ones named alike
+            query.with.splice(0..0, ctes);
+        }
+        Ok(query)
+    }
+
+    /// Whether the query reads table `name` in `FROM`, in its own or in the one of
+    /// a subquery of `FROM` or of a set operation.
+    pub fn reads_table(&self, name: &str) -> bool {
+        let named = self.from.iter().any(|from| match from {
+            FromType::String { tb, .. } => tb == name,
+            FromType::Join { tb, joins, .. } => tb == name || joins.iter().any(|j| j.tb == name),
+        });
+        let derived = self
+            .derived
+            .iter()
+            .any(|(_, query)| query.reads_table(name));
+        let set = self
+            .set_operation
+            .as_ref()
+            .is_some_and(|set| set.left.reads_table(name) || set.right.reads_table(name));
+        // a derived table of the same name hides the one read
+        (named && !self.derived.iter().any(|(alias, _)| alias == name)) || derived || set
+    }
+
+    // the query without its `WITH`
+    fn with_order(bd: &Query) -> Result<SelectQuery, Errors> {
+        let mut query = SelectQuery::from_body(&bd.body)?;
+        if bd.order_by.is_empty() && bd.limit.is_none() && bd.fetch.is_none() && bd.offset.is_none()
+        {
+            return Ok(query);
+        }
+        // `(SELECT ... LIMIT n) ORDER BY ...` sorts the rows the inner query gives
+        if !query.order_by.is_empty() || query.limit.is_some() || query.offset > 0 {
+            query = SelectQuery {
+                from: vec![FromType::String {
+                    tb: "subquery".to_string(),
+                    alias: None,
+                }],
+                projection: vec![Projection::Wildcard(None)],
+                derived: vec![("subquery".to_string(), query)],
+                ..Default::default()
+            };
+        }
+        for order_by in &bd.order_by {
+            if query.set_operation.is_none() {
+                Aggregate::collect(&order_by.expr, &mut query.aggregates)?;
+            }
+            query.order_by.push(OrderBy::new(order_by)?);
+        }
+        if let Some(limit) = &bd.limit {
+            query.limit = Some(row_count(limit)?);
+        }
+        if let Some(fetch) = &bd.fetch {
+            if fetch.with_ties || fetch.percent || query.limit.is_some() {
+                return Err(Errors::UnimplementedOperation);
+            }
+            // `FETCH FIRST ROW ONLY` is one row
+            query.limit = Some(fetch.quantity.as_ref().map_or(Ok(1), row_count)?);
+        }
+        if let Some(offset) = &bd.offset {
+            query.offset = row_count(&offset.value)?;
+        }
+        Ok(query)
+    }
+
+    // a `SELECT`, a set operation or a query in parentheses
+    fn from_body(body: &SetExpr) -> Result<SelectQuery, Errors> {
+        let select = match body {
+            SetExpr::Select(select) => select,
+            SetExpr::SetOperation {
+                op,
+                set_quantifier,
+                left,
+                right,
+            } => {
+                let all = match set_quantifier {
+                    SetQuantifier::All => true,
+                    SetQuantifier::Distinct | SetQuantifier::None => false,
+                    _ => return Err(Errors::UnimplementedOperation),
+                };
+                let set_operation = SetOperation {
+                    op: match op {
+                        SetOperator::Union => SetOp::Union,
+                        SetOperator::Intersect => SetOp::Intersect,
+                        SetOperator::Except => SetOp::Except,
+                    },
+                    all,
+                    left: SelectQuery::from_body(left)?,
+                    right: SelectQuery::from_body(right)?,
+                };
+                return Ok(SelectQuery {
+                    set_operation: Some(Box::new(set_operation)),
+                    ..Default::default()
+                });
+            }
+            SetExpr::Query(query) => return SelectQuery::new(query),
+            _ => return Err(Errors::InvalidExpression),
+        };
+        let mut select_projections: Vec<Projection> = vec![];
+        let mut select_condition: Option<Expression> = None;
+        let mut select_group_by: Vec<String> = vec![];
+        let mut select_having: Option<Expression> = None;
+        let mut select_aggregates: Vec<Aggregate> = vec![];
+        let mut select_derived: Vec<(String, SelectQuery)> = vec![];
+        let projects = &select.projection;
+        let froms = &select.from;
+        let exprs = &select.selection;
+        if let Some(expr) = exprs {
+            select_condition = Some(Expression::from_expr(expr)?);
+        }
+        let select_from = FromType::new(froms.to_owned(), &mut select_derived)?;
+        let select_distinct = match &select.distinct {
+            None => None,
+            Some(SqlDistinct::Distinct) => Some(Distinct::Rows),
+            Some(SqlDistinct::On(exprs)) => {
+                let mut on = vec![];
+                for expr in exprs {
+                    Aggregate::collect(expr, &mut select_aggregates)?;
+                    on.push(Expression::from_expr(expr)?);
+                }
+                Some(Distinct::On(on))
+            }
+        };
+        for projection in projects {
+            if let SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } =
+                projection
+            {
+                Aggregate::collect(expr, &mut select_aggregates)?;
+            }
+            select_projections.push(Projection::new(projection)?);
+        }
+        match &select.group_by {
+            GroupByExpr::Expressions(exprs) => {
+                for expr in exprs {
+                    match expr {
+                        Expr::Identifier(_) | Expr::CompoundIdentifier(_) => {
+                            select_group_by.push(expr.to_string())
                         }
-                    }
-                    select_from = match FromType::new(froms.to_owned()) {
-                        Ok(v) => v,
-                        Err(err) => return Err(err),
-                    };
-                    for projection in projects {
-                        let cname = projection.to_string();
-                        select_projections.push(cname);
+                        _ => return Err(Errors::UnimplementedOperation),
                     }
                 }
-                _ => {
-                    return Err(Errors::InvalidExpression);
-                }
-            },
-            _ => {}
+            }
+            GroupByExpr::All => return Err(Errors::UnimplementedOperation),
+        }
+        if let Some(having) = &select.having {
+            Aggregate::collect(having, &mut select_aggregates)?;
+            select_having = Some(Expression::from_expr(having)?);
         }
         Ok(SelectQuery {
+            distinct: select_distinct,
             from: select_from,
             projection: select_projections,
             condition: select_condition,
+            group_by: select_group_by,
+            having: select_having,
+            aggregates: select_aggregates,
+            derived: select_derived,
+            ..Default::default()
         })
     }
 }
 
+// a non-negative number of rows in `LIMIT`, `OFFSET` or `FETCH`
+fn row_count(expr: &Expr) -> Result<usize, Errors> {
+    match expr {
+        Expr::Value(Value::Number(n, _)) => {
+            n.parse::<usize>().map_err(|_| Errors::InvalidExpression)
+        }
+        _ => Err(Errors::InvalidExpression),
+    }
+}
+
 #[test]
 pub fn test_select() {
     let sql = "SELECT articles.id, articles.title, articles.userid, users.username FROM articles JOIN users ON articles.userid = users.id;";
     let stat = parse_sql(sql);
     let _query = SelectQuery::format_stat(stat.unwrap());
 
+    let sql1 = "SELECT * FROM articles a JOIN users u ON a.userid = u.id LEFT JOIN tags ON tags.id = a.tag, logs;";
+    let query1 = SelectQuery::format_stat(parse_sql(sql1).unwrap()).unwrap();
+    match &query1.from[..] {
+        [FromType::Join { tb, alias, joins }, FromType::String {
+            tb: logs,
+            alias: None,
+        }] => {
+            assert_eq!(
+                (tb.as_str(), alias.as_deref(), logs.as_str()),
+                ("articles", Some("a"), "logs")
+            );
+            assert_eq!(joins.len(), 2);
+            assert_eq!(joins[0].alias.as_deref(), Some("u"));
+            assert!(matches!(joins[1].join_type, JoinType::Left) && joins[1].on.is_some());
+        }
+        from => panic!("unexpected from {:?}", from),
+    }
+
+    let sql6 =
+        "SELECT * FROM (SELECT id FROM users) AS u WHERE id IN (SELECT userid FROM articles);";
+    let query6 = SelectQuery::format_stat(parse_sql(sql6).unwrap()).unwrap();
+    assert_eq!(query6.derived[0].0, "u");
+    assert!(matches!(&query6.from[..], [FromType::String { tb, alias: None }] if tb == "u"));
+    assert!(matches!(
+        query6.condition,
+        Some(Expression::InSubquery { negated: false, .. })
+    ));
+
+    let sql7 = "SELECT id FROM users UNION ALL SELECT userid FROM articles EXCEPT SELECT 1 ORDER BY 1 LIMIT 2;";
+    let query7 = SelectQuery::format_stat(parse_sql(sql7).unwrap()).unwrap();
+    let set = query7.set_operation.unwrap();
+    assert_eq!(
+        (set.op, set.all, query7.limit),
+        (SetOp::Except, false, Some(2))
+    );
+    assert!(matches!(
+        set.left.set_operation.as_deref(),
+        Some(SetOperation {
+            op: SetOp::Union,
+            all: true,
+            ..
+        })
+    ));
+
+    let sql8 = "WITH RECURSIVE tree (id) AS (SELECT 1 UNION ALL SELECT users.id FROM users JOIN tree ON users.parent = tree.id) SELECT * FROM tree;";
+    let query8 = SelectQuery::format_stat(parse_sql(sql8).unwrap()).unwrap();
+    let cte = &query8.with[0];
+    assert_eq!(
+        (cte.name.as_str(), &cte.columns[..], cte.recursive),
+        ("tree", &["id".to_string()][..], true)
+    );
+    let set = cte.query.set_operation.as_ref().unwrap();
+    assert!(!set.left.reads_table("tree") && set.right.reads_table("tree"));
+    assert!(query8.reads_table("tree"));
+
     let sql2 = "SELECT id,username from users;";
     let stat2 = parse_sql(sql2);
     let _query2 = SelectQuery::format_stat(stat2.unwrap());
+
+    let sql3 =
+        "SELECT id, name FROM users ORDER BY users.name DESC, 1 NULLS FIRST LIMIT 10 OFFSET 20;";
+    let query3 = SelectQuery::format_stat(parse_sql(sql3).unwrap()).unwrap();
+    assert_eq!((query3.limit, query3.offset), (Some(10), 20));
+    let sql4 = "SELECT id FROM users OFFSET 5 ROWS FETCH FIRST 3 ROWS ONLY;";
+    let query4 = SelectQuery::format_stat(parse_sql(sql4).unwrap()).unwrap();
+    assert_eq!((query4.limit, query4.offset), (Some(3), 5));
+
+    let sql5 = "SELECT name, COUNT(*), SUM(DISTINCT score) FROM users GROUP BY name HAVING COUNT(*) > 1 ORDER BY MAX(score);";
+    let query5 = SelectQuery::format_stat(parse_sql(sql5).unwrap()).unwrap();
+    assert_eq!(query5.group_by, vec!["name"]);
+    assert!(query5.having.is_some());
+    assert_eq!(
+        query5
+            .aggregates
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<&str>>(),
+        vec!["COUNT(*)", "SUM(DISTINCT score)", "MAX(score)"]
+    );
+    assert_eq!(
+        query3.order_by,
+        vec![
+            OrderBy {
+                key: OrderKey::Column("users.name".to_string()),
+                asc: false,
+                nulls_first: true,
+            },
+            OrderBy {
+                key: OrderKey::Position(1),
+                asc: true,
+                nulls_first: true,
+            },
+        ]
+    );
 }
@@ -0,0 +1,160 @@
+use crate::database::mvcc::IsolationLevel;
+use crate::parser::utils::parse_sql;
+use crate::system::errors::Errors;
+use sqlparser::ast::{Statement, TransactionIsolationLevel, TransactionMode};
+
+#[derive(Debug, PartialEq)]
+pub enum TransactionQuery {
+    Begin(Option<IsolationLevel>),
+    Commit,
+    Rollback,
+    Savepoint(String),
+    RollbackTo(String),
+    Release(String),
+    // `session` is set for the session default, otherwise the level applies to the
+    // current or next transaction
+    SetIsolation {
+        level: IsolationLevel,
+        session: bool,
+    },
+}
+
+impl TransactionQuery {
+    pub fn new(query: &str) -> Result<TransactionQuery, Errors> {
+        let words = query
+            .trim()
+            .trim_end_matches(';')
+            .split_whitespace()
+            .collect::<Vec<&str>>();
+        let lower = words
+            .iter()
+            .map(|w| w.to_lowercase())
+            .collect::<Vec<String>>();
+        // sqlparser does not know `ROLLBACK TO` and `RELEASE`, both are read by hand
+        let mut rest: &[String] = &lower;
+        if rest.first().map(String::as_str) == Some("rollback") {
+            rest = &rest[1..];
+            if matches!(
+                rest.first().map(String::as_str),
+                Some("transaction" | "work")
+            ) {
+                rest = &rest[1..];
+            }
+            if rest.first().map(String::as_str) == Some("to") {
+                return TransactionQuery::savepoint_name(&words, &rest[1..])
+                    .map(TransactionQuery::RollbackTo);
+            }
+        } else if rest.first().map(String::as_str) == Some("release") {
+            return TransactionQuery::savepoint_name(&words, &rest[1..])
+                .map(TransactionQuery::Release);
+        }
+        // SNAPSHOT is not a standard level, it is read as REPEATABLE READ which maps to it
+        if lower.len() > 2 && lower[lower.len() - 2..] == ["level", "snapshot"] {
+            let query = format!("{} REPEATABLE READ", words[..words.len() - 1].join(" "));
+            return TransactionQuery::format_stat(parse_sql(&query)?);
+        }
+        TransactionQuery::format_stat(parse_sql(query)?)
+    }
+
+    pub fn format_stat(state: Statement) -> Result<TransactionQuery, Errors> {
+        match state {
+            Statement::StartTransaction { modes, .. } => {
+                Ok(TransactionQuery::Begin(TransactionQuery::isolation(&modes)))
+            }
+            Statement::SetTransaction { modes, session, .. } => {
+                match TransactionQuery::isolation(&modes) {
+                    Some(level) => Ok(TransactionQuery::SetIsolation { level, session }),
+                    None => Err(Errors::UnimplementedOperation),
+                }
+            }
+            Statement::Commit { .. } => Ok(TransactionQuery::Commit),
+            Statement::Rollback { .. } => Ok(TransactionQuery::Rollback),
+            Statement::Savepoint { name } => Ok(TransactionQuery::Savepoint(name.value)),
+            _ => Err(Errors::InvalidExpression),
+        }
+    }
+
+    // READ UNCOMMITTED is upgraded to READ COMMITTED, REPEATABLE READ and SERIALIZABLE
+    // both run on a snapshot
+    fn isolation(modes: &[TransactionMode]) -> Option<IsolationLevel> {
+        modes.iter().find_map(|mode| match mode {
+            TransactionMode::IsolationLevel(level) => Some(match level {
+                TransactionIsolationLevel::ReadUncommitted
+                | TransactionIsolationLevel::ReadCommitted => IsolationLevel::ReadCommitted,
+                TransactionIsolationLevel::RepeatableRead
+                | TransactionIsolationLevel::Serializable => IsolationLevel::Snapshot,
+            }),
+            TransactionMode::AccessMode(_) => None,
+        })
+    }
+
+    // `[SAVEPOINT] name` at the end of `words`, `rest` is its lowercased tail
+    fn savepoint_name(words: &[&str], rest: &[String]) -> Result<String, Errors> {
+        let rest = match rest.first().map(String::as_str) {
+            Some("savepoint") => &rest[1..],
+            _ => rest,
+        };
+        if rest.len() != 1 {
+            return Err(Errors::ParseSQLError);
+        }
+        Ok(words[words.len() - 1].to_string())
+    }
+}
+
+#[test]
+fn test_transaction_query() {
+    assert_eq!(
+        TransactionQuery::new("BEGIN").unwrap(),
+        TransactionQuery::Begin(None)
+    );
+    assert_eq!(
+        TransactionQuery::new("start transaction;").unwrap(),
+        TransactionQuery::Begin(None)
+    );
+    assert_eq!(
+        TransactionQuery::new("BEGIN ISOLATION LEVEL SERIALIZABLE").unwrap(),
+        TransactionQuery::Begin(Some(IsolationLevel::Snapshot))
+    );
+    assert_eq!(
+        TransactionQuery::new("SET TRANSACTION ISOLATION LEVEL READ COMMITTED").unwrap(),
+        TransactionQuery::SetIsolation {
+            level: IsolationLevel::ReadCommitted,
+            session: false
+        }
+    );
+    assert_eq!(
+        TransactionQuery::new(
+            "SET SESSION CHARACTERISTICS AS TRANSACTION ISOLATION LEVEL SNAPSHOT"
+        )
+        .unwrap(),
+        TransactionQuery::SetIsolation {
+            level: IsolationLevel::Snapshot,
+            session: true
+        }
+    );
+    assert_eq!(
+        TransactionQuery::new("COMMIT").unwrap(),
+        TransactionQuery::Commit
+    );
+    assert_eq!(
+        TransactionQuery::new("ROLLBACK WORK").unwrap(),
+        TransactionQuery::Rollback
+    );
+    assert_eq!(
+        TransactionQuery::new("SAVEPOINT before_import").unwrap(),
+        TransactionQuery::Savepoint("before_import".to_string())
+    );
+    assert_eq!(
+        TransactionQuery::new("ROLLBACK TO SAVEPOINT before_import").unwrap(),
+        TransactionQuery::RollbackTo("before_import".to_string())
+    );
+    assert_eq!(
+        TransactionQuery::new("rollback to sp1;").unwrap(),
+        TransactionQuery::RollbackTo("sp1".to_string())
+    );
+    assert_eq!(
+        TransactionQuery::new("RELEASE SAVEPOINT sp1").unwrap(),
+        TransactionQuery::Release("sp1".to_string())
+    );
+    assert!(TransactionQuery::new("ROLLBACK TO").is_err());
+}
@@ -1,5 +1,7 @@
-use crate::parser::condition::Condition;
+use crate::parser::expr::Expression;
 use crate::parser::join::FromType;
+#[cfg(test)]
+use crate::parser::utils::parse_sql;
 use crate::system::errors::Errors;
 use crate::system::utils::custom_strip;
 use sqlparser::ast::Statement;
@@ -9,14 +11,14 @@ use std::collections::HashMap;
 pub struct UpdateQuery {
     pub tb_name: String,
     pub assignments: HashMap<String, String>,
-    pub condition: Option<Condition>,
+    pub condition: Option<Expression>,
 }
 
 impl UpdateQuery {
     pub fn format_stat(statement: Statement) -> Result<UpdateQuery, Errors> {
         let mut tb_name: String = "".to_string();
         let mut assignments_data: HashMap<String, String> = HashMap::new();
-        let mut condition_data: Option<Condition> = None;
+        let mut condition_data: Option<Expression> = None;
         if let Statement::Update {
             table,
             assignments,
@@ -24,13 +26,13 @@ impl UpdateQuery {
             ..
         } = statement
         {
-            let from = match FromType::new(vec![table]) {
+            let from = match FromType::new(vec![table], &mut vec![]) {
                 Ok(v) => v.first().unwrap().to_owned(),
                 Err(err) => {
                     return Err(err);
                 }
             };
-            if let FromType::String { tb } = from {
+            if let FromType::String { tb, .. } = from {
                 tb_name = tb;
             }
             assignments_data = assignments
@@ -43,10 +45,8 @@ impl UpdateQuery {
                     )
                 })
                 .collect::<HashMap<String, String>>();
-            let condition = Condition::from_expr(&selection.unwrap());
-            match condition {
-                Ok(v) => condition_data = Option::from(v),
-                Err(e) => return Err(e),
+            if let Some(expr) = selection {
+                condition_data = Some(Expression::from_expr(&expr)?);
             }
         }
         Ok(UpdateQuery {
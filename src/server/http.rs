@@ -0,0 +1,530 @@
+use crate::database::base::Value;
+use crate::database::result::Output;
+use crate::database::session::Session;
+use crate::server::split_statements;
+use crate::system::commands::parser::execute;
+use crate::system::commands::system::{self as SC, database_names, database_statement};
+use crate::system::dbs::DbSystem;
+use crate::system::errors::Errors;
+use serde_json::{json, Value as Json};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+// requests with a larger body are refused
+const MAX_BODY: usize = 16 << 20;
+
+/// The parts of a request the API looks at, header names are lower case.
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+    keep_alive: bool,
+}
+
+/// A JSON reply with its status code.
+struct Response {
+    status: u16,
+    body: Json,
+}
+
+impl Response {
+    fn ok(body: Json) -> Response {
+        Response { status: 200, body }
+    }
+
+    fn error(status: u16, kind: &str, message: &str) -> Response {
+        Response {
+            status,
+            body: json!({ "error": { "kind": kind, "message": message } }),
+        }
+    }
+}
+
+impl From<Errors> for Response {
+    fn from(err: Errors) -> Self {
+        let debug = format!("{:?}", err);
+        let kind = debug.split('(').next().unwrap_or_default();
+        Response::error(http_status(&err), kind, err.to_string().trim_end())
+    }
+}
+
+/// Serves the requests of one client until it closes the connection. `sys` holds the
+/// users read when the server started, every request fails while it could not be read.
+pub fn handle(stream: TcpStream, _id: u32, sys: &io::Result<DbSystem>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+    loop {
+        let request = match read_request(&mut reader) {
+            Ok(Some(v)) => v,
+            Ok(None) => return Ok(()),
+            Err(err) if err.kind() == ErrorKind::InvalidData => {
+                let response = Response::error(400, "BadRequest", &err.to_string());
+                return write_response(&mut stream, &response, false);
+            }
+            Err(err) => return Err(err),
+        };
+        let response = match sys {
+            Ok(sys) if authorized(&request, sys) => route(&request),
+            Ok(_) => Response::error(401, "Unauthorized", "Invalid username or password."),
+            Err(err) => {
+                let message = format!("The users cannot be read: {}.", err);
+                Response::error(500, "ConfigError", &message)
+            }
+        };
+        write_response(&mut stream, &response, request.keep_alive)?;
+        if !request.keep_alive {
+            return Ok(());
+        }
+    }
+}
+
+// `POST /query`, `GET /databases`, `GET /databases/{db}/tables` and
+// `GET /databases/{db}/tables/{tb}`
+fn route(request: &Request) -> Response {
+    let parts = request
+        .path
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>();
+    let result = match (request.method.as_str(), parts.as_slice()) {
+        ("POST", ["query"]) => query(request),
+        ("GET", ["databases"]) => database_names()
+            .map(|names| Response::ok(json!({ "databases": names })))
+            .map_err(|_| Errors::FileSystemError),
+        ("GET", ["databases", db_name, "tables"]) => tables(db_name),
+        ("GET", ["databases", db_name, "tables", tb_name]) => table_schema(db_name, tb_name),
+        (_, ["query"] | ["databases", ..]) => {
+            let message = format!("{} is not allowed on {}.", request.method, request.path);
+            return Response::error(405, "MethodNotAllowed", &message);
+        }
+        _ => {
+            let message = format!("{} is not found.", request.path);
+            return Response::error(404, "NotFound", &message);
+        }
+    };
+    result.unwrap_or_else(Response::from)
+}
+
+/// Runs the statements of `{"sql": ..., "database": ...}` in a session of their own,
+/// a transaction left open by them is rolled back. The database may also be given as
+/// `?database=` and a body that is not JSON is taken as the SQL itself.
+fn query(request: &Request) -> Result<Response, Errors> {
+    let text = String::from_utf8_lossy(&request.body).to_string();
+    let (sql, database) = match serde_json::from_str::<Json>(&text) {
+        Ok(Json::Object(body)) => match body.get("sql").and_then(|sql| sql.as_str()) {
+            Some(sql) => {
+                let database = body.get("database").and_then(|db| db.as_str());
+                (sql.to_string(), database.map(|db| db.to_string()))
+            }
+            None => {
+                let message = "The body needs a \"sql\" string.";
+                return Ok(Response::error(400, "BadRequest", message));
+            }
+        },
+        _ => (text, None),
+    };
+    let database = database.or(request.query.get("database").cloned());
+    let mut session = Session::new();
+    if let Some(db_name) = database.filter(|name| !name.is_empty()) {
+        SC::use_db(format!("sys usedb {}", db_name), &mut session)?;
+    }
+    let statements = split_statements(&sql);
+    if statements.is_empty() {
+        return Ok(Response::error(400, "BadRequest", "The query is empty."));
+    }
+    let mut results = vec![];
+    for (ix, sql) in statements.iter().enumerate() {
+        let output = match database_statement(sql, &mut session) {
+            Some(result) => result.map(|_| Output::Done),
+            None => execute(sql.to_string(), &mut session),
+        };
+        match output {
+            Ok(output) => results.push(output_json(output)),
+            Err(err) if statements.len() > 1 => {
+                let mut response = Response::from(err);
+                response.body["error"]["statement"] = json!(ix);
+                return Ok(response);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    if results.len() == 1 {
+        return Ok(Response::ok(results.pop().unwrap()));
+    }
+    Ok(Response::ok(json!({ "results": results })))
+}
+
+fn tables(db_name: &str) -> Result<Response, Errors> {
+    let mut session = Session::new();
+    SC::use_db(format!("sys usedb {}", db_name), &mut session)?;
+    let names = session.run(|db, _| {
+        Ok(db
+            .tables
+            .iter()
+            .map(|tb| tb.name.to_string())
+            .collect::<Vec<String>>())
+    })?;
+    Ok(Response::ok(json!({ "tables": names })))
+}
+
+fn table_schema(db_name: &str, tb_name: &str) -> Result<Response, Errors> {
+    let mut session = Session::new();
+    SC::use_db(format!("sys usedb {}", db_name), &mut session)?;
+    let schema = session.run(|db, _| {
+        let tb = db.get_table(tb_name.to_string())?;
+        let columns = tb
+            .columns
+            .iter()
+            .map(|attr| {
+                json!({
+                    "name": attr.name,
+                    "datatype": attr.datatype.to_string(),
+                    "is_pk": attr.is_pk,
+                    "is_nullable": attr.is_nullable,
+                    "default": attr.default,
+                })
+            })
+            .collect::<Vec<Json>>();
+        let indexes = tb
+            .indexes
+            .iter()
+            .map(|idx| json!({ "name": idx.name, "column": idx.column, "unique": idx.unique }))
+            .collect::<Vec<Json>>();
+        Ok(json!({
+            "name": tb.name,
+            "columns": columns,
+            "foreign_keys": tb.foreign_keys.clone().unwrap_or_default(),
+            "indexes": indexes,
+        }))
+    })?;
+    Ok(Response::ok(schema))
+}
+
+// rows as arrays in the order of `columns`, messages as they are, anything else as an
+// affected count
+fn output_json(output: Output) -> Json {
+    match output {
+        Output::Rows(result)
+        | Output::Schema {
+            columns: result, ..
+        } => {
+            let types = result
+                .types
+                .iter()
+                .map(|datatype| datatype.to_string())
+                .collect::<Vec<String>>();
+            let rows = result
+                .rows
+                .iter()
+                .map(|row| row.iter().map(value_json).collect::<Vec<Json>>())
+                .collect::<Vec<Vec<Json>>>();
+            json!({ "columns": result.columns, "types": types, "rows": rows })
+        }
+        Output::Affected(affected) => json!({ "affected": affected }),
+        Output::Message(message) => json!({ "message": message }),
+        Output::Done => json!({ "affected": 0 }),
+    }
+}
+
+fn value_json(val: &Value) -> Json {
+    match val {
+        Value::Int(v) => json!(v),
+        // the digits the REPL shows, not those of the float widened to f64
+        Value::Float(_) => val
+            .to_string()
+            .parse::<f64>()
+            .map(|v| json!(v))
+            .unwrap_or(Json::Null),
+        Value::Bool(v) => json!(v),
+        Value::Str(v) => json!(v),
+        Value::Null => Json::Null,
+    }
+}
+
+// status code of the response to an engine error
+fn http_status(err: &Errors) -> u16 {
+    match err {
+        Errors::ParseSQLError
+        | Errors::InvalidExpression
+        | Errors::InvalidCommand
+        | Errors::InvalidColumnType
+        | Errors::InvalidOrderPosition(_)
+        | Errors::ColumnNotGrouped(_)
+        | Errors::TypeMismatch(_)
+        | Errors::DivisionByZero
+        | Errors::NumericOverflow
+        | Errors::FunctionNotExisted(_)
+        | Errors::InvalidCast(_)
+        | Errors::InvalidSubquery(_)
+        | Errors::ColumnCountMismatch(_)
+        | Errors::RecursionLimitExceeded(..)
+        | Errors::InvalidDatabaseName(_)
+        | Errors::NullValue(_)
+        | Errors::AmbiguousColumn(_)
+        | Errors::RowTooLarge(_)
+        | Errors::TransactionStarted
+        | Errors::TransactionNotStarted
+        | Errors::SchemaChangeInTransaction => 400,
+        Errors::DatabaseNotExisted
+        | Errors::TableNotExisted(_)
+        | Errors::ColumnNotExisted(_)
+        | Errors::IndexNotExisted(_)
+        | Errors::SavepointNotExisted(_) => 404,
+        Errors::DatabaseExisted(_)
+        | Errors::DatabaseInUse(_)
+        | Errors::TableExisted(_)
+        | Errors::IndexExisted(_)
+        | Errors::IndexRequired(_)
+        | Errors::PrimaryKeyExisted(_)
+        | Errors::WriteConflict(_)
+        | Errors::DatabaseLocked(_) => 409,
+        Errors::UnimplementedOperation => 501,
+        _ => 500,
+    }
+}
+
+// basic auth with a user of `cfg.bin`
+fn authorized(request: &Request, sys: &DbSystem) -> bool {
+    let credentials = request
+        .headers
+        .get("authorization")
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| decode_base64(encoded.trim()))
+        .map(|decoded| String::from_utf8_lossy(&decoded).to_string());
+    match credentials.as_ref().and_then(|s| s.split_once(':')) {
+        Some((username, password)) => sys.login(username.to_string(), password.to_string()),
+        None => false,
+    }
+}
+
+fn read_request(reader: &mut BufReader<TcpStream>) -> io::Result<Option<Request>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let bad_request = |message: &str| io::Error::new(ErrorKind::InvalidData, message.to_string());
+    let parts = line.split_whitespace().collect::<Vec<&str>>();
+    let (method, target, version) = match parts.as_slice() {
+        [method, target, version] => (method.to_string(), *target, version.to_string()),
+        _ => return Err(bad_request("Malformed request line.")),
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (decode_url(k), decode_url(v)))
+        .collect::<HashMap<String, String>>();
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(bad_request("Unexpected end of the headers."));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        match line.split_once(':') {
+            Some((name, value)) => {
+                headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+            }
+            None => return Err(bad_request("Malformed header.")),
+        }
+    }
+    let len = match headers.get("content-length") {
+        Some(len) => len
+            .parse::<usize>()
+            .map_err(|_| bad_request("Invalid Content-Length."))?,
+        None => 0,
+    };
+    if len > MAX_BODY {
+        return Err(bad_request("The body is too large."));
+    }
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body)?;
+    let connection = headers
+        .get("connection")
+        .map(|v| v.to_lowercase())
+        .unwrap_or_default();
+    let keep_alive = match version.as_str() {
+        "HTTP/1.0" => connection == "keep-alive",
+        _ => connection != "close",
+    };
+    Ok(Some(Request {
+        method,
+        path: decode_url(path),
+        query,
+        headers,
+        body,
+        keep_alive,
+    }))
+}
+
+fn write_response(stream: &mut TcpStream, response: &Response, keep_alive: bool) -> io::Result<()> {
+    let body = response.body.to_string();
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        501 => "Not Implemented",
+        _ => "Internal Server Error",
+    };
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n",
+        response.status,
+        reason,
+        body.len()
+    );
+    if response.status == 401 {
+        head.push_str("WWW-Authenticate: Basic realm=\"SimpleDB\"\r\n");
+    }
+    if !keep_alive {
+        head.push_str("Connection: close\r\n");
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()
+}
+
+fn decode_url(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = vec![];
+    let mut ix = 0;
+    while ix < bytes.len() {
+        let hex = s
+            .get(ix + 1..ix + 3)
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[ix], hex) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                ix += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (b, _) => decoded.push(b),
+        }
+        ix += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    let mut decoded = vec![];
+    let mut bits: u32 = 0;
+    let mut count = 0;
+    for c in s.trim_end_matches('=').bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        bits = (bits << 6) | v as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            decoded.push((bits >> count) as u8);
+        }
+    }
+    Some(decoded)
+}
+
+#[test]
+fn test_http_query_api() {
+    use crate::system::utils::TempDb;
+    use std::net::TcpListener;
+
+    let serve = |sys: io::Result<DbSystem>| {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for (id, stream) in listener.incoming().enumerate() {
+                let _ = handle(stream.unwrap(), id as u32, &sys);
+            }
+        });
+        addr
+    };
+    let addr = serve(DbSystem::load());
+    // `root:123456`
+    let auth = "Authorization: Basic cm9vdDoxMjM0NTY=\r\n";
+    let send = |addr, method: &str, path: &str, auth: &str, body: &str| {
+        let request = format!(
+            "{} {} HTTP/1.1\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method,
+            path,
+            auth,
+            body.len(),
+            body
+        );
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse::<u16>().unwrap();
+        let body = response.split_once("\r\n\r\n").unwrap().1;
+        (status, serde_json::from_str::<Json>(body).unwrap())
+    };
+    let request =
+        |method: &str, path: &str, auth: &str, body: &str| send(addr, method, path, auth, body);
+
+    assert_eq!(request("GET", "/databases", "", "").0, 401);
+    let (status, body) = request("GET", "/databases", auth, "");
+    assert_eq!(status, 200);
+    assert!(body["databases"].is_array());
+
+    // created and dropped over HTTP, the guard only cleans up after a failure
+    let temp = TempDb::named("http");
+    let db_name = temp.name.as_str();
+    let sql = format!("CREATE DATABASE {}; SELEC 1", db_name);
+    let (status, body) = request("POST", "/query", auth, &sql);
+    assert_eq!(status, 400);
+    assert_eq!(body["error"]["kind"], "InvalidCommand");
+    assert_eq!(body["error"]["statement"], 1);
+
+    let query = |sql: &str| {
+        let body = json!({ "database": db_name, "sql": sql }).to_string();
+        request("POST", "/query", auth, &body)
+    };
+    let (status, _) = query("CREATE TABLE t (id INT PRIMARY KEY, name TEXT, score FLOAT)");
+    assert_eq!(status, 200);
+    let (_, body) = query("INSERT INTO t (id, name, score) VALUES (1, 'a', 1.5), (2, 'b', 3.1)");
+    assert_eq!(body, json!({ "affected": 2 }));
+    let (_, body) = query("SELECT id, name, score FROM t WHERE id = 2");
+    assert_eq!(
+        body,
+        json!({ "columns": ["id", "name", "score"], "types": ["int", "string", "float"], "rows": [[2, "b", 3.1]] })
+    );
+    let (status, body) = query("INSERT INTO t (id, name) VALUES (1, 'c')");
+    assert_eq!(status, 409);
+    assert_eq!(body["error"]["kind"], "PrimaryKeyExisted");
+
+    let path = format!("/databases/{}/tables/t", db_name);
+    let (status, body) = request("GET", &path, auth, "");
+    assert_eq!(status, 200);
+    assert_eq!(body["columns"][0]["name"], "id");
+    assert_eq!(body["columns"][0]["is_pk"], true);
+    let path = format!("/databases/{}/tables/missing", db_name);
+    assert_eq!(request("GET", &path, auth, "").0, 404);
+
+    let (status, _) = query(&format!("DROP DATABASE {}", db_name));
+    assert_eq!(status, 200);
+    assert_eq!(request("GET", "/databases/nope/tables", auth, "").0, 404);
+
+    // a config that could not be read at startup fails every request
+    let broken = serve(Err(io::Error::new(
+        ErrorKind::NotFound,
+        "cfg.bin is missing",
+    )));
+    let (status, body) = send(broken, "GET", "/databases", auth, "");
+    assert_eq!(status, 500);
+    assert_eq!(body["error"]["kind"], "ConfigError");
+}
@@ -0,0 +1,141 @@
+pub(crate) mod http;
+pub(crate) mod mysql;
+pub(crate) mod postgres;
+
+use crate::database::base::{DataType, Value};
+use crate::database::result::ResultSet;
+use crate::storage::record::Reader;
+use crate::system::dbs::DbSystem;
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::net::TcpListener;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread;
+
+/// Wire protocols the server speaks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    MySql,
+    Postgres,
+    Http,
+}
+
+impl Protocol {
+    pub fn new(name: &str) -> Option<Protocol> {
+        match name.to_lowercase().as_str() {
+            "mysql" => Some(Protocol::MySql),
+            "postgres" | "postgresql" | "pg" => Some(Protocol::Postgres),
+            "http" => Some(Protocol::Http),
+            _ => None,
+        }
+    }
+
+    pub fn default_addr(&self) -> &'static str {
+        match self {
+            Protocol::MySql => "127.0.0.1:3306",
+            Protocol::Postgres => "127.0.0.1:5432",
+            Protocol::Http => "127.0.0.1:8080",
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Protocol::MySql => f.write_str("mysql"),
+            Protocol::Postgres => f.write_str("postgres"),
+            Protocol::Http => f.write_str("http"),
+        }
+    }
+}
+
+/// Accepts clients on `addr`. Every connection is served by its own thread with its
+/// own session, connections to the same database share it through MVCC.
+pub fn serve(protocol: Protocol, addr: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("SimpleDB is listening on {} ({}).", addr, protocol);
+    // the http api reads its users once, mysql and postgres on every login
+    let users = match protocol {
+        Protocol::Http => Some(Arc::new(DbSystem::load())),
+        _ => None,
+    };
+    let mut next_id: u32 = 1;
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(v) => v,
+            Err(err) => {
+                eprintln!("Failed to accept a connection: {}", err);
+                continue;
+            }
+        };
+        let id = next_id;
+        next_id = next_id.wrapping_add(1);
+        let users = users.clone();
+        thread::spawn(move || {
+            // a panic ends its own connection only
+            let result = panic::catch_unwind(AssertUnwindSafe(|| match protocol {
+                Protocol::MySql => mysql::handle(stream, id),
+                Protocol::Postgres => postgres::handle(stream, id),
+                Protocol::Http => http::handle(stream, id, users.as_deref().unwrap()),
+            }));
+            match result {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => eprintln!("Connection {} closed: {}", id, err),
+                Err(_) => eprintln!("Connection {} closed by a panic.", id),
+            }
+        });
+    }
+    Ok(())
+}
+
+pub(crate) fn names_result(column: &str, names: Vec<String>) -> ResultSet {
+    ResultSet {
+        columns: vec![column.to_string()],
+        types: vec![DataType::String],
+        rows: names
+            .into_iter()
+            .map(|name| vec![Value::Str(name)])
+            .collect(),
+    }
+}
+
+/// Splits a batch of statements on the semicolons outside of quotes.
+pub(crate) fn split_statements(sql: &str) -> Vec<&str> {
+    let mut statements = vec![];
+    let mut quote = None;
+    let mut start = 0;
+    for (ix, c) in sql.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, ';') => {
+                statements.push(&sql[start..ix]);
+                start = ix + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(&sql[start..]);
+    statements
+        .into_iter()
+        .map(|sql| sql.trim())
+        .filter(|sql| !sql.is_empty())
+        .collect()
+}
+
+pub(crate) fn take_cstr(reader: &mut Reader) -> io::Result<String> {
+    let rest = &reader.bytes[reader.pos..];
+    let len = match rest.iter().position(|b| *b == 0) {
+        Some(v) => v,
+        None => {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "unterminated string",
+            ))
+        }
+    };
+    let s = String::from_utf8_lossy(reader.take(len)?).to_string();
+    reader.take(1)?;
+    Ok(s)
+}
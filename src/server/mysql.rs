@@ -0,0 +1,614 @@
+use crate::database::base::{DataType, Value};
+use crate::database::result::{Output, ResultSet};
+use crate::database::session::Session;
+use crate::server::{names_result, take_cstr};
+use crate::storage::record::Reader;
+use crate::system::commands::parser::execute;
+use crate::system::commands::system::{self as SC, database_names, database_statement};
+use crate::system::dbs::DbSystem;
+use crate::system::errors::Errors;
+use sha2::{Digest, Sha256};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SERVER_VERSION: &str = "5.7.99-SimpleDB";
+const MAX_PACKET: usize = 0xff_ffff;
+
+// capability flags
+const CLIENT_LONG_PASSWORD: u32 = 0x1;
+const CLIENT_LONG_FLAG: u32 = 0x4;
+const CLIENT_CONNECT_WITH_DB: u32 = 0x8;
+const CLIENT_PROTOCOL_41: u32 = 0x200;
+const CLIENT_TRANSACTIONS: u32 = 0x2000;
+const CLIENT_SECURE_CONNECTION: u32 = 0x8000;
+const CLIENT_PLUGIN_AUTH: u32 = 0x8_0000;
+const CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA: u32 = 0x20_0000;
+const CAPABILITIES: u32 = CLIENT_LONG_PASSWORD
+    | CLIENT_LONG_FLAG
+    | CLIENT_CONNECT_WITH_DB
+    | CLIENT_PROTOCOL_41
+    | CLIENT_TRANSACTIONS
+    | CLIENT_SECURE_CONNECTION
+    | CLIENT_PLUGIN_AUTH
+    | CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA;
+
+const SERVER_STATUS_IN_TRANS: u16 = 0x1;
+const SERVER_STATUS_AUTOCOMMIT: u16 = 0x2;
+
+const COM_QUIT: u8 = 0x01;
+const COM_INIT_DB: u8 = 0x02;
+const COM_QUERY: u8 = 0x03;
+const COM_FIELD_LIST: u8 = 0x04;
+const COM_PING: u8 = 0x0e;
+
+// utf8_general_ci for text, binary for numbers
+const CHARSET_UTF8: u16 = 33;
+const CHARSET_BINARY: u16 = 63;
+
+// Passwords are checked by `DbSystem::login`, which needs them in clear text, so
+// clients are switched to the `mysql_clear_password` plugin.
+const CLEAR_PASSWORD: &str = "mysql_clear_password";
+const NATIVE_PASSWORD: &str = "mysql_native_password";
+
+/// One client connection: packets are framed by a 3-byte length and a sequence
+/// number that every reply continues from the last packet received.
+struct Connection {
+    stream: TcpStream,
+    seq: u8,
+    out: Vec<u8>,
+    session: Session,
+}
+
+impl Connection {
+    fn read_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut payload = vec![];
+        loop {
+            let mut header = [0u8; 4];
+            match self.stream.read_exact(&mut header) {
+                Ok(_) => {}
+                Err(err) if err.kind() == ErrorKind::UnexpectedEof && payload.is_empty() => {
+                    return Ok(None)
+                }
+                Err(err) => return Err(err),
+            }
+            let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+            self.seq = header[3].wrapping_add(1);
+            let start = payload.len();
+            payload.resize(start + len, 0);
+            self.stream.read_exact(&mut payload[start..])?;
+            // a payload of exactly MAX_PACKET bytes continues in the next packet
+            if len < MAX_PACKET {
+                return Ok(Some(payload));
+            }
+        }
+    }
+
+    fn send(&mut self, payload: &[u8]) {
+        let mut chunks = payload.chunks(MAX_PACKET).collect::<Vec<&[u8]>>();
+        if payload.len().is_multiple_of(MAX_PACKET) {
+            chunks.push(&[]);
+        }
+        for chunk in chunks {
+            self.out
+                .extend_from_slice(&(chunk.len() as u32).to_le_bytes()[..3]);
+            self.out.push(self.seq);
+            self.out.extend_from_slice(chunk);
+            self.seq = self.seq.wrapping_add(1);
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.write_all(&self.out)?;
+        self.out.clear();
+        self.stream.flush()
+    }
+
+    fn status(&self) -> u16 {
+        if self.session.in_transaction() {
+            SERVER_STATUS_AUTOCOMMIT | SERVER_STATUS_IN_TRANS
+        } else {
+            SERVER_STATUS_AUTOCOMMIT
+        }
+    }
+
+    fn send_ok(&mut self, affected: u64) {
+        let mut buf = vec![0x00];
+        put_lenenc_int(&mut buf, affected);
+        put_lenenc_int(&mut buf, 0);
+        buf.extend_from_slice(&self.status().to_le_bytes());
+        buf.extend_from_slice(&0u16.to_le_bytes());
+        self.send(&buf);
+    }
+
+    fn send_eof(&mut self) {
+        let mut buf = vec![0xfe];
+        buf.extend_from_slice(&0u16.to_le_bytes());
+        buf.extend_from_slice(&self.status().to_le_bytes());
+        self.send(&buf);
+    }
+
+    fn send_err(&mut self, code: u16, state: &str, message: &str) {
+        let mut buf = vec![0xff];
+        buf.extend_from_slice(&code.to_le_bytes());
+        buf.push(b'#');
+        buf.extend_from_slice(state.as_bytes());
+        buf.extend_from_slice(message.as_bytes());
+        self.send(&buf);
+    }
+
+    fn send_error(&mut self, err: &Errors) {
+        let (code, state) = error_code(err);
+        self.send_err(code, state, err.to_string().trim_end());
+    }
+
+    fn send_rows(&mut self, result: &ResultSet) {
+        let mut buf = vec![];
+        put_lenenc_int(&mut buf, result.columns.len() as u64);
+        self.send(&buf);
+        for (name, datatype) in result.columns.iter().zip(&result.types) {
+            let (kind, charset, length, decimals) = column_type(datatype);
+            let mut buf = vec![];
+            put_lenenc_str(&mut buf, b"def");
+            put_lenenc_str(&mut buf, self.session.db_name().as_bytes());
+            put_lenenc_str(&mut buf, b"");
+            put_lenenc_str(&mut buf, b"");
+            put_lenenc_str(&mut buf, name.as_bytes());
+            put_lenenc_str(&mut buf, name.as_bytes());
+            buf.push(0x0c);
+            buf.extend_from_slice(&charset.to_le_bytes());
+            buf.extend_from_slice(&length.to_le_bytes());
+            buf.push(kind);
+            buf.extend_from_slice(&0u16.to_le_bytes());
+            buf.push(decimals);
+            buf.extend_from_slice(&[0, 0]);
+            self.send(&buf);
+        }
+        self.send_eof();
+        for row in &result.rows {
+            let mut buf = vec![];
+            for val in row {
+                match val {
+                    Value::Null => buf.push(0xfb),
+                    Value::Bool(v) => put_lenenc_str(&mut buf, if *v { b"1" } else { b"0" }),
+                    _ => put_lenenc_str(&mut buf, val.to_string().as_bytes()),
+                }
+            }
+            self.send(&buf);
+        }
+        self.send_eof();
+    }
+
+    fn send_output(&mut self, output: Result<Output, Errors>) {
+        match output {
+            Ok(Output::Rows(result))
+            | Ok(Output::Schema {
+                columns: result, ..
+            }) => self.send_rows(&result),
+            Ok(Output::Affected(affected)) => self.send_ok(affected as u64),
+            Ok(Output::Message(_)) | Ok(Output::Done) => self.send_ok(0),
+            Err(err) => self.send_error(&err),
+        }
+    }
+}
+
+/// Serves one client: handshake and login, then commands until it quits.
+pub fn handle(stream: TcpStream, id: u32) -> io::Result<()> {
+    let mut conn = Connection {
+        stream,
+        seq: 0,
+        out: vec![],
+        session: Session::new(),
+    };
+    if !authenticate(&mut conn, id)? {
+        return Ok(());
+    }
+    while let Some(packet) = conn.read_packet()? {
+        let (command, body) = match packet.split_first() {
+            Some((command, body)) => (*command, body),
+            None => continue,
+        };
+        match command {
+            COM_QUIT => break,
+            COM_INIT_DB => {
+                let command = format!("sys usedb {}", String::from_utf8_lossy(body));
+                let output = SC::use_db(command, &mut conn.session).map(|_| Output::Done);
+                conn.send_output(output);
+            }
+            COM_QUERY => {
+                let sql = String::from_utf8_lossy(body).to_string();
+                let output = query(&mut conn.session, &sql);
+                conn.send_output(output);
+            }
+            COM_PING => conn.send_ok(0),
+            // column completion of old clients, answered with no columns
+            COM_FIELD_LIST => conn.send_eof(),
+            _ => conn.send_err(1047, "08S01", "Unknown command"),
+        }
+        conn.flush()?;
+    }
+    Ok(())
+}
+
+// Sends the initial handshake, reads the login and checks it with `DbSystem`.
+fn authenticate(conn: &mut Connection, id: u32) -> io::Result<bool> {
+    let scramble = scramble(id);
+    let mut buf = vec![0x0a];
+    buf.extend_from_slice(SERVER_VERSION.as_bytes());
+    buf.push(0);
+    buf.extend_from_slice(&id.to_le_bytes());
+    buf.extend_from_slice(&scramble[..8]);
+    buf.push(0);
+    buf.extend_from_slice(&(CAPABILITIES as u16).to_le_bytes());
+    buf.push(CHARSET_UTF8 as u8);
+    buf.extend_from_slice(&SERVER_STATUS_AUTOCOMMIT.to_le_bytes());
+    buf.extend_from_slice(&((CAPABILITIES >> 16) as u16).to_le_bytes());
+    buf.push(scramble.len() as u8 + 1);
+    buf.extend_from_slice(&[0; 10]);
+    buf.extend_from_slice(&scramble[8..]);
+    buf.push(0);
+    buf.extend_from_slice(NATIVE_PASSWORD.as_bytes());
+    buf.push(0);
+    conn.send(&buf);
+    conn.flush()?;
+
+    let packet = match conn.read_packet()? {
+        Some(v) => v,
+        None => return Ok(false),
+    };
+    let login = match Login::parse(&packet) {
+        Ok(v) => v,
+        Err(_) => {
+            conn.send_err(1043, "08S01", "Bad handshake");
+            conn.flush()?;
+            return Ok(false);
+        }
+    };
+    let password = if login.plugin == CLEAR_PASSWORD {
+        login.auth
+    } else if login.capabilities & CLIENT_PLUGIN_AUTH != 0 {
+        let mut buf = vec![0xfe];
+        buf.extend_from_slice(CLEAR_PASSWORD.as_bytes());
+        buf.push(0);
+        buf.extend_from_slice(&scramble);
+        buf.push(0);
+        conn.send(&buf);
+        conn.flush()?;
+        match conn.read_packet()? {
+            Some(v) => v,
+            None => return Ok(false),
+        }
+    } else {
+        conn.send_err(
+            1251,
+            "08004",
+            "Client does not support authentication protocol requested by server",
+        );
+        conn.flush()?;
+        return Ok(false);
+    };
+    let password = String::from_utf8_lossy(&password)
+        .trim_end_matches('\0')
+        .to_string();
+    if !DbSystem::new().login(login.user.to_string(), password) {
+        let message = format!("Access denied for user '{}'", login.user);
+        conn.send_err(1045, "28000", &message);
+        conn.flush()?;
+        return Ok(false);
+    }
+    if let Some(db_name) = login.database {
+        if let Err(err) = SC::use_db(format!("sys usedb {}", db_name), &mut conn.session) {
+            conn.send_error(&err);
+            conn.flush()?;
+            return Ok(false);
+        }
+    }
+    conn.send_ok(0);
+    conn.flush()?;
+    Ok(true)
+}
+
+/// The fields of a HandshakeResponse41 packet the server uses.
+struct Login {
+    capabilities: u32,
+    user: String,
+    auth: Vec<u8>,
+    database: Option<String>,
+    plugin: String,
+}
+
+impl Login {
+    fn parse(packet: &[u8]) -> io::Result<Login> {
+        let mut reader = Reader {
+            bytes: packet,
+            pos: 0,
+        };
+        let capabilities = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+        if capabilities & CLIENT_PROTOCOL_41 == 0 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "protocol 41 required",
+            ));
+        }
+        // max packet size, character set and filler
+        reader.take(4 + 1 + 23)?;
+        let user = take_cstr(&mut reader)?;
+        let auth = if capabilities & CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA != 0 {
+            let len = take_lenenc_int(&mut reader)? as usize;
+            reader.take(len)?.to_vec()
+        } else {
+            let len = reader.take(1)?[0] as usize;
+            reader.take(len)?.to_vec()
+        };
+        let mut database = None;
+        if capabilities & CLIENT_CONNECT_WITH_DB != 0 && reader.pos < packet.len() {
+            let name = take_cstr(&mut reader)?;
+            if !name.is_empty() {
+                database = Some(name);
+            }
+        }
+        let mut plugin = "".to_string();
+        if capabilities & CLIENT_PLUGIN_AUTH != 0 && reader.pos < packet.len() {
+            plugin = take_cstr(&mut reader)?;
+        }
+        Ok(Login {
+            capabilities,
+            user,
+            auth,
+            database,
+            plugin,
+        })
+    }
+}
+
+/// Runs a `COM_QUERY`. Besides the statements of the engine it answers what drivers
+/// and the `mysql` client send on their own: session variables, `SET` of client
+/// settings, `USE`, `SHOW DATABASES` and `SHOW TABLES`.
+fn query(session: &mut Session, sql: &str) -> Result<Output, Errors> {
+    let sql = sql.trim().trim_end_matches(';').trim();
+    let lower = sql.to_lowercase();
+    let words = lower.split_whitespace().collect::<Vec<&str>>();
+    match words.as_slice() {
+        ["select", ..]
+            if !words.contains(&"from") && (lower.contains("@@") || lower.contains("()")) =>
+        {
+            Ok(Output::Rows(variables(session, sql)))
+        }
+        ["set", "transaction", ..] | ["set", "session", "characteristics", ..] => {
+            execute(sql.to_string(), session)
+        }
+        ["set", ..] => Ok(Output::Done),
+        ["show", "databases"] => {
+            let names = database_names().map_err(|_| Errors::FileSystemError)?;
+            Ok(Output::Rows(names_result("Database", names)))
+        }
+        ["show", "tables"] => {
+            let column = format!("Tables_in_{}", session.db_name());
+            let names = session.run(|db, _| {
+                Ok(db
+                    .tables
+                    .iter()
+                    .map(|tb| tb.name.to_string())
+                    .collect::<Vec<String>>())
+            })?;
+            Ok(Output::Rows(names_result(&column, names)))
+        }
+        _ => match database_statement(sql, session) {
+            Some(result) => result.map(|_| Output::Done),
+            None => execute(sql.to_string(), session),
+        },
+    }
+}
+
+// `SELECT @@version_comment LIMIT 1`, `SELECT DATABASE()` and the like
+fn variables(session: &Session, sql: &str) -> ResultSet {
+    // ASCII lowercase keeps the byte offsets of `sql`, other characters may change length
+    let mut items = sql[6..].trim();
+    if let Some(ix) = items.to_ascii_lowercase().find(" limit ") {
+        items = &items[..ix];
+    }
+    let mut columns = vec![];
+    let mut row = vec![];
+    for item in items.split(',').map(|item| item.trim()) {
+        let (expr, name) = match item.to_ascii_lowercase().find(" as ") {
+            Some(ix) => (item[..ix].trim(), item[ix + 4..].trim()),
+            None => (item, item),
+        };
+        let expr = expr.to_lowercase();
+        let var = expr
+            .trim_start_matches("@@")
+            .trim_start_matches("session.")
+            .trim_start_matches("global.");
+        let val = match var {
+            "version_comment" => Value::Str("SimpleDB".to_string()),
+            "version" | "version()" => Value::Str(SERVER_VERSION.to_string()),
+            "database()" if !session.db_name().is_empty() => {
+                Value::Str(session.db_name().to_string())
+            }
+            "autocommit" => Value::Int(1),
+            "max_allowed_packet" => Value::Int(MAX_PACKET as i64),
+            "tx_isolation" | "transaction_isolation" => {
+                Value::Str(format!("{:?}", session.isolation).to_uppercase())
+            }
+            _ => Value::Null,
+        };
+        columns.push(name.to_string());
+        row.push(val);
+    }
+    ResultSet {
+        types: vec![DataType::String; columns.len()],
+        columns,
+        rows: vec![row],
+    }
+}
+
+// type, character set, display length and decimals of a column definition
+fn column_type(datatype: &DataType) -> (u8, u16, u32, u8) {
+    match datatype {
+        DataType::Int => (0x03, CHARSET_BINARY, 11, 0),
+        DataType::Float => (0x04, CHARSET_BINARY, 12, 31),
+        DataType::Bool => (0x01, CHARSET_BINARY, 1, 0),
+        DataType::String | DataType::Invalid => (0xfd, CHARSET_UTF8, 255 * 3, 0),
+    }
+}
+
+// error number and sql state reported for an engine error
+fn error_code(err: &Errors) -> (u16, &'static str) {
+    match err {
+        Errors::ParseSQLError | Errors::InvalidExpression | Errors::InvalidCommand => {
+            (1064, "42000")
+        }
+        Errors::DatabaseNotExisted => (1049, "42000"),
+        Errors::DatabaseExisted(_) => (1007, "HY000"),
+        Errors::DatabaseInUse(_) => (1205, "HY000"),
+        Errors::TableNotExisted(_) => (1146, "42S02"),
+        Errors::TableExisted(_) => (1050, "42S01"),
+        Errors::ColumnNotExisted(_) | Errors::InvalidOrderPosition(_) => (1054, "42S22"),
+        Errors::IndexNotExisted(_) => (1091, "42000"),
+        Errors::IndexExisted(_) => (1061, "42000"),
+        Errors::PrimaryKeyExisted(_) => (1062, "23000"),
+        Errors::WriteConflict(_) => (1213, "40001"),
+        Errors::DatabaseLocked(_) => (1205, "HY000"),
+        Errors::ColumnNotGrouped(_) => (1055, "42000"),
+        Errors::DivisionByZero => (1365, "22012"),
+        Errors::NumericOverflow => (1690, "22003"),
+        Errors::FunctionNotExisted(_) => (1305, "42000"),
+        Errors::InvalidCast(_) => (1292, "22007"),
+        Errors::InvalidSubquery(_) => (1242, "21000"),
+        Errors::ColumnCountMismatch(_) => (1222, "21000"),
+        Errors::RecursionLimitExceeded(..) => (3636, "HY000"),
+        Errors::InvalidDatabaseName(_) => (1102, "42000"),
+        Errors::NullValue(_) => (1048, "23000"),
+        Errors::AmbiguousColumn(_) => (1052, "23000"),
+        Errors::RowTooLarge(_) => (1118, "42000"),
+        Errors::UnimplementedOperation => (1235, "42000"),
+        _ => (1105, "HY000"),
+    }
+}
+
+// 20 printable bytes, the clear text plugin never uses them but clients expect them
+fn scramble(id: u32) -> Vec<u8> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let mut hasher = Sha256::new();
+    hasher.update(nanos.to_le_bytes());
+    hasher.update(id.to_le_bytes());
+    hasher.finalize()[..20]
+        .iter()
+        .map(|b| b'!' + b % 94)
+        .collect::<Vec<u8>>()
+}
+
+fn put_lenenc_int(buf: &mut Vec<u8>, n: u64) {
+    if n < 251 {
+        buf.push(n as u8);
+    } else if n < 1 << 16 {
+        buf.push(0xfc);
+        buf.extend_from_slice(&(n as u16).to_le_bytes());
+    } else if n < 1 << 24 {
+        buf.push(0xfd);
+        buf.extend_from_slice(&(n as u32).to_le_bytes()[..3]);
+    } else {
+        buf.push(0xfe);
+        buf.extend_from_slice(&n.to_le_bytes());
+    }
+}
+
+fn put_lenenc_str(buf: &mut Vec<u8>, s: &[u8]) {
+    put_lenenc_int(buf, s.len() as u64);
+    buf.extend_from_slice(s);
+}
+
+fn take_lenenc_int(reader: &mut Reader) -> io::Result<u64> {
+    let first = reader.take(1)?[0];
+    let len = match first {
+        0xfc => 2,
+        0xfd => 3,
+        0xfe => 8,
+        _ => return Ok(first as u64),
+    };
+    let mut bytes = [0u8; 8];
+    bytes[..len].copy_from_slice(reader.take(len)?);
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[test]
+fn test_mysql_handshake_and_query() {
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for (id, stream) in listener.incoming().enumerate() {
+            let _ = handle(stream.unwrap(), id as u32);
+        }
+    });
+    let read = |stream: &mut TcpStream| {
+        let mut header = [0u8; 4];
+        stream.read_exact(&mut header).unwrap();
+        let mut payload =
+            vec![0u8; u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize];
+        stream.read_exact(&mut payload).unwrap();
+        payload
+    };
+    let write = |stream: &mut TcpStream, seq: u8, payload: &[u8]| {
+        let mut buf = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+        buf.push(seq);
+        buf.extend_from_slice(payload);
+        stream.write_all(&buf).unwrap();
+    };
+    let login = |password: &str| {
+        let mut buf = (CLIENT_PROTOCOL_41 | CLIENT_SECURE_CONNECTION | CLIENT_PLUGIN_AUTH)
+            .to_le_bytes()
+            .to_vec();
+        buf.extend_from_slice(&[0; 4 + 1 + 23]);
+        buf.extend_from_slice(b"root\0");
+        buf.push(password.len() as u8 + 1);
+        buf.extend_from_slice(password.as_bytes());
+        buf.push(0);
+        buf.extend_from_slice(b"mysql_clear_password\0");
+        buf
+    };
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let handshake = read(&mut stream);
+    assert_eq!(handshake[0], 0x0a);
+    write(&mut stream, 1, &login("wrong"));
+    assert_eq!(read(&mut stream)[0], 0xff);
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    read(&mut stream);
+    write(&mut stream, 1, &login("123456"));
+    assert_eq!(read(&mut stream)[0], 0x00);
+    write(&mut stream, 0, b"\x03SELECT @@version_comment LIMIT 1");
+    assert_eq!(read(&mut stream), vec![1]);
+    let column = read(&mut stream);
+    assert!(column.ends_with(&[0xfd, 0, 0, 0, 0, 0]));
+    assert_eq!(read(&mut stream)[0], 0xfe);
+    assert_eq!(read(&mut stream), b"\x08SimpleDB".to_vec());
+    assert_eq!(read(&mut stream)[0], 0xfe);
+    write(&mut stream, 0, b"\x03SELECT * FROM missing");
+    let err = read(&mut stream);
+    assert_eq!(err[0], 0xff);
+    assert_eq!(u16::from_le_bytes([err[1], err[2]]), 1049);
+    write(&mut stream, 0, b"\x01");
+}
+
+#[test]
+fn test_variables() {
+    let session = Session::new();
+    let result = variables(
+        &session,
+        "SELECT @@version_comment AS İİİ, @@AUTOCOMMIT as Ⱥ LIMIT 1",
+    );
+    assert_eq!(result.columns, vec!["İİİ", "Ⱥ"]);
+    assert_eq!(
+        result.rows,
+        vec![vec![Value::Str("SimpleDB".to_string()), Value::Int(1)]]
+    );
+    let result = variables(&session, "SELECT @@session.tx_isolation, 'Ⱥ' Limit 1");
+    assert_eq!(result.columns, vec!["@@session.tx_isolation", "'Ⱥ'"]);
+    assert_eq!(result.rows[0][1], Value::Null);
+}
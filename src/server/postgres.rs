@@ -0,0 +1,969 @@
+use crate::database::base::{DataType, Value};
+use crate::database::mvcc::IsolationLevel;
+use crate::database::result::{Output, ResultSet};
+use crate::database::session::Session;
+use crate::parser::utils::parse_sql;
+use crate::server::{names_result, split_statements, take_cstr};
+use crate::storage::record::Reader;
+use crate::system::commands::parser::execute;
+use crate::system::commands::system::{self as SC, database_names, database_statement};
+use crate::system::dbs::DbSystem;
+use crate::system::errors::Errors;
+use sqlparser::ast::{self, Expr, Statement};
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+const SERVER_VERSION: &str = "14.0 (SimpleDB)";
+const PROTOCOL_V3: u32 = 196608;
+const SSL_REQUEST: u32 = 80877103;
+const GSSENC_REQUEST: u32 = 80877104;
+const CANCEL_REQUEST: u32 = 80877102;
+
+// type oids of pg_type
+const BOOL_OID: u32 = 16;
+const INT8_OID: u32 = 20;
+const INT2_OID: u32 = 21;
+const INT4_OID: u32 = 23;
+const TEXT_OID: u32 = 25;
+const FLOAT4_OID: u32 = 700;
+const FLOAT8_OID: u32 = 701;
+const NUMERIC_OID: u32 = 1700;
+
+/// A statement prepared by `Parse`.
+struct Prepared {
+    sql: String,
+    param_types: Vec<u32>,
+}
+
+/// A statement bound by `Bind`. It runs when it is described or executed first, the
+/// rows left by an `Execute` with a row limit are returned by the next one.
+struct Portal {
+    sql: String,
+    output: Option<Output>,
+}
+
+/// An error reported to the client, with its SQLSTATE code.
+struct PgError {
+    code: &'static str,
+    message: String,
+}
+
+impl From<Errors> for PgError {
+    fn from(err: Errors) -> Self {
+        PgError {
+            code: sql_state(&err),
+            message: err.to_string().trim_end().to_string(),
+        }
+    }
+}
+
+/// One client connection. Every message but the startup one is a type byte and a
+/// length that counts itself.
+struct Connection {
+    stream: TcpStream,
+    out: Vec<u8>,
+    session: Session,
+    statements: HashMap<String, Prepared>,
+    portals: HashMap<String, Portal>,
+}
+
+impl Connection {
+    fn read_message(&mut self) -> io::Result<Option<(u8, Vec<u8>)>> {
+        let mut kind = [0u8; 1];
+        match self.stream.read_exact(&mut kind) {
+            Ok(_) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        let body = self.read_body()?;
+        Ok(Some((kind[0], body)))
+    }
+
+    fn read_body(&mut self) -> io::Result<Vec<u8>> {
+        let mut len = [0u8; 4];
+        self.stream.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len < 4 {
+            return Err(io::Error::new(ErrorKind::InvalidData, "bad message length"));
+        }
+        let mut body = vec![0u8; len - 4];
+        self.stream.read_exact(&mut body)?;
+        Ok(body)
+    }
+
+    fn send(&mut self, kind: u8, body: &[u8]) {
+        self.out.push(kind);
+        self.out
+            .extend_from_slice(&(body.len() as u32 + 4).to_be_bytes());
+        self.out.extend_from_slice(body);
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.write_all(&self.out)?;
+        self.out.clear();
+        self.stream.flush()
+    }
+
+    fn send_ready(&mut self) {
+        let status = if self.session.in_transaction() {
+            b'T'
+        } else {
+            b'I'
+        };
+        self.send(b'Z', &[status]);
+    }
+
+    fn send_error(&mut self, severity: &str, err: &PgError) {
+        let mut buf = vec![];
+        for (field, value) in [
+            (b'S', severity),
+            (b'V', severity),
+            (b'C', err.code),
+            (b'M', err.message.as_str()),
+        ] {
+            buf.push(field);
+            put_cstr(&mut buf, value);
+        }
+        buf.push(0);
+        self.send(b'E', &buf);
+    }
+
+    fn send_row_description(&mut self, columns: &[String], types: &[DataType]) {
+        let mut buf = (columns.len() as u16).to_be_bytes().to_vec();
+        for (name, datatype) in columns.iter().zip(types) {
+            let (oid, len) = column_type(datatype);
+            put_cstr(&mut buf, name);
+            buf.extend_from_slice(&0u32.to_be_bytes());
+            buf.extend_from_slice(&0u16.to_be_bytes());
+            buf.extend_from_slice(&oid.to_be_bytes());
+            buf.extend_from_slice(&len.to_be_bytes());
+            buf.extend_from_slice(&(-1i32).to_be_bytes());
+            buf.extend_from_slice(&0u16.to_be_bytes());
+        }
+        self.send(b'T', &buf);
+    }
+
+    fn send_rows(&mut self, rows: &[Vec<Value>]) {
+        for row in rows {
+            let mut buf = (row.len() as u16).to_be_bytes().to_vec();
+            for val in row {
+                let text = match val {
+                    Value::Null => {
+                        buf.extend_from_slice(&(-1i32).to_be_bytes());
+                        continue;
+                    }
+                    Value::Bool(v) => (if *v { "t" } else { "f" }).to_string(),
+                    _ => val.to_string(),
+                };
+                buf.extend_from_slice(&(text.len() as u32).to_be_bytes());
+                buf.extend_from_slice(text.as_bytes());
+            }
+            self.send(b'D', &buf);
+        }
+    }
+
+    fn send_complete(&mut self, tag: &str) {
+        let mut buf = vec![];
+        put_cstr(&mut buf, tag);
+        self.send(b'C', &buf);
+    }
+
+    // the statements of a `Query` message run one after another until one fails
+    fn simple_query(&mut self, sql: &str) {
+        let statements = split_statements(sql);
+        if statements.is_empty() {
+            self.send(b'I', &[]);
+        }
+        for sql in statements {
+            match query(&mut self.session, sql) {
+                Ok(Output::Rows(result)) => {
+                    self.send_row_description(&result.columns, &result.types);
+                    self.send_rows(&result.rows);
+                    self.send_complete(&format!("SELECT {}", result.rows.len()));
+                }
+                Ok(output) => self.send_complete(&command_tag(sql, &output)),
+                Err(err) => {
+                    self.send_error("ERROR", &err);
+                    break;
+                }
+            }
+        }
+        self.send_ready();
+    }
+
+    // one message of the extended query protocol, an error skips the rest up to `Sync`
+    fn extended(&mut self, kind: u8, body: &[u8]) -> Result<(), PgError> {
+        let mut reader = Reader {
+            bytes: body,
+            pos: 0,
+        };
+        match kind {
+            b'P' => {
+                let name = take_cstr(&mut reader)?;
+                let sql = take_cstr(&mut reader)?;
+                let count = take_u16(&mut reader)?;
+                let mut param_types = vec![];
+                for _ in 0..count {
+                    param_types.push(take_u32(&mut reader)?);
+                }
+                let sql = sql.trim().trim_end_matches(';').to_string();
+                self.statements.insert(name, Prepared { sql, param_types });
+                self.send(b'1', &[]);
+            }
+            b'B' => {
+                let portal = take_cstr(&mut reader)?;
+                let name = take_cstr(&mut reader)?;
+                let prepared = self.statements.get(&name).ok_or_else(|| {
+                    PgError::new(
+                        "26000",
+                        format!("prepared statement \"{}\" does not exist", name),
+                    )
+                })?;
+                let formats = take_formats(&mut reader)?;
+                let count = take_u16(&mut reader)? as usize;
+                let mut params = vec![];
+                for ix in 0..count {
+                    let len = take_u32(&mut reader)? as i32;
+                    if len < 0 {
+                        params.push(None);
+                        continue;
+                    }
+                    let bytes = reader.take(len as usize)?;
+                    let binary = formats.get(ix).or(formats.first()).copied() == Some(1);
+                    let oid = prepared.param_types.get(ix).copied().unwrap_or(0);
+                    params.push(Some(param_text(bytes, binary, oid)?));
+                }
+                if take_formats(&mut reader)?.contains(&1) {
+                    return Err(PgError::new(
+                        "0A000",
+                        "binary result format is not supported",
+                    ));
+                }
+                let sql = bind_params(&prepared.sql, &params, &prepared.param_types);
+                self.portals.insert(portal, Portal { sql, output: None });
+                self.send(b'2', &[]);
+            }
+            b'D' => {
+                let target = reader.take(1)?[0];
+                let name = take_cstr(&mut reader)?;
+                if target == b'S' {
+                    self.describe_statement(&name)?;
+                } else {
+                    match self.run_portal(&name)? {
+                        Output::Rows(result) => {
+                            let (columns, types) = (result.columns.clone(), result.types.clone());
+                            self.send_row_description(&columns, &types);
+                        }
+                        _ => self.send(b'n', &[]),
+                    }
+                }
+            }
+            b'E' => {
+                let name = take_cstr(&mut reader)?;
+                let max_rows = take_u32(&mut reader)? as usize;
+                self.run_portal(&name)?;
+                let portal = self.portals.get_mut(&name).unwrap();
+                match portal.output.take().unwrap_or(Output::Done) {
+                    Output::Rows(mut result) if max_rows > 0 && result.rows.len() > max_rows => {
+                        let rest = result.rows.split_off(max_rows);
+                        let rows = std::mem::replace(&mut result.rows, rest);
+                        portal.output = Some(Output::Rows(result));
+                        self.send_rows(&rows);
+                        self.send(b's', &[]);
+                    }
+                    Output::Rows(result) => {
+                        self.send_rows(&result.rows);
+                        self.send_complete(&format!("SELECT {}", result.rows.len()));
+                    }
+                    output => {
+                        let tag = command_tag(&portal.sql, &output);
+                        self.send_complete(&tag);
+                    }
+                }
+            }
+            b'C' => {
+                let target = reader.take(1)?[0];
+                let name = take_cstr(&mut reader)?;
+                if target == b'S' {
+                    self.statements.remove(&name);
+                } else {
+                    self.portals.remove(&name);
+                }
+                self.send(b'3', &[]);
+            }
+            _ => {
+                let message = format!("unsupported message type '{}'", kind as char);
+                return Err(PgError::new("08P01", message));
+            }
+        }
+        Ok(())
+    }
+
+    // a statement is only run on execution, a query is run without its parameters to
+    // find the columns it returns
+    fn describe_statement(&mut self, name: &str) -> Result<(), PgError> {
+        let prepared = self.statements.get(name).ok_or_else(|| {
+            PgError::new(
+                "26000",
+                format!("prepared statement \"{}\" does not exist", name),
+            )
+        })?;
+        let count = param_count(&prepared.sql).max(prepared.param_types.len());
+        let mut buf = (count as u16).to_be_bytes().to_vec();
+        for ix in 0..count {
+            let oid = match prepared.param_types.get(ix) {
+                Some(oid) if *oid != 0 => *oid,
+                _ => TEXT_OID,
+            };
+            buf.extend_from_slice(&oid.to_be_bytes());
+        }
+        let sql = describe_query(&prepared.sql, &prepared.param_types);
+        self.send(b't', &buf);
+        let sql = match sql {
+            Some(v) => v,
+            None => {
+                self.send(b'n', &[]);
+                return Ok(());
+            }
+        };
+        match query(&mut self.session, &sql)? {
+            Output::Rows(result) => self.send_row_description(&result.columns, &result.types),
+            _ => self.send(b'n', &[]),
+        }
+        Ok(())
+    }
+
+    fn run_portal(&mut self, name: &str) -> Result<&Output, PgError> {
+        let portal = self
+            .portals
+            .get_mut(name)
+            .ok_or_else(|| PgError::new("34000", format!("portal \"{}\" does not exist", name)))?;
+        if portal.output.is_none() {
+            portal.output = Some(query(&mut self.session, &portal.sql)?);
+        }
+        Ok(portal.output.as_ref().unwrap())
+    }
+}
+
+impl PgError {
+    fn new(code: &'static str, message: impl Into<String>) -> PgError {
+        PgError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<io::Error> for PgError {
+    fn from(_: io::Error) -> Self {
+        PgError::new("08P01", "invalid message format")
+    }
+}
+
+/// Serves one client: startup and login, then messages until it terminates.
+pub fn handle(stream: TcpStream, id: u32) -> io::Result<()> {
+    let mut conn = Connection {
+        stream,
+        out: vec![],
+        session: Session::new(),
+        statements: HashMap::new(),
+        portals: HashMap::new(),
+    };
+    if !startup(&mut conn, id)? {
+        return Ok(());
+    }
+    // set by an error of the extended protocol, cleared by `Sync`
+    let mut failed = false;
+    while let Some((kind, body)) = conn.read_message()? {
+        match kind {
+            b'X' => break,
+            b'Q' => {
+                conn.portals.remove("");
+                conn.statements.remove("");
+                let sql = String::from_utf8_lossy(&body);
+                let sql = sql.trim_end_matches('\0').to_string();
+                conn.simple_query(&sql);
+                failed = false;
+            }
+            b'S' => {
+                conn.portals.clear();
+                conn.send_ready();
+                failed = false;
+            }
+            b'H' => {}
+            _ if failed => continue,
+            _ => {
+                if let Err(err) = conn.extended(kind, &body) {
+                    conn.send_error("ERROR", &err);
+                    failed = true;
+                }
+                // replies of the extended protocol wait for `Sync` or `Flush`
+                continue;
+            }
+        }
+        conn.flush()?;
+    }
+    Ok(())
+}
+
+// Reads the startup packet, asks for the password in clear text and checks it with
+// `DbSystem`. SSL and GSS encryption are declined.
+fn startup(conn: &mut Connection, id: u32) -> io::Result<bool> {
+    let body = loop {
+        let body = conn.read_body()?;
+        if body.len() < 4 {
+            return Ok(false);
+        }
+        let code = u32::from_be_bytes([body[0], body[1], body[2], body[3]]);
+        match code {
+            SSL_REQUEST | GSSENC_REQUEST => {
+                conn.stream.write_all(b"N")?;
+            }
+            PROTOCOL_V3 => break body,
+            CANCEL_REQUEST => return Ok(false),
+            _ => {
+                let message = format!(
+                    "unsupported frontend protocol {}.{}",
+                    code >> 16,
+                    code & 0xffff
+                );
+                conn.send_error("FATAL", &PgError::new("0A000", message));
+                conn.flush()?;
+                return Ok(false);
+            }
+        }
+    };
+    let mut reader = Reader {
+        bytes: &body,
+        pos: 4,
+    };
+    let mut params = HashMap::new();
+    while reader.pos < body.len() && body[reader.pos] != 0 {
+        let key = take_cstr(&mut reader)?;
+        let value = take_cstr(&mut reader)?;
+        params.insert(key, value);
+    }
+    let user = params.get("user").cloned().unwrap_or_default();
+
+    conn.send(b'R', &3u32.to_be_bytes());
+    conn.flush()?;
+    let password = match conn.read_message()? {
+        Some((b'p', body)) => String::from_utf8_lossy(&body)
+            .trim_end_matches('\0')
+            .to_string(),
+        _ => return Ok(false),
+    };
+    if !DbSystem::new().login(user.to_string(), password) {
+        let message = format!("password authentication failed for user \"{}\"", user);
+        conn.send_error("FATAL", &PgError::new("28P01", message));
+        conn.flush()?;
+        return Ok(false);
+    }
+    if let Some(db_name) = params.get("database").filter(|name| !name.is_empty()) {
+        let command = format!("sys usedb {}", db_name);
+        if let Err(err) = SC::use_db(command, &mut conn.session) {
+            conn.send_error("FATAL", &PgError::from(err));
+            conn.flush()?;
+            return Ok(false);
+        }
+    }
+    conn.send(b'R', &0u32.to_be_bytes());
+    for (key, value) in [
+        ("server_version", SERVER_VERSION),
+        ("server_encoding", "UTF8"),
+        ("client_encoding", "UTF8"),
+        ("DateStyle", "ISO, MDY"),
+        ("integer_datetimes", "on"),
+        ("standard_conforming_strings", "on"),
+    ] {
+        let mut buf = vec![];
+        put_cstr(&mut buf, key);
+        put_cstr(&mut buf, value);
+        conn.send(b'S', &buf);
+    }
+    let mut buf = id.to_be_bytes().to_vec();
+    buf.extend_from_slice(&0u32.to_be_bytes());
+    conn.send(b'K', &buf);
+    conn.send_ready();
+    conn.flush()?;
+    Ok(true)
+}
+
+/// Runs one statement. Besides the statements of the engine it answers what drivers
+/// send on their own: `SET` of client settings, `SHOW` of a setting and functions like
+/// `version()` and `current_database()`.
+fn query(session: &mut Session, sql: &str) -> Result<Output, PgError> {
+    let lower = sql.to_lowercase();
+    let words = lower.split_whitespace().collect::<Vec<&str>>();
+    let output = match words.as_slice() {
+        ["select", ..] if !words.contains(&"from") && lower.contains("()") => {
+            Output::Rows(functions(session, sql))
+        }
+        ["set", "transaction", ..] | ["set", "session", "characteristics", ..] => {
+            execute(sql.to_string(), session)?
+        }
+        ["set", ..] => Output::Done,
+        ["show", "databases"] => {
+            let names = database_names().map_err(|_| Errors::FileSystemError)?;
+            Output::Rows(names_result("datname", names))
+        }
+        ["show", name] => Output::Rows(setting(session, name)?),
+        _ => match database_statement(sql, session) {
+            Some(result) => result.map(|_| Output::Done)?,
+            None => execute(sql.to_string(), session)?,
+        },
+    };
+    match output {
+        Output::Schema { columns, .. } => Ok(Output::Rows(columns)),
+        output => Ok(output),
+    }
+}
+
+// `SELECT version()`, `SELECT current_database()` and the like
+fn functions(session: &Session, sql: &str) -> ResultSet {
+    let mut columns = vec![];
+    let mut row = vec![];
+    for item in sql[6..].split(',').map(|item| item.trim()) {
+        let (expr, name) = match item.to_lowercase().find(" as ") {
+            Some(ix) => (
+                item[..ix].trim().to_lowercase(),
+                item[ix + 4..].trim().to_string(),
+            ),
+            None => {
+                let expr = item.to_lowercase();
+                let name = expr.trim_end_matches("()").to_string();
+                (expr, name)
+            }
+        };
+        let val = match expr.as_str() {
+            "version()" => Value::Str(format!("PostgreSQL {}", SERVER_VERSION)),
+            "current_database()" if !session.db_name().is_empty() => {
+                Value::Str(session.db_name().to_string())
+            }
+            "current_schema()" => Value::Str("public".to_string()),
+            _ => Value::Null,
+        };
+        columns.push(name);
+        row.push(val);
+    }
+    ResultSet {
+        types: vec![DataType::String; columns.len()],
+        columns,
+        rows: vec![row],
+    }
+}
+
+fn setting(session: &Session, name: &str) -> Result<ResultSet, PgError> {
+    let value = match name {
+        "server_version" => SERVER_VERSION,
+        "server_encoding" | "client_encoding" => "UTF8",
+        "standard_conforming_strings" | "integer_datetimes" => "on",
+        "transaction_isolation" | "default_transaction_isolation" => match session.isolation {
+            IsolationLevel::ReadCommitted => "read committed",
+            IsolationLevel::Snapshot => "repeatable read",
+        },
+        _ => {
+            let message = format!("unrecognized configuration parameter \"{}\"", name);
+            return Err(PgError::new("42704", message));
+        }
+    };
+    Ok(names_result(name, vec![value.to_string()]))
+}
+
+// the tag of a `CommandComplete` for a statement that returns no rows
+fn command_tag(sql: &str, output: &Output) -> String {
+    let lower = sql.to_lowercase();
+    let words = lower.split_whitespace().collect::<Vec<&str>>();
+    let affected = match output {
+        Output::Affected(affected) => *affected,
+        _ => 0,
+    };
+    match words.as_slice() {
+        ["insert", ..] => format!("INSERT 0 {}", affected),
+        ["update", ..] => format!("UPDATE {}", affected),
+        ["delete", ..] => format!("DELETE {}", affected),
+        ["create" | "drop", "unique", "index", ..] => format!("{} INDEX", words[0].to_uppercase()),
+        ["create" | "drop", object, ..] => format!("{} {}", words[0], object).to_uppercase(),
+        ["rollback", "to", ..] => "ROLLBACK".to_string(),
+        ["start", ..] => "START TRANSACTION".to_string(),
+        [first, ..] => first.to_uppercase(),
+        [] => "".to_string(),
+    }
+}
+
+// type oid and size of a column in a row description, -1 for variable length
+fn column_type(datatype: &DataType) -> (u32, i16) {
+    match datatype {
+        DataType::Int => (INT4_OID, 4),
+        DataType::Float => (FLOAT4_OID, 4),
+        DataType::Bool => (BOOL_OID, 1),
+        DataType::String | DataType::Invalid => (TEXT_OID, -1),
+    }
+}
+
+// SQLSTATE reported for an engine error
+fn sql_state(err: &Errors) -> &'static str {
+    match err {
+        Errors::ParseSQLError | Errors::InvalidExpression | Errors::InvalidCommand => "42601",
+        Errors::DatabaseNotExisted => "3D000",
+        Errors::DatabaseExisted(_) => "42P04",
+        Errors::DatabaseInUse(_) => "55006",
+        Errors::TableNotExisted(_) => "42P01",
+        Errors::TableExisted(_) => "42P07",
+        Errors::ColumnNotExisted(_) => "42703",
+        Errors::IndexNotExisted(_) => "42704",
+        Errors::IndexExisted(_) => "42P07",
+        Errors::IndexRequired(_) => "2BP01",
+        Errors::PrimaryKeyExisted(_) => "23505",
+        Errors::InvalidColumnType => "42804",
+        Errors::TransactionStarted => "25001",
+        Errors::TransactionNotStarted => "25P01",
+        Errors::SavepointNotExisted(_) => "3B001",
+        Errors::WriteConflict(_) => "40001",
+        Errors::SchemaChangeInTransaction => "25001",
+        Errors::DatabaseLocked(_) => "55P03",
+        Errors::InvalidOrderPosition(_) => "42P10",
+        Errors::ColumnNotGrouped(_) => "42803",
+        Errors::TypeMismatch(_) => "42883",
+        Errors::DivisionByZero => "22012",
+        Errors::NumericOverflow => "22003",
+        Errors::FunctionNotExisted(_) => "42883",
+        Errors::InvalidCast(_) => "22P02",
+        Errors::InvalidSubquery(_) => "21000",
+        Errors::ColumnCountMismatch(_) => "42601",
+        Errors::RecursionLimitExceeded(..) => "54001",
+        Errors::InvalidDatabaseName(_) => "42602",
+        Errors::NullValue(_) => "23502",
+        Errors::AmbiguousColumn(_) => "42702",
+        Errors::RowTooLarge(_) => "54000",
+        Errors::UnimplementedOperation => "0A000",
+        _ => "XX000",
+    }
+}
+
+// calls `f` with the number of every `$n` placeholder outside of quotes and the range it
+// takes in `sql`
+fn placeholders(sql: &str, mut f: impl FnMut(usize, usize, usize)) {
+    let bytes = sql.as_bytes();
+    let mut quote = None;
+    let mut ix = 0;
+    while ix < bytes.len() {
+        let c = bytes[ix];
+        match quote {
+            Some(q) if q == c => quote = None,
+            Some(_) => {}
+            None if c == b'\'' || c == b'"' => quote = Some(c),
+            None if c == b'$' => {
+                let end = ix
+                    + 1
+                    + bytes[ix + 1..]
+                        .iter()
+                        .take_while(|b| b.is_ascii_digit())
+                        .count();
+                if let Ok(n) = sql[ix + 1..end].parse::<usize>() {
+                    f(n, ix, end);
+                    ix = end;
+                    continue;
+                }
+            }
+            None => {}
+        }
+        ix += 1;
+    }
+}
+
+// a query is described by running it with every parameter a NULL of its type, which
+// changes its rows but not its columns, and without reading any of its rows
+fn describe_query(sql: &str, types: &[u32]) -> Option<String> {
+    let mut nulls = String::new();
+    let mut last = 0;
+    placeholders(sql, |n, start, end| {
+        nulls.push_str(&sql[last..start]);
+        let datatype = match types.get(n.wrapping_sub(1)).copied().unwrap_or(0) {
+            INT2_OID | INT4_OID | INT8_OID => Some("INT"),
+            FLOAT4_OID | FLOAT8_OID | NUMERIC_OID => Some("FLOAT"),
+            BOOL_OID => Some("BOOLEAN"),
+            TEXT_OID => Some("TEXT"),
+            _ => None,
+        };
+        match datatype {
+            Some(datatype) => nulls.push_str(&format!("CAST(NULL AS {})", datatype)),
+            None => nulls.push_str("NULL"),
+        }
+        last = end;
+    });
+    nulls.push_str(&sql[last..]);
+    let mut statement = parse_sql(&nulls).ok()?;
+    if let Statement::Query(query) = &mut statement {
+        query.order_by = vec![];
+        query.offset = None;
+        query.fetch = None;
+        query.limit = Some(Expr::Value(ast::Value::Number("0".to_string(), false)));
+        return Some(statement.to_string());
+    }
+    None
+}
+
+fn param_count(sql: &str) -> usize {
+    let mut count = 0;
+    placeholders(sql, |n, _, _| count = count.max(n));
+    count
+}
+
+/// Replaces the `$n` placeholders by the bound values as literals. Plain decimal numbers
+/// of a numeric or unspecified type are kept as they are, anything else becomes a
+/// quoted string.
+fn bind_params(sql: &str, params: &[Option<String>], types: &[u32]) -> String {
+    let mut bound = String::new();
+    let mut last = 0;
+    placeholders(sql, |n, start, end| {
+        bound.push_str(&sql[last..start]);
+        let ix = n.wrapping_sub(1);
+        let literal = match params.get(ix) {
+            Some(Some(text)) => {
+                let numeric = matches!(
+                    types.get(ix).copied().unwrap_or(0),
+                    0 | INT2_OID | INT4_OID | INT8_OID | FLOAT4_OID | FLOAT8_OID | NUMERIC_OID
+                );
+                if numeric && is_decimal(text) {
+                    text.to_string()
+                } else if types.get(ix) == Some(&BOOL_OID) && (text == "t" || text == "f") {
+                    (text == "t").to_string()
+                } else {
+                    format!("'{}'", text.replace('\'', "''"))
+                }
+            }
+            _ => "NULL".to_string(),
+        };
+        bound.push_str(&literal);
+        last = end;
+    });
+    bound.push_str(&sql[last..]);
+    bound
+}
+
+// `-?[0-9]+(.[0-9]+)?`, text like `NaN`, `inf` or `1e5` would read as a column or
+// something else than the number inlined
+fn is_decimal(text: &str) -> bool {
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let unsigned = text.strip_prefix('-').unwrap_or(text);
+    match unsigned.split_once('.') {
+        Some((int, frac)) => digits(int) && digits(frac),
+        None => digits(unsigned),
+    }
+}
+
+// the text of a parameter sent in binary, only the fixed size numbers are understood
+fn param_text(bytes: &[u8], binary: bool, oid: u32) -> Result<String, PgError> {
+    if !binary {
+        return Ok(String::from_utf8_lossy(bytes).to_string());
+    }
+    let text = match (oid, bytes.len()) {
+        (BOOL_OID, 1) => (if bytes[0] != 0 { "t" } else { "f" }).to_string(),
+        (INT2_OID, 2) => i16::from_be_bytes(bytes.try_into().unwrap()).to_string(),
+        (INT4_OID, 4) => i32::from_be_bytes(bytes.try_into().unwrap()).to_string(),
+        (INT8_OID, 8) => i64::from_be_bytes(bytes.try_into().unwrap()).to_string(),
+        (FLOAT4_OID, 4) => f32::from_be_bytes(bytes.try_into().unwrap()).to_string(),
+        (FLOAT8_OID, 8) => f64::from_be_bytes(bytes.try_into().unwrap()).to_string(),
+        (TEXT_OID, _) => String::from_utf8_lossy(bytes).to_string(),
+        _ => {
+            return Err(PgError::new(
+                "0A000",
+                "binary parameter format is not supported",
+            ))
+        }
+    };
+    Ok(text)
+}
+
+fn put_cstr(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(s.as_bytes());
+    buf.push(0);
+}
+
+fn take_u16(reader: &mut Reader) -> io::Result<u16> {
+    Ok(u16::from_be_bytes(reader.take(2)?.try_into().unwrap()))
+}
+
+fn take_u32(reader: &mut Reader) -> io::Result<u32> {
+    Ok(u32::from_be_bytes(reader.take(4)?.try_into().unwrap()))
+}
+
+fn take_formats(reader: &mut Reader) -> io::Result<Vec<u16>> {
+    let count = take_u16(reader)?;
+    (0..count).map(|_| take_u16(reader)).collect()
+}
+
+#[test]
+fn test_postgres_startup_and_queries() {
+    use crate::system::utils::TempDb;
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for (id, stream) in listener.incoming().enumerate() {
+            let _ = handle(stream.unwrap(), id as u32);
+        }
+    });
+    let read = |stream: &mut TcpStream| {
+        let mut header = [0u8; 5];
+        stream.read_exact(&mut header).unwrap();
+        let len = u32::from_be_bytes(header[1..].try_into().unwrap()) as usize;
+        let mut body = vec![0u8; len - 4];
+        stream.read_exact(&mut body).unwrap();
+        (header[0], body)
+    };
+    let write = |stream: &mut TcpStream, kind: u8, body: &[u8]| {
+        let mut buf = vec![kind];
+        buf.extend_from_slice(&(body.len() as u32 + 4).to_be_bytes());
+        buf.extend_from_slice(body);
+        stream.write_all(&buf).unwrap();
+    };
+    let connect = |password: &str| {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let mut body = PROTOCOL_V3.to_be_bytes().to_vec();
+        body.extend_from_slice(b"user\0root\0\0");
+        let mut buf = (body.len() as u32 + 4).to_be_bytes().to_vec();
+        buf.extend_from_slice(&body);
+        stream.write_all(&buf).unwrap();
+        assert_eq!(read(&mut stream), (b'R', 3u32.to_be_bytes().to_vec()));
+        write(&mut stream, b'p', format!("{}\0", password).as_bytes());
+        stream
+    };
+
+    let mut stream = connect("wrong");
+    let (kind, body) = read(&mut stream);
+    assert_eq!(kind, b'E');
+    assert!(String::from_utf8_lossy(&body).contains("28P01"));
+
+    let mut stream = connect("123456");
+    assert_eq!(read(&mut stream).0, b'R');
+    let mut kind = 0;
+    while kind != b'Z' {
+        kind = read(&mut stream).0;
+    }
+    write(
+        &mut stream,
+        b'Q',
+        b"SELECT version(); SHOW server_version\0",
+    );
+    for expected in [b'T', b'D', b'C', b'T', b'D', b'C', b'Z'] {
+        assert_eq!(read(&mut stream).0, expected);
+    }
+
+    // extended protocol, an error skips the messages up to Sync
+    write(&mut stream, b'P', b"\0SELECT * FROM t WHERE id = $1\0\0\0");
+    write(&mut stream, b'B', b"\0\0\0\0\0\x01\0\0\0\x011\0\0");
+    write(&mut stream, b'E', b"\0\0\0\0\0");
+    write(&mut stream, b'S', b"");
+    assert_eq!(read(&mut stream).0, b'1');
+    assert_eq!(read(&mut stream).0, b'2');
+    let (kind, body) = read(&mut stream);
+    assert_eq!(kind, b'E');
+    assert!(String::from_utf8_lossy(&body).contains("3D000"));
+    assert_eq!(read(&mut stream), (b'Z', b"I".to_vec()));
+
+    let temp = TempDb::new("postgres");
+    let mut conn = crate::connection::Connection::open(&temp.name).unwrap();
+    conn.execute("CREATE TABLE t (id INT PRIMARY KEY, amount FLOAT)")
+        .unwrap();
+    conn.execute("INSERT INTO t (id, amount) VALUES (1, 1.5), (2, 3.5), (3, 5.5)")
+        .unwrap();
+    drop(conn);
+    write(&mut stream, b'Q', format!("USE {}\0", temp.name).as_bytes());
+    assert_eq!(read(&mut stream).0, b'C');
+    assert_eq!(read(&mut stream).0, b'Z');
+
+    // the statement is described with the columns its portal returns, whatever the
+    // parameter is used for
+    let mut describe = |sql: &str, oid: u32, param: &str| {
+        let mut body = format!("\0{}\0", sql).into_bytes();
+        body.extend_from_slice(&1u16.to_be_bytes());
+        body.extend_from_slice(&oid.to_be_bytes());
+        write(&mut stream, b'P', &body);
+        write(&mut stream, b'D', b"S\0");
+        let mut body = b"\0\0\0\0\0\x01".to_vec();
+        body.extend_from_slice(&(param.len() as u32).to_be_bytes());
+        body.extend_from_slice(param.as_bytes());
+        body.extend_from_slice(b"\0\0");
+        write(&mut stream, b'B', &body);
+        write(&mut stream, b'D', b"P\0");
+        write(&mut stream, b'E', b"\0\0\0\0\0");
+        write(&mut stream, b'S', b"");
+        assert_eq!(read(&mut stream).0, b'1');
+        assert_eq!(read(&mut stream).0, b't');
+        let statement = read(&mut stream);
+        assert_eq!(read(&mut stream).0, b'2');
+        let portal = read(&mut stream);
+        let mut rows = 0;
+        while read(&mut stream).0 == b'D' {
+            rows += 1;
+        }
+        assert_eq!(read(&mut stream).0, b'Z');
+        assert_eq!((statement.0, portal.0), (b'T', b'T'), "{}", sql);
+        (statement.1, portal.1, rows)
+    };
+    // the type of every column of a RowDescription
+    let types = |body: &[u8]| {
+        let mut reader = Reader {
+            bytes: body,
+            pos: 2,
+        };
+        let mut oids = vec![];
+        while reader.pos < body.len() {
+            take_cstr(&mut reader).unwrap();
+            let field = reader.take(18).unwrap();
+            oids.push(u32::from_be_bytes(field[6..10].try_into().unwrap()));
+        }
+        oids
+    };
+    let union = "SELECT id FROM t WHERE amount > $1 UNION SELECT id FROM t WHERE id = 1";
+    let having = "SELECT id, COUNT(*) FROM t GROUP BY id HAVING COUNT(*) > $1";
+    let cte = "WITH big AS (SELECT id, amount FROM t WHERE amount > $1) SELECT * FROM big";
+    for (sql, oid, param, rows) in [
+        ("SELECT id FROM t WHERE amount > $1", FLOAT8_OID, "2", 2),
+        (union, FLOAT8_OID, "4", 2),
+        (having, INT4_OID, "0", 3),
+        (cte, FLOAT8_OID, "3", 2),
+    ] {
+        let (statement, portal, count) = describe(sql, oid, param);
+        assert_eq!(statement, portal, "{}", sql);
+        assert_eq!(count, rows, "{}", sql);
+    }
+    // a parameter in the select list is named after its placeholder, not the value
+    let (statement, portal, rows) = describe("SELECT id, $1 FROM t", INT4_OID, "7");
+    assert_eq!(types(&statement), types(&portal));
+    assert_eq!(rows, 3);
+    write(&mut stream, b'X', b"");
+}
+
+#[test]
+fn test_bind_params() {
+    let sql = "SELECT * FROM t WHERE a = $1 AND b = '$2' AND c = $2 OR d IS $3";
+    let params = [Some("1".to_string()), Some("it's".to_string()), None];
+    assert_eq!(
+        bind_params(sql, &params, &[]),
+        "SELECT * FROM t WHERE a = 1 AND b = '$2' AND c = 'it''s' OR d IS NULL"
+    );
+    let values = ["-2.50", "NaN", "inf", "1e5", "1.", ".5", "--1", "0x10", "2"];
+    let params = values.map(|v| Some(v.to_string()));
+    assert_eq!(
+        bind_params("$1 $2 $3 $4 $5 $6 $7 $8 $9", &params, &[0, 0, FLOAT8_OID]),
+        "-2.50 'NaN' 'inf' '1e5' '1.' '.5' '--1' '0x10' 2"
+    );
+    assert_eq!(
+        describe_query(
+            "SELECT a, $2 FROM t WHERE b = $1 GROUP BY a ORDER BY a LIMIT 5",
+            &[INT4_OID]
+        )
+        .unwrap(),
+        "SELECT a, NULL FROM t WHERE b = CAST(NULL AS INT) GROUP BY a LIMIT 0"
+    );
+    assert_eq!(describe_query("INSERT INTO t (a) VALUES ($1)", &[]), None);
+    assert_eq!(
+        split_statements("SELECT ';'; BEGIN;"),
+        vec!["SELECT ';'", "BEGIN"]
+    );
+}
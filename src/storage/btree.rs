@@ -0,0 +1,339 @@
+use crate::database::base::Value;
+use crate::storage::page::{PageId, PageKind, NO_PAGE, PAGE_SIZE};
+use crate::storage::pager::Pager;
+use crate::storage::record::{encode_value, Reader};
+use std::cmp::Ordering;
+use std::io::{self, ErrorKind};
+use std::ops::Bound;
+
+/// Index entry: the column value and the row id of the row holding it. Adding the
+/// row id keeps every entry unique, so duplicate column values need no posting lists.
+pub type IndexKey = (Value, u64);
+
+// Node layout: kind u8 | next u32 | count u16 | body
+// leaf body:     key...
+// internal body: child u32 | (key, child u32)...
+// key:           value | rid u64
+const NODE_HEADER: usize = 7;
+const MAX_KEY: usize = PAGE_SIZE / 8;
+
+fn cmp_key(a: &IndexKey, b: &IndexKey) -> Ordering {
+    a.0.total_cmp(&b.0).then(a.1.cmp(&b.1))
+}
+
+fn key_len(key: &IndexKey) -> usize {
+    let mut buf: Vec<u8> = vec![];
+    encode_value(&mut buf, &key.0);
+    buf.len() + 8
+}
+
+struct Node {
+    leaf: bool,
+    next: PageId,
+    keys: Vec<IndexKey>,
+    children: Vec<PageId>,
+}
+
+impl Node {
+    fn read(pager: &mut Pager, id: PageId) -> io::Result<Node> {
+        let page = pager.read(id)?;
+        let leaf = page.data[0] == PageKind::BTreeLeaf as u8;
+        if !leaf && page.data[0] != PageKind::BTreeInternal as u8 {
+            return Err(io::Error::new(ErrorKind::InvalidData, "not a b-tree page"));
+        }
+        let next = page.next();
+        let count = page.read_u16(5) as usize;
+        let mut reader = Reader {
+            bytes: &page.data,
+            pos: NODE_HEADER,
+        };
+        let mut keys: Vec<IndexKey> = Vec::with_capacity(count);
+        let mut children: Vec<PageId> = vec![];
+        if !leaf {
+            children.push(u32::from_le_bytes(reader.take(4)?.try_into().unwrap()));
+        }
+        for _ in 0..count {
+            let value = reader.value()?;
+            let rid = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+            keys.push((value, rid));
+            if !leaf {
+                children.push(u32::from_le_bytes(reader.take(4)?.try_into().unwrap()));
+            }
+        }
+        Ok(Node {
+            leaf,
+            next,
+            keys,
+            children,
+        })
+    }
+
+    fn encoded_len(&self) -> usize {
+        let child_len = if self.leaf { 0 } else { 4 };
+        NODE_HEADER
+            + child_len
+            + self
+                .keys
+                .iter()
+                .map(|k| key_len(k) + child_len)
+                .sum::<usize>()
+    }
+
+    fn write(&self, pager: &mut Pager, id: PageId) -> io::Result<()> {
+        let mut body: Vec<u8> = vec![];
+        if !self.leaf {
+            body.extend_from_slice(&self.children[0].to_le_bytes());
+        }
+        for (ix, (value, rid)) in self.keys.iter().enumerate() {
+            encode_value(&mut body, value);
+            body.extend_from_slice(&rid.to_le_bytes());
+            if !self.leaf {
+                body.extend_from_slice(&self.children[ix + 1].to_le_bytes());
+            }
+        }
+        let page = pager.write(id)?;
+        page.data.fill(0);
+        page.data[0] = if self.leaf {
+            PageKind::BTreeLeaf as u8
+        } else {
+            PageKind::BTreeInternal as u8
+        };
+        page.set_next(self.next);
+        page.write_u16(5, self.keys.len() as u16);
+        page.data[NODE_HEADER..NODE_HEADER + body.len()].copy_from_slice(&body);
+        Ok(())
+    }
+
+    // Index of the child whose subtree may contain `key`: child i holds the keys
+    // below separator i, child i + 1 the keys from separator i on.
+    fn child_for(&self, key: &IndexKey) -> usize {
+        self.keys
+            .partition_point(|k| cmp_key(k, key) != Ordering::Greater)
+    }
+}
+
+/// B+tree stored in pages of the database file. Leaves are chained through their
+/// `next` pointer for range scans. Deletes do not rebalance, a leaf may become empty
+/// and is simply skipped by scans.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BTree {
+    pub root: PageId,
+}
+
+impl BTree {
+    pub fn create(pager: &mut Pager) -> io::Result<BTree> {
+        let root = pager.allocate()?;
+        Node {
+            leaf: true,
+            next: NO_PAGE,
+            keys: vec![],
+            children: vec![],
+        }
+        .write(pager, root)?;
+        Ok(BTree { root })
+    }
+
+    /// Inserts `key`, doing nothing when the exact entry is already present.
+    pub fn insert(&mut self, pager: &mut Pager, key: IndexKey) -> io::Result<()> {
+        if key_len(&key) > MAX_KEY {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "index key is too large",
+            ));
+        }
+        if let Some((sep, right)) = BTree::insert_at(pager, self.root, key)? {
+            let root = pager.allocate()?;
+            Node {
+                leaf: false,
+                next: NO_PAGE,
+                keys: vec![sep],
+                children: vec![self.root, right],
+            }
+            .write(pager, root)?;
+            self.root = root;
+        }
+        Ok(())
+    }
+
+    fn insert_at(
+        pager: &mut Pager,
+        id: PageId,
+        key: IndexKey,
+    ) -> io::Result<Option<(IndexKey, PageId)>> {
+        let mut node = Node::read(pager, id)?;
+        if node.leaf {
+            match node.keys.binary_search_by(|k| cmp_key(k, &key)) {
+                Ok(_) => return Ok(None),
+                Err(pos) => node.keys.insert(pos, key),
+            }
+        } else {
+            let child_ix = node.child_for(&key);
+            match BTree::insert_at(pager, node.children[child_ix], key)? {
+                Some((sep, right)) => {
+                    node.keys.insert(child_ix, sep);
+                    node.children.insert(child_ix + 1, right);
+                }
+                None => return Ok(None),
+            }
+        }
+        if node.encoded_len() <= PAGE_SIZE {
+            node.write(pager, id)?;
+            return Ok(None);
+        }
+        let mid = node.keys.len() / 2;
+        let right_id = pager.allocate()?;
+        let (sep, right) = if node.leaf {
+            let keys = node.keys.split_off(mid);
+            let right = Node {
+                leaf: true,
+                next: node.next,
+                keys,
+                children: vec![],
+            };
+            node.next = right_id;
+            (right.keys[0].clone(), right)
+        } else {
+            let keys = node.keys.split_off(mid + 1);
+            let children = node.children.split_off(mid + 1);
+            let sep = node.keys.pop().unwrap();
+            let right = Node {
+                leaf: false,
+                next: NO_PAGE,
+                keys,
+                children,
+            };
+            (sep, right)
+        };
+        node.write(pager, id)?;
+        right.write(pager, right_id)?;
+        Ok(Some((sep, right_id)))
+    }
+
+    /// Removes `key`, returning whether it was present.
+    pub fn delete(&mut self, pager: &mut Pager, key: &IndexKey) -> io::Result<bool> {
+        let mut id = self.root;
+        loop {
+            let mut node = Node::read(pager, id)?;
+            if !node.leaf {
+                id = node.children[node.child_for(key)];
+                continue;
+            }
+            return match node.keys.binary_search_by(|k| cmp_key(k, key)) {
+                Ok(pos) => {
+                    node.keys.remove(pos);
+                    node.write(pager, id)?;
+                    Ok(true)
+                }
+                Err(_) => Ok(false),
+            };
+        }
+    }
+
+    /// Row ids of the entries whose value lies between `lo` and `hi`, in key order.
+    /// Null entries are only returned when a bound explicitly includes null.
+    pub fn range(
+        &self,
+        pager: &mut Pager,
+        lo: Bound<&Value>,
+        hi: Bound<&Value>,
+    ) -> io::Result<Vec<u64>> {
+        let start: Option<IndexKey> = match lo {
+            Bound::Included(v) => Some((v.clone(), 0)),
+            Bound::Excluded(v) => Some((v.clone(), u64::MAX)),
+            Bound::Unbounded => None,
+        };
+        let mut id = self.root;
+        let mut node = Node::read(pager, id)?;
+        while !node.leaf {
+            id = match &start {
+                Some(key) => node.children[node.child_for(key)],
+                None => node.children[0],
+            };
+            node = Node::read(pager, id)?;
+        }
+        let mut rids: Vec<u64> = vec![];
+        loop {
+            for (value, rid) in &node.keys {
+                let below = match lo {
+                    Bound::Included(v) => value.total_cmp(v) == Ordering::Less,
+                    Bound::Excluded(v) => value.total_cmp(v) != Ordering::Greater,
+                    Bound::Unbounded => *value == Value::Null,
+                };
+                if below {
+                    continue;
+                }
+                let above = match hi {
+                    Bound::Included(v) => value.total_cmp(v) == Ordering::Greater,
+                    Bound::Excluded(v) => value.total_cmp(v) != Ordering::Less,
+                    Bound::Unbounded => false,
+                };
+                if above {
+                    return Ok(rids);
+                }
+                rids.push(*rid);
+            }
+            if node.next == NO_PAGE {
+                return Ok(rids);
+            }
+            node = Node::read(pager, node.next)?;
+        }
+    }
+
+    /// Frees every page of the tree.
+    pub fn destroy(&self, pager: &mut Pager) -> io::Result<()> {
+        let mut stack = vec![self.root];
+        while let Some(id) = stack.pop() {
+            let node = Node::read(pager, id)?;
+            stack.extend(node.children);
+            pager.free(id)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_btree_split_and_range() {
+    let path = std::env::temp_dir().join(format!("simpledb_btree_{}.bin", std::process::id()));
+    let path = path.to_str().unwrap().to_string();
+    let _ = std::fs::remove_file(&path);
+    let mut pager = Pager::open(&path).unwrap();
+    let mut tree = BTree::create(&mut pager).unwrap();
+    for rid in 0..5000u64 {
+        let value = Value::Str(format!("key-{:05}", (rid * 7919) % 5000));
+        tree.insert(&mut pager, (value, rid)).unwrap();
+    }
+    tree.insert(&mut pager, (Value::Null, 9999)).unwrap();
+    assert_ne!(tree.root, 1);
+
+    let lo = Value::Str("key-01000".to_string());
+    let hi = Value::Str("key-01010".to_string());
+    let rids = tree
+        .range(&mut pager, Bound::Included(&lo), Bound::Excluded(&hi))
+        .unwrap();
+    assert_eq!(rids.len(), 10);
+    assert!(rids.iter().all(|rid| (rid * 7919) % 5000 >= 1000));
+
+    let all = tree
+        .range(&mut pager, Bound::Unbounded, Bound::Unbounded)
+        .unwrap();
+    assert_eq!(all.len(), 5000);
+    let nulls = tree
+        .range(
+            &mut pager,
+            Bound::Included(&Value::Null),
+            Bound::Included(&Value::Null),
+        )
+        .unwrap();
+    assert_eq!(nulls, vec![9999]);
+
+    let key = (Value::Str("key-01000".to_string()), rids[0]);
+    assert!(tree.delete(&mut pager, &key).unwrap());
+    assert!(!tree.delete(&mut pager, &key).unwrap());
+    let rids = tree
+        .range(&mut pager, Bound::Included(&lo), Bound::Excluded(&hi))
+        .unwrap();
+    assert_eq!(rids.len(), 9);
+    tree.destroy(&mut pager).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(crate::storage::wal::Wal::path_for(&path)).unwrap();
+}
@@ -0,0 +1,55 @@
+use crate::database::base::{ColumnAttr, ForeignKeyAttr};
+use crate::storage::page::{PageId, PageKind, NO_PAGE};
+use crate::storage::pager::Pager;
+use serde::{Deserialize, Serialize};
+use std::io::{self, ErrorKind};
+
+/// Schema of a table as stored in the catalog pages, rows live in the heap chain.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TableMeta {
+    pub name: String,
+    pub columns: Vec<ColumnAttr>,
+    pub foreign_keys: Option<Vec<ForeignKeyAttr>>,
+    pub first_page: PageId,
+    #[serde(default)]
+    pub indexes: Vec<IndexMeta>,
+}
+
+/// An index over one column, `root` is the root page of its b-tree. The unique index
+/// is the one every table keeps on its primary key.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IndexMeta {
+    pub name: String,
+    pub column: String,
+    pub root: PageId,
+    #[serde(default)]
+    pub unique: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Catalog {
+    pub db_name: String,
+    pub tables: Vec<TableMeta>,
+}
+
+impl Catalog {
+    pub fn read(pager: &mut Pager) -> io::Result<(Catalog, Vec<u8>)> {
+        let root = pager.catalog_root()?;
+        if root == NO_PAGE {
+            return Err(io::Error::new(ErrorKind::InvalidData, "missing catalog"));
+        }
+        let bytes = pager.read_chain(root)?;
+        let catalog: Catalog = serde_json::from_slice(&bytes)?;
+        Ok((catalog, bytes))
+    }
+
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    pub fn write(pager: &mut Pager, bytes: &[u8]) -> io::Result<()> {
+        let root = pager.catalog_root()?;
+        let root = pager.write_chain(PageKind::Catalog, root, bytes)?;
+        pager.set_catalog_root(root)
+    }
+}
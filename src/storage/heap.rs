@@ -0,0 +1,353 @@
+use crate::database::base::Value;
+use crate::database::mvcc::{Csn, TxnId, FROZEN, PENDING};
+use crate::storage::page::{Page, PageId, NO_PAGE};
+use crate::storage::pager::Pager;
+use crate::storage::record::{decode_row, encode_row};
+use std::collections::BTreeSet;
+use std::io::{self, ErrorKind};
+use std::mem;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RowLoc {
+    pub page: PageId,
+    pub slot: u16,
+}
+
+/// On-disk bookkeeping for one in-memory row version: its stable row id, where its
+/// record lives, whether it changed since the last sync, and the transactions that
+/// created (`xmin`) and deleted (`xmax`) it with their commit numbers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowSlot {
+    pub rid: u64,
+    pub loc: Option<RowLoc>,
+    pub dirty: bool,
+    pub xmin: TxnId,
+    pub xmin_csn: Csn,
+    pub xmax: TxnId,
+    pub xmax_csn: Csn,
+}
+
+impl RowSlot {
+    /// Only versions created by a committed transaction and not deleted by one
+    /// belong on disk.
+    pub fn is_durable(&self) -> bool {
+        self.xmin_csn != PENDING && self.xmax_csn == PENDING
+    }
+}
+
+/// A row written or removed by a sync: the values stored before (`None` for a new
+/// row) and after (`None` for a removed row). Indexes are maintained from these.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowChange {
+    pub rid: u64,
+    pub old: Option<Vec<Value>>,
+    pub new: Option<Vec<Value>>,
+}
+
+/// What a sync is about to change in a heap, so a save that fails can be undone
+/// without copying every row: the chain ends, the pending removals and the bookkeeping
+/// of the rows it writes or takes off the pages.
+#[derive(Debug)]
+pub struct HeapCheckpoint {
+    first_page: PageId,
+    last_page: PageId,
+    removed: Vec<RowLoc>,
+    // position, location, dirty flag and whether the row was unsynced
+    rows: Vec<(usize, Option<RowLoc>, bool, bool)>,
+}
+
+/// Tracks the heap page chain of a table. `rows` runs parallel to the column vectors
+/// of the owning `Table`, so row `ix` of the table is described by `rows[ix]`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeapState {
+    pub first_page: PageId,
+    pub last_page: PageId,
+    pub next_rid: u64,
+    pub rows: Vec<RowSlot>,
+    pub removed: Vec<RowLoc>,
+    /// Row ids of the versions without a record, which indexes do not know of.
+    pub unsynced: BTreeSet<u64>,
+}
+
+impl HeapState {
+    /// Appends a row that is visible to everyone.
+    pub fn push_row(&mut self) {
+        self.push_version(FROZEN);
+        self.rows.last_mut().unwrap().xmin_csn = 0;
+    }
+
+    /// Appends a row version created by the uncommitted transaction `xmin`.
+    pub fn push_version(&mut self, xmin: TxnId) {
+        self.rows.push(RowSlot {
+            rid: self.next_rid,
+            loc: None,
+            dirty: true,
+            xmin,
+            xmin_csn: PENDING,
+            xmax: FROZEN,
+            xmax_csn: PENDING,
+        });
+        self.unsynced.insert(self.next_rid);
+        self.next_rid += 1;
+    }
+
+    /// Whether a row of `values` fits in a page, rows are never split across pages.
+    pub fn fits(values: &[Value]) -> bool {
+        encode_row(0, values).len() <= Page::max_record()
+    }
+
+    pub fn position(&self, rid: u64) -> Option<usize> {
+        self.rows.binary_search_by_key(&rid, |row| row.rid).ok()
+    }
+
+    /// Positions of the versions without a record, in order.
+    pub fn unsynced_rows(&self) -> Vec<usize> {
+        self.unsynced
+            .iter()
+            .filter_map(|rid| self.position(*rid))
+            .collect()
+    }
+
+    pub fn remove_rows(&mut self, ixs: &[usize]) {
+        let mut ix = 0;
+        let removed = &mut self.removed;
+        let unsynced = &mut self.unsynced;
+        self.rows.retain(|row| {
+            let keep = !ixs.contains(&ix);
+            ix += 1;
+            if !keep {
+                unsynced.remove(&row.rid);
+                if let Some(loc) = row.loc {
+                    removed.push(loc);
+                }
+            }
+            keep
+        });
+    }
+
+    pub fn checkpoint(&self) -> HeapCheckpoint {
+        let rows = self
+            .rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| row.dirty || (!row.is_durable() && row.loc.is_some()))
+            .map(|(ix, row)| (ix, row.loc, row.dirty, self.unsynced.contains(&row.rid)))
+            .collect();
+        HeapCheckpoint {
+            first_page: self.first_page,
+            last_page: self.last_page,
+            removed: self.removed.clone(),
+            rows,
+        }
+    }
+
+    /// Puts back what the syncs since `checkpoint` changed, the rows must be the same.
+    pub fn restore(&mut self, checkpoint: HeapCheckpoint) {
+        self.first_page = checkpoint.first_page;
+        self.last_page = checkpoint.last_page;
+        self.removed = checkpoint.removed;
+        for (ix, loc, dirty, unsynced) in checkpoint.rows {
+            let row = &mut self.rows[ix];
+            row.loc = loc;
+            row.dirty = dirty;
+            if unsynced {
+                self.unsynced.insert(row.rid);
+            } else {
+                self.unsynced.remove(&row.rid);
+            }
+        }
+    }
+
+    /// Writes removed and dirty rows into their pages. Clean rows are not touched,
+    /// so the pager only ends up with the pages this table actually changed. Versions
+    /// of uncommitted transactions stay in memory, versions whose deletion committed
+    /// leave the pages but stay in memory for the snapshots that can still see them.
+    pub fn sync<F>(&mut self, pager: &mut Pager, row_values: F) -> io::Result<Vec<RowChange>>
+    where
+        F: Fn(usize) -> Vec<Value>,
+    {
+        let mut changes: Vec<RowChange> = vec![];
+        for loc in mem::take(&mut self.removed) {
+            if let Some((rid, old)) = HeapState::stored(pager, loc)? {
+                changes.push(RowChange {
+                    rid,
+                    old: Some(old),
+                    new: None,
+                });
+            }
+            pager.write(loc.page)?.heap_delete(loc.slot);
+        }
+        for ix in 0..self.rows.len() {
+            if !self.rows[ix].is_durable() {
+                if let Some(loc) = self.rows[ix].loc.take() {
+                    self.unsynced.insert(self.rows[ix].rid);
+                    if let Some((rid, old)) = HeapState::stored(pager, loc)? {
+                        changes.push(RowChange {
+                            rid,
+                            old: Some(old),
+                            new: None,
+                        });
+                    }
+                    pager.write(loc.page)?.heap_delete(loc.slot);
+                }
+                continue;
+            }
+            if !self.rows[ix].dirty {
+                continue;
+            }
+            let rid = self.rows[ix].rid;
+            let values = row_values(ix);
+            let rec = encode_row(rid, &values);
+            if rec.len() > Page::max_record() {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "row is larger than a page",
+                ));
+            }
+            let mut old: Option<Vec<Value>> = None;
+            let mut written = false;
+            if let Some(loc) = self.rows[ix].loc {
+                old = HeapState::stored(pager, loc)?.map(|(_, values)| values);
+                written = pager.write(loc.page)?.heap_update(loc.slot, &rec);
+                if !written {
+                    pager.write(loc.page)?.heap_delete(loc.slot);
+                }
+            }
+            if !written {
+                let loc = self.append(pager, &rec)?;
+                self.rows[ix].loc = Some(loc);
+                self.unsynced.remove(&rid);
+            }
+            self.rows[ix].dirty = false;
+            changes.push(RowChange {
+                rid,
+                old,
+                new: Some(values),
+            });
+        }
+        Ok(changes)
+    }
+
+    fn stored(pager: &mut Pager, loc: RowLoc) -> io::Result<Option<(u64, Vec<Value>)>> {
+        match pager.read(loc.page)?.heap_get(loc.slot) {
+            Some(rec) => Ok(Some(decode_row(rec)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn append(&mut self, pager: &mut Pager, rec: &[u8]) -> io::Result<RowLoc> {
+        if self.last_page != NO_PAGE {
+            if let Some(slot) = pager.write(self.last_page)?.heap_insert(rec) {
+                return Ok(RowLoc {
+                    page: self.last_page,
+                    slot,
+                });
+            }
+        }
+        let page = pager.allocate()?;
+        pager.write(page)?.init_heap();
+        if self.last_page == NO_PAGE {
+            self.first_page = page;
+        } else {
+            pager.write(self.last_page)?.set_next(page);
+        }
+        self.last_page = page;
+        let slot = pager.write(page)?.heap_insert(rec).unwrap();
+        Ok(RowLoc { page, slot })
+    }
+
+    /// Reads every record of the chain starting at `first_page`, returning rows in
+    /// insertion (row id) order.
+    pub fn load(pager: &mut Pager, first_page: PageId) -> io::Result<(HeapState, Vec<Vec<Value>>)> {
+        let mut heap = HeapState {
+            first_page,
+            last_page: NO_PAGE,
+            next_rid: 0,
+            rows: vec![],
+            removed: vec![],
+            unsynced: BTreeSet::new(),
+        };
+        let mut records: Vec<(u64, RowLoc, Vec<Value>)> = vec![];
+        let mut current = first_page;
+        while current != NO_PAGE {
+            let page = pager.read(current)?;
+            for (slot, rec) in page.heap_records() {
+                let (rid, values) = decode_row(rec)?;
+                records.push((
+                    rid,
+                    RowLoc {
+                        page: current,
+                        slot,
+                    },
+                    values,
+                ));
+            }
+            heap.last_page = current;
+            current = page.next();
+        }
+        records.sort_by_key(|(rid, _, _)| *rid);
+        let mut rows: Vec<Vec<Value>> = vec![];
+        for (rid, loc, values) in records {
+            heap.rows.push(RowSlot {
+                rid,
+                loc: Some(loc),
+                dirty: false,
+                xmin: FROZEN,
+                xmin_csn: 0,
+                xmax: FROZEN,
+                xmax_csn: PENDING,
+            });
+            heap.next_rid = rid + 1;
+            rows.push(values);
+        }
+        Ok((heap, rows))
+    }
+}
+
+#[test]
+fn test_heap_sync_only_rewrites_changed_rows() {
+    let path = std::env::temp_dir().join(format!("simpledb_heap_{}.bin", std::process::id()));
+    let path = path.to_str().unwrap().to_string();
+    let _ = std::fs::remove_file(&path);
+    let mut pager = Pager::open(&path).unwrap();
+    let mut heap = HeapState::default();
+    let mut data: Vec<Vec<Value>> = vec![];
+    for i in 0..500 {
+        heap.push_row();
+        data.push(vec![Value::Int(i), Value::Str(format!("row {}", i))]);
+    }
+    heap.sync(&mut pager, |ix| data[ix].clone()).unwrap();
+    pager.commit(vec![]).unwrap();
+    assert!(heap.unsynced.is_empty());
+
+    // row 3 is replaced by a new version, row 7 is dropped
+    heap.rows[3].xmax = 1;
+    heap.rows[3].xmax_csn = 1;
+    heap.push_version(1);
+    heap.rows[500].xmin_csn = 1;
+    data.push(vec![Value::Int(3), Value::Str("changed".to_string())]);
+    heap.remove_rows(&[7]);
+    data.remove(7);
+
+    // a sync whose pages are discarded is undone from a checkpoint
+    let before = heap.clone();
+    let checkpoint = heap.checkpoint();
+    heap.sync(&mut pager, |ix| data[ix].clone()).unwrap();
+    pager.discard();
+    heap.restore(checkpoint);
+    assert_eq!(heap, before);
+
+    let changes = heap.sync(&mut pager, |ix| data[ix].clone()).unwrap();
+    pager.commit(vec![]).unwrap();
+    assert_eq!(changes.len(), 3);
+    assert_eq!(heap.rows[3].loc, None);
+    assert_eq!(heap.unsynced_rows(), vec![3]);
+
+    let (loaded, rows) = HeapState::load(&mut pager, heap.first_page).unwrap();
+    let mut expected = data.clone();
+    expected.remove(3);
+    assert_eq!(rows, expected);
+    assert_eq!(loaded.next_rid, 501);
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(crate::storage::wal::Wal::path_for(&path)).unwrap();
+}
@@ -0,0 +1,115 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, ErrorKind};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// milliseconds to wait for a lock held by another process, 0 gives up right away
+static TIMEOUT_MS: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_timeout(ms: u64) {
+    TIMEOUT_MS.store(ms, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockMode {
+    Shared,
+    Exclusive,
+}
+
+/// Advisory lock on a file: any number of processes may read under a shared lock, a
+/// writer needs the exclusive one. It only keeps other processes out, the sessions of
+/// this process coordinate through MVCC.
+#[derive(Debug)]
+pub struct FileLock {
+    path: String,
+    file: File,
+    mode: Option<LockMode>,
+}
+
+impl FileLock {
+    pub fn open(path: &str) -> io::Result<FileLock> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        Ok(FileLock {
+            path: path.to_string(),
+            file,
+            mode: None,
+        })
+    }
+
+    pub fn mode(&self) -> Option<LockMode> {
+        self.mode
+    }
+
+    pub fn file(&mut self) -> &mut File {
+        &mut self.file
+    }
+
+    /// Takes the lock in `mode`, waiting up to the configured timeout while another
+    /// process holds a conflicting one. An exclusive lock already covers a shared one.
+    pub fn acquire(&mut self, mode: LockMode) -> io::Result<()> {
+        if self.mode == Some(mode) || self.mode == Some(LockMode::Exclusive) {
+            return Ok(());
+        }
+        let deadline = Instant::now() + Duration::from_millis(TIMEOUT_MS.load(Ordering::Relaxed));
+        loop {
+            let result = match mode {
+                LockMode::Shared => self.file.try_lock_shared(),
+                LockMode::Exclusive => self.file.try_lock(),
+            };
+            match result {
+                Ok(_) => {
+                    self.mode = Some(mode);
+                    return Ok(());
+                }
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Err(TryLockError::WouldBlock) => break,
+                Err(TryLockError::Error(err)) => return Err(err),
+            }
+        }
+        // upgrading is not atomic, the shared lock may be gone after a failed attempt
+        if self.mode == Some(LockMode::Shared) && self.file.try_lock_shared().is_err() {
+            self.mode = None;
+        }
+        Err(io::Error::new(
+            ErrorKind::WouldBlock,
+            format!("{} is locked by another process", self.path),
+        ))
+    }
+
+    pub fn release(&mut self) -> io::Result<()> {
+        if self.mode.take().is_some() {
+            self.file.unlock()?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_file_lock_modes() {
+    let path = std::env::temp_dir().join(format!("simpledb_lock_{}.bin", std::process::id()));
+    let path = path.to_str().unwrap().to_string();
+    let mut reader = FileLock::open(&path).unwrap();
+    let mut other = FileLock::open(&path).unwrap();
+    let mut writer = FileLock::open(&path).unwrap();
+    reader.acquire(LockMode::Shared).unwrap();
+    other.acquire(LockMode::Shared).unwrap();
+    let err = writer.acquire(LockMode::Exclusive).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::WouldBlock);
+    assert_eq!(writer.mode(), None);
+
+    reader.release().unwrap();
+    other.release().unwrap();
+    writer.acquire(LockMode::Exclusive).unwrap();
+    assert!(reader.acquire(LockMode::Shared).is_err());
+    writer.release().unwrap();
+    reader.acquire(LockMode::Shared).unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,8 @@
+pub(crate) mod btree;
+pub(crate) mod catalog;
+pub(crate) mod heap;
+pub(crate) mod lock;
+pub(crate) mod page;
+pub(crate) mod pager;
+pub(crate) mod record;
+pub(crate) mod wal;
@@ -0,0 +1,231 @@
+pub const PAGE_SIZE: usize = 4096;
+
+pub type PageId = u32;
+
+// Page 0 is always the file header, so 0 doubles as "no page" in links.
+pub const HEADER_PAGE: PageId = 0;
+pub const NO_PAGE: PageId = 0;
+
+pub const MAGIC: &[u8; 8] = b"SIMPLEDB";
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PageKind {
+    Free = 0,
+    Catalog = 1,
+    Heap = 2,
+    BTreeLeaf = 3,
+    BTreeInternal = 4,
+}
+
+// Heap page layout:
+// kind u8 | next u32 | slot_count u16 | free_end u16 | slots (offset u16, len u16)... | records
+const HEAP_HEADER: usize = 9;
+const SLOT_SIZE: usize = 4;
+
+// Catalog page layout: kind u8 | next u32 | len u16 | data
+const CHAIN_HEADER: usize = 7;
+pub const CHAIN_CAPACITY: usize = PAGE_SIZE - CHAIN_HEADER;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    pub data: Vec<u8>,
+}
+
+impl Page {
+    pub fn new() -> Page {
+        Page {
+            data: vec![0; PAGE_SIZE],
+        }
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Page {
+        Page { data }
+    }
+
+    pub fn read_u16(&self, at: usize) -> u16 {
+        u16::from_le_bytes([self.data[at], self.data[at + 1]])
+    }
+
+    pub fn read_u32(&self, at: usize) -> u32 {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(&self.data[at..at + 4]);
+        u32::from_le_bytes(buf)
+    }
+
+    pub fn write_u16(&mut self, at: usize, val: u16) {
+        self.data[at..at + 2].copy_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, at: usize, val: u32) {
+        self.data[at..at + 4].copy_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn next(&self) -> PageId {
+        self.read_u32(1)
+    }
+
+    pub fn set_next(&mut self, next: PageId) {
+        self.write_u32(1, next);
+    }
+
+    pub fn init_free(&mut self, next: PageId) {
+        self.data.fill(0);
+        self.data[0] = PageKind::Free as u8;
+        self.set_next(next);
+    }
+
+    pub fn init_chain(&mut self, kind: PageKind, next: PageId, chunk: &[u8]) {
+        self.data.fill(0);
+        self.data[0] = kind as u8;
+        self.set_next(next);
+        self.write_u16(5, chunk.len() as u16);
+        self.data[CHAIN_HEADER..CHAIN_HEADER + chunk.len()].copy_from_slice(chunk);
+    }
+
+    pub fn chain_data(&self) -> &[u8] {
+        let len = self.read_u16(5) as usize;
+        &self.data[CHAIN_HEADER..CHAIN_HEADER + len]
+    }
+
+    pub fn init_heap(&mut self) {
+        self.data.fill(0);
+        self.data[0] = PageKind::Heap as u8;
+        self.set_next(NO_PAGE);
+        self.write_u16(5, 0);
+        self.write_u16(7, PAGE_SIZE as u16);
+    }
+
+    pub fn slot_count(&self) -> u16 {
+        self.read_u16(5)
+    }
+
+    fn free_end(&self) -> usize {
+        self.read_u16(7) as usize
+    }
+
+    fn slot(&self, slot: u16) -> (usize, usize) {
+        let at = HEAP_HEADER + slot as usize * SLOT_SIZE;
+        (self.read_u16(at) as usize, self.read_u16(at + 2) as usize)
+    }
+
+    fn set_slot(&mut self, slot: u16, offset: usize, len: usize) {
+        let at = HEAP_HEADER + slot as usize * SLOT_SIZE;
+        self.write_u16(at, offset as u16);
+        self.write_u16(at + 2, len as u16);
+    }
+
+    fn live_bytes(&self) -> usize {
+        (0..self.slot_count()).map(|s| self.slot(s).1).sum()
+    }
+
+    /// Largest record a freshly initialised heap page can hold.
+    pub fn max_record() -> usize {
+        PAGE_SIZE - HEAP_HEADER - SLOT_SIZE
+    }
+
+    pub fn heap_get(&self, slot: u16) -> Option<&[u8]> {
+        if slot >= self.slot_count() {
+            return None;
+        }
+        let (offset, len) = self.slot(slot);
+        if len == 0 {
+            return None;
+        }
+        Some(&self.data[offset..offset + len])
+    }
+
+    pub fn heap_records(&self) -> Vec<(u16, &[u8])> {
+        (0..self.slot_count())
+            .filter_map(|s| self.heap_get(s).map(|rec| (s, rec)))
+            .collect()
+    }
+
+    pub fn heap_insert(&mut self, rec: &[u8]) -> Option<u16> {
+        let free_slot = (0..self.slot_count()).find(|&s| self.slot(s).1 == 0);
+        let dir_growth = if free_slot.is_some() { 0 } else { SLOT_SIZE };
+        let dir_end = HEAP_HEADER + self.slot_count() as usize * SLOT_SIZE + dir_growth;
+        if self.free_end() < dir_end + rec.len() {
+            let reclaimable = PAGE_SIZE - dir_end - self.live_bytes();
+            if reclaimable < rec.len() {
+                return None;
+            }
+            self.compact();
+        }
+        let slot = match free_slot {
+            Some(s) => s,
+            None => {
+                let s = self.slot_count();
+                self.write_u16(5, s + 1);
+                s
+            }
+        };
+        let offset = self.free_end() - rec.len();
+        self.data[offset..offset + rec.len()].copy_from_slice(rec);
+        self.set_slot(slot, offset, rec.len());
+        self.write_u16(7, offset as u16);
+        Some(slot)
+    }
+
+    /// Rewrites the record in place, returning false when the page has no room for it.
+    pub fn heap_update(&mut self, slot: u16, rec: &[u8]) -> bool {
+        let (offset, len) = self.slot(slot);
+        if rec.len() <= len {
+            self.data[offset..offset + rec.len()].copy_from_slice(rec);
+            self.set_slot(slot, offset, rec.len());
+            return true;
+        }
+        let dir_end = HEAP_HEADER + self.slot_count() as usize * SLOT_SIZE;
+        if PAGE_SIZE - dir_end - (self.live_bytes() - len) < rec.len() {
+            return false;
+        }
+        self.set_slot(slot, 0, 0);
+        if self.free_end() < dir_end + rec.len() {
+            self.compact();
+        }
+        let offset = self.free_end() - rec.len();
+        self.data[offset..offset + rec.len()].copy_from_slice(rec);
+        self.set_slot(slot, offset, rec.len());
+        self.write_u16(7, offset as u16);
+        true
+    }
+
+    pub fn heap_delete(&mut self, slot: u16) {
+        if slot < self.slot_count() {
+            self.set_slot(slot, 0, 0);
+        }
+    }
+
+    fn compact(&mut self) {
+        let records = self
+            .heap_records()
+            .into_iter()
+            .map(|(s, rec)| (s, rec.to_vec()))
+            .collect::<Vec<(u16, Vec<u8>)>>();
+        let mut end = PAGE_SIZE;
+        for (slot, rec) in records {
+            end -= rec.len();
+            self.data[end..end + rec.len()].copy_from_slice(&rec);
+            self.set_slot(slot, end, rec.len());
+        }
+        self.write_u16(7, end as u16);
+    }
+}
+
+#[test]
+fn test_heap_page_reuses_space() {
+    let mut page = Page::new();
+    page.init_heap();
+    let a = page.heap_insert(&[1u8; 1000]).unwrap();
+    let b = page.heap_insert(&[2u8; 1000]).unwrap();
+    let c = page.heap_insert(&[3u8; 1000]).unwrap();
+    assert!(page.heap_insert(&[4u8; 1100]).is_none());
+    page.heap_delete(b);
+    let d = page.heap_insert(&[4u8; 1100]).unwrap();
+    assert_eq!(d, b);
+    assert_eq!(page.heap_get(a).unwrap(), &[1u8; 1000][..]);
+    assert_eq!(page.heap_get(c).unwrap(), &[3u8; 1000][..]);
+    assert_eq!(page.heap_get(d).unwrap(), &[4u8; 1100][..]);
+    assert!(page.heap_update(a, &[5u8; 10]));
+    assert_eq!(page.heap_records().len(), 3);
+}
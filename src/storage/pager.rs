@@ -0,0 +1,431 @@
+use crate::storage::page::{
+    Page, PageId, PageKind, CHAIN_CAPACITY, FORMAT_VERSION, HEADER_PAGE, MAGIC, NO_PAGE, PAGE_SIZE,
+};
+use crate::storage::wal::{Frame as WalFrame, Mutation, Wal};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+
+#[cfg(test)]
+thread_local! {
+    /// Makes the next commit of this thread fail as if the wal could not be written.
+    pub static FAIL_COMMIT: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Number of clean pages kept in the buffer pool before the least recently used is evicted.
+pub const POOL_SIZE: usize = 256;
+
+// Header page layout:
+// magic [u8; 8] | version u32 | page_size u32 | page_count u32 | catalog_root u32 | freelist u32
+// | change_counter u32
+const PAGE_COUNT_AT: usize = 16;
+const CATALOG_ROOT_AT: usize = 20;
+const FREELIST_AT: usize = 24;
+const CHANGE_COUNTER_AT: usize = 28;
+
+struct Frame {
+    page: Page,
+    last_used: u64,
+}
+
+/// Reads and writes fixed-size pages of a database file through a small buffer pool.
+/// Modified pages stay pinned in the pool until `commit`, so only the pages a mutation
+/// touched are written back, and they go through the write-ahead log first.
+pub struct Pager {
+    file: File,
+    wal: Wal,
+    pool: HashMap<PageId, Frame>,
+    dirty: HashSet<PageId>,
+    tick: u64,
+    recovered: Vec<Mutation>,
+}
+
+impl fmt::Debug for Pager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pager")
+            .field("cached", &self.pool.len())
+            .field("dirty", &self.dirty.len())
+            .finish()
+    }
+}
+
+impl Pager {
+    pub fn open(path: &str) -> io::Result<Pager> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut pager = Pager {
+            file,
+            wal: Wal::open(path)?,
+            pool: HashMap::new(),
+            dirty: HashSet::new(),
+            tick: 0,
+            recovered: vec![],
+        };
+        pager.recover()?;
+        let is_new = pager.file.metadata()?.len() == 0;
+        if is_new {
+            let mut header = Page::new();
+            header.data[0..8].copy_from_slice(MAGIC);
+            header.write_u32(8, FORMAT_VERSION);
+            header.write_u32(12, PAGE_SIZE as u32);
+            header.write_u32(PAGE_COUNT_AT, 1);
+            header.write_u32(CATALOG_ROOT_AT, NO_PAGE);
+            header.write_u32(FREELIST_AT, NO_PAGE);
+            pager.install(HEADER_PAGE, header, true);
+            pager.commit(vec![])?;
+        } else {
+            let header = pager.read(HEADER_PAGE)?;
+            if &header.data[0..8] != MAGIC || header.read_u32(8) != FORMAT_VERSION {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "not a SimpleDB page file",
+                ));
+            }
+        }
+        Ok(pager)
+    }
+
+    /// Checks the magic bytes so callers can tell page files from the legacy json dumps.
+    pub fn is_page_file(path: &str) -> io::Result<bool> {
+        let mut file = File::open(path)?;
+        let mut magic = [0u8; 8];
+        match file.read_exact(&mut magic) {
+            Ok(_) => Ok(&magic == MAGIC),
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn page_count(&mut self) -> io::Result<u32> {
+        Ok(self.read(HEADER_PAGE)?.read_u32(PAGE_COUNT_AT))
+    }
+
+    pub fn catalog_root(&mut self) -> io::Result<PageId> {
+        Ok(self.read(HEADER_PAGE)?.read_u32(CATALOG_ROOT_AT))
+    }
+
+    pub fn set_catalog_root(&mut self, root: PageId) -> io::Result<()> {
+        self.write(HEADER_PAGE)?.write_u32(CATALOG_ROOT_AT, root);
+        Ok(())
+    }
+
+    /// Tells whether another process committed to the file since this pager read its
+    /// header, the cached pages can no longer be trusted then.
+    pub fn is_stale(&mut self) -> io::Result<bool> {
+        let mut counter = [0u8; 4];
+        self.file.seek(SeekFrom::Start(CHANGE_COUNTER_AT as u64))?;
+        self.file.read_exact(&mut counter)?;
+        let cached = self.read(HEADER_PAGE)?.read_u32(CHANGE_COUNTER_AT);
+        Ok(u32::from_le_bytes(counter) != cached)
+    }
+
+    pub fn read(&mut self, id: PageId) -> io::Result<&Page> {
+        self.fetch(id)?;
+        Ok(&self.pool.get(&id).unwrap().page)
+    }
+
+    /// Returns the page for modification and marks it dirty.
+    pub fn write(&mut self, id: PageId) -> io::Result<&mut Page> {
+        self.fetch(id)?;
+        self.dirty.insert(id);
+        Ok(&mut self.pool.get_mut(&id).unwrap().page)
+    }
+
+    /// Hands out a page from the freelist, or grows the file by one page.
+    pub fn allocate(&mut self) -> io::Result<PageId> {
+        let free_head = self.read(HEADER_PAGE)?.read_u32(FREELIST_AT);
+        if free_head != NO_PAGE {
+            let next = self.read(free_head)?.next();
+            self.write(HEADER_PAGE)?.write_u32(FREELIST_AT, next);
+            self.write(free_head)?.data.fill(0);
+            return Ok(free_head);
+        }
+        let id = self.page_count()?;
+        self.write(HEADER_PAGE)?.write_u32(PAGE_COUNT_AT, id + 1);
+        self.install(id, Page::new(), true);
+        Ok(id)
+    }
+
+    pub fn free(&mut self, id: PageId) -> io::Result<()> {
+        let free_head = self.read(HEADER_PAGE)?.read_u32(FREELIST_AT);
+        self.write(id)?.init_free(free_head);
+        self.write(HEADER_PAGE)?.write_u32(FREELIST_AT, id);
+        Ok(())
+    }
+
+    /// Frees every page of a chain linked through the page `next` pointers.
+    pub fn free_chain(&mut self, first: PageId) -> io::Result<()> {
+        let mut current = first;
+        while current != NO_PAGE {
+            let next = self.read(current)?.next();
+            self.free(current)?;
+            current = next;
+        }
+        Ok(())
+    }
+
+    /// Writes `data` over the chain starting at `first`, reusing its pages and
+    /// allocating or freeing pages as the length requires. Returns the new first page.
+    pub fn write_chain(
+        &mut self,
+        kind: PageKind,
+        first: PageId,
+        data: &[u8],
+    ) -> io::Result<PageId> {
+        let mut pages: Vec<PageId> = vec![];
+        let mut current = first;
+        while current != NO_PAGE {
+            pages.push(current);
+            current = self.read(current)?.next();
+        }
+        let chunks = data.chunks(CHAIN_CAPACITY).collect::<Vec<&[u8]>>();
+        let chunks = if chunks.is_empty() {
+            vec![&data[..0]]
+        } else {
+            chunks
+        };
+        while pages.len() < chunks.len() {
+            pages.push(self.allocate()?);
+        }
+        for extra in pages.split_off(chunks.len()) {
+            self.free(extra)?;
+        }
+        for (ix, chunk) in chunks.iter().enumerate() {
+            let next = pages.get(ix + 1).copied().unwrap_or(NO_PAGE);
+            self.write(pages[ix])?.init_chain(kind, next, chunk);
+        }
+        Ok(pages[0])
+    }
+
+    pub fn read_chain(&mut self, first: PageId) -> io::Result<Vec<u8>> {
+        let mut data = vec![];
+        let mut current = first;
+        while current != NO_PAGE {
+            let page = self.read(current)?;
+            data.extend_from_slice(page.chain_data());
+            current = page.next();
+        }
+        Ok(data)
+    }
+
+    /// Makes all dirty pages durable: they are appended to the write-ahead log together
+    /// with the mutations they implement, then written to the database file, and the log
+    /// is truncated once the file is synced.
+    pub fn commit(&mut self, mutations: Vec<Mutation>) -> io::Result<()> {
+        if self.dirty.is_empty() {
+            return Ok(());
+        }
+        let header = self.write(HEADER_PAGE)?;
+        let counter = header.read_u32(CHANGE_COUNTER_AT).wrapping_add(1);
+        header.write_u32(CHANGE_COUNTER_AT, counter);
+        let mut ids = self.dirty.iter().copied().collect::<Vec<PageId>>();
+        ids.sort();
+        let frame = WalFrame {
+            mutations,
+            pages: ids
+                .iter()
+                .map(|id| (*id, self.pool.get(id).unwrap().page.clone()))
+                .collect::<Vec<(PageId, Page)>>(),
+        };
+        #[cfg(test)]
+        if FAIL_COMMIT.with(|fail| fail.replace(false)) {
+            return Err(io::Error::other("the wal could not be written"));
+        }
+        self.wal.append(&frame)?;
+        self.apply(&frame.pages)?;
+        self.wal.reset()?;
+        self.dirty.clear();
+        self.evict(POOL_SIZE);
+        Ok(())
+    }
+
+    /// Drops the pages modified since the last commit, they are read back from the
+    /// file when needed again.
+    pub fn discard(&mut self) {
+        for id in std::mem::take(&mut self.dirty) {
+            self.pool.remove(&id);
+        }
+    }
+
+    /// Mutations replayed from the write-ahead log when the file was opened.
+    pub fn take_recovered(&mut self) -> Vec<Mutation> {
+        std::mem::take(&mut self.recovered)
+    }
+
+    // Replays the committed frames left behind by a process that stopped before the
+    // log was truncated. Torn frames are dropped, their pages never reached the file.
+    fn recover(&mut self) -> io::Result<()> {
+        if self.wal.is_empty()? {
+            return Ok(());
+        }
+        for frame in self.wal.frames()? {
+            self.apply(&frame.pages)?;
+            self.recovered.extend(frame.mutations);
+        }
+        self.wal.reset()?;
+        self.pool.clear();
+        Ok(())
+    }
+
+    fn apply(&mut self, pages: &[(PageId, Page)]) -> io::Result<()> {
+        for (id, page) in pages {
+            self.file
+                .seek(SeekFrom::Start(*id as u64 * PAGE_SIZE as u64))?;
+            self.file.write_all(&page.data)?;
+        }
+        self.file.sync_data()
+    }
+
+    fn fetch(&mut self, id: PageId) -> io::Result<()> {
+        self.tick += 1;
+        if let Some(frame) = self.pool.get_mut(&id) {
+            frame.last_used = self.tick;
+            return Ok(());
+        }
+        let mut data = vec![0u8; PAGE_SIZE];
+        self.file
+            .seek(SeekFrom::Start(id as u64 * PAGE_SIZE as u64))?;
+        self.file.read_exact(&mut data)?;
+        self.install(id, Page::from_bytes(data), false);
+        Ok(())
+    }
+
+    fn install(&mut self, id: PageId, page: Page, dirty: bool) {
+        self.evict(POOL_SIZE.saturating_sub(1));
+        self.tick += 1;
+        self.pool.insert(
+            id,
+            Frame {
+                page,
+                last_used: self.tick,
+            },
+        );
+        if dirty {
+            self.dirty.insert(id);
+        }
+    }
+
+    // Dirty pages are never evicted, they only leave the pool after being flushed.
+    fn evict(&mut self, keep: usize) {
+        while self.pool.len() > keep {
+            let victim = self
+                .pool
+                .iter()
+                .filter(|(id, _)| !self.dirty.contains(id) && **id != HEADER_PAGE)
+                .min_by_key(|(_, frame)| frame.last_used)
+                .map(|(id, _)| *id);
+            match victim {
+                Some(id) => {
+                    self.pool.remove(&id);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+#[test]
+fn test_pager_reuses_freed_pages() {
+    let path = std::env::temp_dir().join(format!("simpledb_pager_{}.bin", std::process::id()));
+    let path = path.to_str().unwrap().to_string();
+    let _ = std::fs::remove_file(&path);
+    let mut pager = Pager::open(&path).unwrap();
+    let payload = vec![7u8; 10_000];
+    let root = pager
+        .write_chain(PageKind::Catalog, NO_PAGE, &payload)
+        .unwrap();
+    pager.set_catalog_root(root).unwrap();
+    pager.commit(vec![]).unwrap();
+    assert_eq!(pager.page_count().unwrap(), 4);
+
+    let mut pager = Pager::open(&path).unwrap();
+    let root = pager.catalog_root().unwrap();
+    assert_eq!(pager.read_chain(root).unwrap(), payload);
+    let root = pager
+        .write_chain(PageKind::Catalog, root, b"short")
+        .unwrap();
+    let reused = pager.allocate().unwrap();
+    assert_ne!(reused, root);
+    assert_eq!(pager.page_count().unwrap(), 4);
+    assert_eq!(pager.read_chain(root).unwrap(), b"short");
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(Wal::path_for(&path)).unwrap();
+}
+
+#[test]
+fn test_pager_replays_wal_after_crash() {
+    let path = std::env::temp_dir().join(format!("simpledb_crash_{}.bin", std::process::id()));
+    let path = path.to_str().unwrap().to_string();
+    let _ = std::fs::remove_file(&path);
+    let mut pager = Pager::open(&path).unwrap();
+    let root = pager
+        .write_chain(PageKind::Catalog, NO_PAGE, b"before")
+        .unwrap();
+    pager.set_catalog_root(root).unwrap();
+    pager.commit(vec![]).unwrap();
+
+    // the log write succeeds but the process dies before the database file is updated
+    pager
+        .write_chain(PageKind::Catalog, root, b"after")
+        .unwrap();
+    let frame = WalFrame {
+        mutations: vec![Mutation::CreateTable {
+            table: "users".to_string(),
+        }],
+        pages: vec![(root, pager.read(root).unwrap().clone())],
+    };
+    pager.wal.append(&frame).unwrap();
+    drop(pager);
+
+    let mut pager = Pager::open(&path).unwrap();
+    assert_eq!(pager.take_recovered(), frame.mutations);
+    assert_eq!(pager.read_chain(root).unwrap(), b"after");
+    assert!(pager.wal.is_empty().unwrap());
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(Wal::path_for(&path)).unwrap();
+}
+
+#[test]
+fn test_pager_discard_drops_unsaved_writes() {
+    let path = std::env::temp_dir().join(format!("simpledb_discard_{}.bin", std::process::id()));
+    let path = path.to_str().unwrap().to_string();
+    let _ = std::fs::remove_file(&path);
+    let mut pager = Pager::open(&path).unwrap();
+    let first = pager.allocate().unwrap();
+    pager.write(first).unwrap().data[100] = 1;
+    pager.commit(vec![]).unwrap();
+
+    pager.write(first).unwrap().data[100] = 2;
+    let second = pager.allocate().unwrap();
+    pager.write(second).unwrap().data[100] = 3;
+    pager.discard();
+    assert_eq!(pager.read(first).unwrap().data[100], 1);
+    assert_eq!(pager.page_count().unwrap(), 2);
+    pager.commit(vec![]).unwrap();
+    let mut pager = Pager::open(&path).unwrap();
+    assert_eq!(pager.read(first).unwrap().data[100], 1);
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(Wal::path_for(&path)).unwrap();
+}
+
+#[test]
+fn test_pager_detects_commits_of_other_pagers() {
+    let path = std::env::temp_dir().join(format!("simpledb_stale_{}.bin", std::process::id()));
+    let path = path.to_str().unwrap().to_string();
+    let _ = std::fs::remove_file(&path);
+    let mut first = Pager::open(&path).unwrap();
+    let mut second = Pager::open(&path).unwrap();
+    assert!(!second.is_stale().unwrap());
+    let id = first.allocate().unwrap();
+    first.write(id).unwrap().data[100] = 1;
+    first.commit(vec![]).unwrap();
+    assert!(!first.is_stale().unwrap());
+    assert!(second.is_stale().unwrap());
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(Wal::path_for(&path)).unwrap();
+}
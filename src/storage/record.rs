@@ -0,0 +1,104 @@
+use crate::database::base::Value;
+use std::io::{self, ErrorKind};
+
+const TAG_NULL: u8 = 0;
+const TAG_INT: u8 = 1;
+const TAG_FLOAT: u8 = 2;
+const TAG_STR: u8 = 3;
+const TAG_BOOL: u8 = 4;
+
+/// Encodes one row as `rid u64 | count u16 | (tag u8, payload)...`.
+pub fn encode_row(rid: u64, values: &[Value]) -> Vec<u8> {
+    let mut buf: Vec<u8> = Vec::with_capacity(16 + values.len() * 8);
+    buf.extend_from_slice(&rid.to_le_bytes());
+    buf.extend_from_slice(&(values.len() as u16).to_le_bytes());
+    for val in values {
+        encode_value(&mut buf, val);
+    }
+    buf
+}
+
+pub fn decode_row(bytes: &[u8]) -> io::Result<(u64, Vec<Value>)> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let rid = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+    let count = u16::from_le_bytes(reader.take(2)?.try_into().unwrap());
+    let mut values: Vec<Value> = vec![];
+    for _ in 0..count {
+        values.push(reader.value()?);
+    }
+    Ok((rid, values))
+}
+
+pub fn encode_value(buf: &mut Vec<u8>, val: &Value) {
+    match val {
+        Value::Null => buf.push(TAG_NULL),
+        Value::Int(x) => {
+            buf.push(TAG_INT);
+            buf.extend_from_slice(&x.to_le_bytes());
+        }
+        Value::Float(x) => {
+            buf.push(TAG_FLOAT);
+            buf.extend_from_slice(&x.to_le_bytes());
+        }
+        Value::Str(x) => {
+            buf.push(TAG_STR);
+            buf.extend_from_slice(&(x.len() as u32).to_le_bytes());
+            buf.extend_from_slice(x.as_bytes());
+        }
+        Value::Bool(x) => {
+            buf.push(TAG_BOOL);
+            buf.push(*x as u8);
+        }
+    }
+}
+
+pub struct Reader<'a> {
+    pub bytes: &'a [u8],
+    pub pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.pos + len > self.bytes.len() {
+            return Err(io::Error::new(ErrorKind::InvalidData, "truncated record"));
+        }
+        let out = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(out)
+    }
+
+    pub fn value(&mut self) -> io::Result<Value> {
+        let tag = self.take(1)?[0];
+        let val = match tag {
+            TAG_NULL => Value::Null,
+            TAG_INT => Value::Int(i64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+            TAG_FLOAT => Value::Float(f64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+            TAG_STR => {
+                let len = u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as usize;
+                let raw = self.take(len)?.to_vec();
+                Value::Str(
+                    String::from_utf8(raw)
+                        .map_err(|_| io::Error::new(ErrorKind::InvalidData, "invalid utf-8"))?,
+                )
+            }
+            TAG_BOOL => Value::Bool(self.take(1)?[0] != 0),
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "unknown value tag")),
+        };
+        Ok(val)
+    }
+}
+
+#[test]
+fn test_row_round_trip() {
+    let values = vec![
+        Value::Int(-3),
+        Value::Null,
+        Value::Str("héllo".to_string()),
+        Value::Float(1.5),
+        Value::Bool(true),
+    ];
+    let bytes = encode_row(42, &values);
+    let (rid, decoded) = decode_row(&bytes).unwrap();
+    assert_eq!(rid, 42);
+    assert_eq!(decoded, values);
+}
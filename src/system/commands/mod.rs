@@ -1,4 +1,5 @@
 pub mod normal;
 pub mod parser;
+pub(crate) mod render;
 pub(crate) mod system;
 mod r#type;
@@ -1,312 +1,219 @@
-use crate::database;
-use crate::database::table::{PrettyTable, Table};
+use crate::database::base::{DataType, Value};
+use crate::database::result::{Output, ResultSet};
+use crate::database::session::Session;
+use crate::database::table::{Rows, Table};
 use crate::parser::create::CreateQuery;
 use crate::parser::delete::DeleteQuery;
 use crate::parser::drop::DropQuery;
+use crate::parser::index::{CreateIndexQuery, DropIndexQuery};
 use crate::parser::insert::InsertQuery;
-use crate::parser::join::FromType;
 use crate::parser::select::SelectQuery;
+use crate::parser::transaction::TransactionQuery;
 use crate::parser::update::UpdateQuery;
 use crate::parser::utils::parse_sql;
+use crate::storage::wal::Mutation;
 use crate::system::errors::Errors;
-use std::collections::HashMap;
 
-pub fn create_tb(query: String, db: &mut database::db::Database) {
-    let state = match parse_sql(query.as_str()) {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    let query = match CreateQuery::format_stat(state) {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
+pub fn create_tb(query: String, session: &mut Session) -> Result<Output, Errors> {
+    let state = parse_sql(query.as_str())?;
+    let query = CreateQuery::format_stat(state)?;
     let tb = Table::new(query);
-    match db.create_table(tb) {
-        Ok(_) => {}
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    db.save_disk().unwrap();
+    let table = tb.name.to_string();
+    session.run_schema(|db| {
+        db.create_table(tb)?;
+        db.commit(Mutation::CreateTable { table })
+            .map_err(|_| Errors::DiskSaveError)?;
+        Ok(Output::Done)
+    })
 }
 
-pub fn drop_tb(query: String, db: &mut database::db::Database) {
-    let state = match parse_sql(query.as_str()) {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    let query_result = DropQuery::format_stat(state);
-    let query = match query_result {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    db.drop_table(query.drop_tbs);
-    db.save_disk().unwrap()
+pub fn drop_tb(query: String, session: &mut Session) -> Result<Output, Errors> {
+    let state = parse_sql(query.as_str())?;
+    let query = DropQuery::format_stat(state)?;
+    session.run_schema(|db| {
+        db.drop_table(query.drop_tbs.clone());
+        let mutation = Mutation::DropTable {
+            tables: query.drop_tbs,
+        };
+        db.commit(mutation).map_err(|_| Errors::DiskSaveError)?;
+        Ok(Output::Done)
+    })
 }
 
-pub fn insert_data(query: String, db: &mut database::db::Database) {
-    let state = match parse_sql(query.as_str()) {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    let query_result = InsertQuery::format_stat(state);
-    let query = match query_result {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    db.insert_row(query.tb_name, query.cols, query.rows);
-    db.save_disk().unwrap();
+pub fn create_idx(query: String, session: &mut Session) -> Result<Output, Errors> {
+    let state = parse_sql(query.as_str())?;
+    let query = CreateIndexQuery::format_stat(state)?;
+    let mutation = Mutation::CreateIndex {
+        index: query.idx_name.to_string(),
+        table: query.tb_name.to_string(),
+    };
+    session.run_schema(|db| {
+        db.create_index(query)?;
+        db.commit(mutation).map_err(|_| Errors::DiskSaveError)?;
+        Ok(Output::Done)
+    })
 }
 
-pub fn select_data(query: String, db: &mut database::db::Database) {
-    let state = match parse_sql(query.as_str()) {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    let query = match SelectQuery::format_stat(state) {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    let froms = query.from.clone();
-    if froms.len() != 1 {
-        println!("{}", Errors::InvalidExpression);
-        return;
-    }
-    let from = match froms.first() {
-        None => {
-            println!("{}", Errors::InvalidExpression);
-            return;
-        }
-        Some(v) => v.to_owned(),
-    };
-    match from {
-        FromType::Join { join_info, .. } => {
-            let left_table = join_info.clone().left_table;
-            let right_table = join_info.clone().right_table;
-            let left_tb = match db.get_table(left_table) {
-                Ok(v) => v,
-                Err(err) => {
-                    err.print();
-                    return;
-                }
-            };
-            let right_tb = match db.get_table(right_table) {
-                Ok(v) => v,
-                Err(err) => {
-                    err.print();
-                    return;
-                }
-            };
-            let joint_tb = Table::join_tbs(left_tb, right_tb, join_info);
-            joint_tb.select_data(query)
-        }
-        FromType::String { tb } => {
-            let tb = match db.get_table(tb) {
-                Ok(v) => v,
-                Err(err) => {
-                    err.print();
-                    return;
-                }
-            };
-            tb.select_data(query)
-        }
-    }
+pub fn drop_idx(query: String, session: &mut Session) -> Result<Output, Errors> {
+    let state = parse_sql(query.as_str())?;
+    let query = DropIndexQuery::format_stat(state)?;
+    session.run_schema(|db| {
+        db.drop_index(query.idx_names.clone())?;
+        let mutation = Mutation::DropIndex {
+            indexes: query.idx_names,
+        };
+        db.commit(mutation).map_err(|_| Errors::DiskSaveError)?;
+        Ok(Output::Done)
+    })
 }
 
-pub fn update_data(query: String, db: &mut database::db::Database) {
-    let state = match parse_sql(query.as_str()) {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    let query = match UpdateQuery::format_stat(state) {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    let tb: &mut Table = match db.get_table_mut(query.tb_name) {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    let rows = match tb.get_rows() {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    let filtered_rows = tb.filter_rows(&query.condition, rows.clone(), None);
-    let pk = tb
-        .columns
-        .iter()
-        .filter(|col| col.is_pk)
-        .map(|col| col.name.clone())
-        .collect::<Vec<String>>()
-        .first()
-        .unwrap()
-        .to_string();
-    let row_ids = filtered_rows
-        .iter()
-        .map(|row| row.get(pk.as_str()).unwrap().to_string())
-        .collect::<Vec<String>>();
-    let row_ixs: Vec<usize> = rows
-        .iter()
-        .enumerate()
-        .filter_map(|(ix, row)| {
-            if row_ids.contains(row.get(pk.as_str()).unwrap()) {
-                Some(ix)
-            } else {
-                None
-            }
-        })
-        .collect();
-    for (col, val) in &query.assignments {
-        for &row_ix in &row_ixs {
-            if let Some(column_data) = tb.col_map.get_mut(col.as_str()) {
-                column_data.update_val(row_ix, val.clone());
-            }
-        }
-    }
-    db.save_disk().unwrap()
+pub fn insert_data(query: String, session: &mut Session) -> Result<Output, Errors> {
+    let state = parse_sql(query.as_str())?;
+    let query = InsertQuery::format_stat(state)?;
+    let affected = query.rows.len();
+    let mutation = Mutation::Insert {
+        table: query.tb_name.to_string(),
+        rows: affected,
+    };
+    session.run(|db, txn| {
+        db.insert_row(query.tb_name, query.cols, query.rows, txn)?;
+        txn.mutations.push(mutation);
+        Ok(Output::Affected(affected))
+    })
 }
 
-pub fn delete_data(query: String, db: &mut database::db::Database) {
-    let state = match parse_sql(query.as_str()) {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    let query_result = DeleteQuery::format_stat(state);
-    let query;
-    match query_result {
-        Ok(v) => query = v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    }
-    let tb: &mut Table = match db.get_table_mut(query.tb_name) {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    let rows = match tb.get_rows() {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    let filtered_rows = tb.filter_rows(&query.condition, rows.clone(), None);
-    let pk = tb
-        .columns
-        .iter()
-        .filter(|col| col.is_pk)
-        .map(|col| col.name.clone())
-        .collect::<Vec<String>>()
-        .first()
-        .unwrap()
-        .to_string();
-    let row_ids = filtered_rows
-        .iter()
-        .map(|row| row.get(pk.as_str()).unwrap().to_string())
-        .collect::<Vec<String>>();
-    let row_ixs: Vec<usize> = rows
-        .iter()
-        .enumerate()
-        .filter_map(|(ix, row)| {
-            if row_ids.contains(row.get(pk.as_str()).unwrap()) {
-                Some(ix)
-            } else {
-                None
-            }
-        })
-        .collect();
-    for (_, val) in &mut tb.col_map {
-        val.delete_val(row_ixs.clone());
-    }
-    println!("Number of affected rows: {}", row_ixs.len());
-    db.save_disk().unwrap()
+pub fn select_data(query: String, session: &mut Session) -> Result<Output, Errors> {
+    let state = parse_sql(query.as_str())?;
+    let query = SelectQuery::format_stat(state)?;
+    session.run(|db, txn| Ok(Output::Rows(db.select(query, &txn.snapshot)?)))
+}
+
+pub fn update_data(query: String, session: &mut Session) -> Result<Output, Errors> {
+    let state = parse_sql(query.as_str())?;
+    let query = UpdateQuery::format_stat(state)?;
+    session.run(|db, txn| {
+        let candidates = db
+            .index_candidates(&query.tb_name, &query.condition)
+            .unwrap_or(None);
+        let tb = db.get_table(query.tb_name.to_string())?;
+        let row_ixs = tb.matching_rows(&query.condition, candidates, &txn.snapshot)?;
+        db.update_rows(query.tb_name.to_string(), &row_ixs, &query.assignments, txn)?;
+        txn.mutations.push(Mutation::Update {
+            table: query.tb_name,
+            rows: row_ixs.len(),
+        });
+        Ok(Output::Affected(row_ixs.len()))
+    })
+}
+
+pub fn delete_data(query: String, session: &mut Session) -> Result<Output, Errors> {
+    let state = parse_sql(query.as_str())?;
+    let query = DeleteQuery::format_stat(state)?;
+    session.run(|db, txn| {
+        let candidates = db
+            .index_candidates(&query.tb_name, &query.condition)
+            .unwrap_or(None);
+        let tb = db.get_table(query.tb_name.to_string())?;
+        let row_ixs = tb.matching_rows(&query.condition, candidates, &txn.snapshot)?;
+        db.delete_rows(query.tb_name.to_string(), &row_ixs, txn)?;
+        txn.mutations.push(Mutation::Delete {
+            table: query.tb_name,
+            rows: row_ixs.len(),
+        });
+        Ok(Output::Affected(row_ixs.len()))
+    })
+}
+
+pub fn transaction(query: String, session: &mut Session) -> Result<Output, Errors> {
+    match TransactionQuery::new(query.as_str())? {
+        TransactionQuery::Begin(isolation) => session.begin(isolation)?,
+        TransactionQuery::Commit => session.commit()?,
+        TransactionQuery::Rollback => session.rollback()?,
+        TransactionQuery::Savepoint(name) => session.savepoint(name)?,
+        TransactionQuery::RollbackTo(name) => session.rollback_to(name)?,
+        TransactionQuery::Release(name) => session.release(name)?,
+        TransactionQuery::SetIsolation {
+            level,
+            session: all,
+        } => session.set_isolation(level, all),
+    };
+    Ok(Output::Done)
 }
 
-pub fn show_tb_data(query: String, db: &mut database::db::Database) {
+pub fn show_tb_data(query: String, session: &mut Session) -> Result<Output, Errors> {
     let vars = query.split(" ").collect::<Vec<&str>>();
     assert_eq!(vars.len(), 2);
     let tb_name = vars[1].to_string();
-    let tb = match db.get_table(tb_name) {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    tb.print_table_data();
+    session.run(|db, txn| Ok(Output::Rows(db.get_table(tb_name)?.all_data(&txn.snapshot))))
 }
 
-pub fn show_tb_info(query: String, db: &mut database::db::Database) {
+pub fn show_tb_info(query: String, session: &mut Session) -> Result<Output, Errors> {
     let vars = query.split(" ").collect::<Vec<&str>>();
     assert_eq!(vars.len(), 2);
     let tb_name = vars[1].to_string();
-    let tb = match db.get_table(tb_name) {
-        Ok(v) => v,
-        Err(err) => {
-            err.print();
-            return;
-        }
-    };
-    tb.show_info();
+    session.run(|db, _| {
+        let tb = db.get_table(tb_name)?;
+        let rows = tb
+            .columns
+            .iter()
+            .map(|c| {
+                vec![
+                    Value::Str(c.name.to_string()),
+                    Value::Str(c.datatype.data_type()),
+                    Value::Bool(c.is_pk),
+                    Value::Bool(c.is_nullable),
+                    c.default.clone().map_or(Value::Null, Value::Str),
+                ]
+            })
+            .collect::<Vec<Vec<Value>>>();
+        let columns = ResultSet {
+            columns: ["name", "datatype", "is_pk", "is_nullable", "default"]
+                .iter()
+                .map(|c| c.to_string())
+                .collect(),
+            types: vec![
+                DataType::String,
+                DataType::String,
+                DataType::Bool,
+                DataType::Bool,
+                DataType::String,
+            ],
+            rows,
+        };
+        let mut details = vec![];
+        if let Some(foreign_keys) = &tb.foreign_keys {
+            let fk_str = foreign_keys
+                .iter()
+                .map(|x| format!("{}---{}.{}", x.col_a, x.table, x.col_b))
+                .collect::<Vec<String>>();
+            details.push(("Foreign Keys".to_string(), fk_str));
+        }
+        if !tb.indexes.is_empty() {
+            let idx_str = tb
+                .indexes
+                .iter()
+                .map(|x| format!("{}({})", x.name, x.column))
+                .collect::<Vec<String>>();
+            details.push(("Indexes".to_string(), idx_str));
+        }
+        Ok(Output::Schema {
+            table: tb.name.to_string(),
+            columns,
+            details,
+        })
+    })
 }
 
-pub fn show_all_tbs(db: &mut database::db::Database) {
-    let tb_names = db
-        .tables
-        .iter()
-        .map(|x| {
-            let mut table_map = HashMap::new();
-            table_map.insert("Table Name".to_string(), x.name.clone());
-            table_map
-        })
-        .collect::<Vec<HashMap<String, String>>>();
-    let pt = PrettyTable::create(
-        db.db_name.to_string(),
-        vec!["Table Name".to_string()],
-        tb_names,
-    );
-    println!("{pt}")
+pub fn show_all_tbs(session: &mut Session) -> Result<Output, Errors> {
+    session.run(|db, _| {
+        Ok(Output::Rows(ResultSet {
+            columns: vec!["Table Name".to_string()],
+            types: vec![DataType::String],
+            rows: db
+                .tables
+                .iter()
+                .map(|x| vec![Value::Str(x.name.clone())])
+                .collect(),
+        }))
+    })
 }
@@ -1,97 +1,153 @@
-use crate::database;
+use crate::database::result::Output;
+use crate::database::session::Session;
 use crate::system::commands::r#type::{CommandType, SysCommand};
+use crate::system::commands::render::print_output;
 use crate::system::commands::{normal as NC, system as SC};
 use crate::system::dbs::DbSystem;
+use crate::system::errors::Errors;
 use ansi_term::Color;
 
-pub fn process_sys_command(query: String, db: &mut database::db::Database) {
-    let command_type = match SysCommand::new(query.clone()) {
-        Ok(v) => v,
-        Err(err) => {
-            print!("{}", err);
-            return;
+/// Runs one `sys` command, these only exist in the REPL.
+pub fn execute_sys(query: String, session: &mut Session) -> Result<Output, Errors> {
+    let message = match SysCommand::new(query.clone())? {
+        SysCommand::CreateDatabase => {
+            SC::create_db(query.as_str())?;
+            "Create Database Successfully!".to_string()
         }
-    };
-    match command_type {
-        SysCommand::CreateDatabase => match SC::create_db(query.as_str()) {
-            Ok(_) => {
-                println!(
-                    "{}",
-                    Color::Green.bold().paint("Create Database Successfully!")
-                )
-            }
-            Err(err) => {
-                println!("{}", Color::Red.bold().paint(format!("{:?}",err)))
-            }
-        },
-        SysCommand::UseDatabase => match SC::use_db(query, db) {
-            Ok(_) => {
-                println!(
-                    "{}",
-                    Color::Green.bold().paint("Change Database Successfully!")
-                )
-            }
-            Err(err) => {
-                println!("{}", Color::Red.bold().paint(format!("{:?}",err)))
-            }
-        },
-        SysCommand::DropDatabase => match SC::drop_db(query) {
-            Ok(_) => {
-                println!(
-                    "{}",
-                    Color::Green.bold().paint("Drop Database Successfully!")
-                )
-            }
-            Err(err) => {
-                println!("{}", Color::Red.bold().paint(format!("{:?}",err)))
-            }
-        },
-        SysCommand::ShowDatabases => match SC::show_databases() {
-            Ok(_) => {}
-            Err(err) => {
-                println!("{}", Color::Red.bold().paint(format!("{:?}",err)))
+        SysCommand::UseDatabase => {
+            let db_name = query.split(" ").nth(2).unwrap_or("").to_string();
+            let recovered = SC::use_db(query, session)?;
+            let mut lines = vec![];
+            if !recovered.is_empty() {
+                lines.push(format!(
+                    "Database {} was not shut down cleanly, replayed {} mutation(s) from the write-ahead log:",
+                    db_name,
+                    recovered.len()
+                ));
+                lines.extend(recovered.iter().map(|mutation| format!("  {}", mutation)));
             }
-        },
+            lines.push("Change Database Successfully!".to_string());
+            lines.join("\n")
+        }
+        SysCommand::DropDatabase => {
+            SC::drop_db(query, session)?;
+            "Drop Database Successfully!".to_string()
+        }
+        SysCommand::ShowDatabases => return SC::show_databases(),
         SysCommand::ChangePassword => {
             let vars = query.split(" ").collect::<Vec<&str>>();
             let mut sys = DbSystem::new();
-            match sys.change_pwd(vars[2].to_string()) {
-                Ok(_) => {
-                    println!(
-                        "{}",
-                        Color::Green.bold().paint("Change Password Successfully!")
-                    )
-                }
-                Err(err) => {
-                    println!("{}", Color::Red.bold().paint(format!("{:?}",err)))
-                }
-            }
+            sys.change_pwd(vars[2].to_string())
+                .map_err(|_| Errors::DiskSaveError)?;
+            "Change Password Successfully!".to_string()
         }
-        SysCommand::HelpTips => SC::help(query),
-        SysCommand::SysInfo => {}
-    }
+        SysCommand::HelpTips | SysCommand::SysInfo => return Ok(Output::Done),
+    };
+    Ok(Output::Message(message))
 }
 
-pub fn process_command(query: String, db: &mut database::db::Database) {
-    let command_type = match CommandType::new(query.clone()) {
-        Ok(v) => v,
-        Err(err) => {
-            println!("{}", err);
-            return;
+/// Runs one statement from the REPL and prints what it produced.
+pub fn process_command(query: String, session: &mut Session) {
+    let command_type = CommandType::new(query.clone());
+    if let Ok(CommandType::System) = command_type {
+        match SysCommand::new(query.clone()) {
+            Ok(SysCommand::HelpTips) => SC::help(query),
+            Ok(SysCommand::ShowDatabases) => match execute_sys(query, session) {
+                Ok(output) => print_output(output, "All Databases"),
+                Err(err) => println!("{}", Color::Red.bold().paint(format!("{:?}", err))),
+            },
+            Ok(_) => match execute_sys(query, session) {
+                Ok(output) => print_output(output, ""),
+                Err(err) => println!("{}", Color::Red.bold().paint(format!("{:?}", err))),
+            },
+            Err(err) => print!("{}", err),
         }
+        return;
+    }
+    let title = match command_type {
+        Ok(CommandType::ShowDB) => session.db_name().to_string(),
+        _ => "".to_string(),
     };
-    match command_type {
-        CommandType::CreateTable => NC::create_tb(query, db),
-        CommandType::Insert => NC::insert_data(query, db),
-        CommandType::Select => NC::select_data(query, db),
-        CommandType::Delete => NC::delete_data(query, db),
-        CommandType::Drop => NC::drop_tb(query, db),
-        CommandType::Update => NC::update_data(query, db),
-        CommandType::ShowTable => NC::show_tb_data(query, db),
-        CommandType::ShowDB => NC::show_all_tbs(db),
-        CommandType::TableInfo => NC::show_tb_info(query, db),
-        CommandType::System => {
-            process_sys_command(query, db);
+    match execute(query, session) {
+        Ok(output) => print_output(output, &title),
+        Err(err) => err.print(),
+    }
+}
+
+/// Runs one statement for any client. `sys` commands belong to the REPL and are
+/// rejected here.
+pub fn execute(query: String, session: &mut Session) -> Result<Output, Errors> {
+    match CommandType::new(query.clone())? {
+        CommandType::CreateTable => NC::create_tb(query, session),
+        CommandType::CreateIndex => NC::create_idx(query, session),
+        CommandType::Insert => NC::insert_data(query, session),
+        CommandType::Select => NC::select_data(query, session),
+        CommandType::Delete => NC::delete_data(query, session),
+        CommandType::Drop => NC::drop_tb(query, session),
+        CommandType::DropIndex => NC::drop_idx(query, session),
+        CommandType::Update => NC::update_data(query, session),
+        CommandType::ShowTable => NC::show_tb_data(query, session),
+        CommandType::ShowDB => NC::show_all_tbs(session),
+        CommandType::TableInfo => NC::show_tb_info(query, session),
+        CommandType::Transaction => NC::transaction(query, session),
+        CommandType::System => Err(Errors::InvalidCommand),
+    }
+}
+
+#[test]
+fn test_show_commands_return_data() {
+    use crate::database::base::Value;
+    use crate::system::utils::TempDb;
+
+    let temp = TempDb::new("show");
+    let db_name = temp.name.as_str();
+    let mut session = Session::new();
+    let output = execute_sys(format!("sys usedb {}", db_name), &mut session).unwrap();
+    assert_eq!(
+        output,
+        Output::Message("Change Database Successfully!".to_string())
+    );
+    execute(
+        "CREATE TABLE t (id INT PRIMARY KEY, name TEXT)".to_string(),
+        &mut session,
+    )
+    .unwrap();
+    execute(
+        "INSERT INTO t (id, name) VALUES (1, 'a'), (2, 'b')".to_string(),
+        &mut session,
+    )
+    .unwrap();
+    execute("DELETE FROM t WHERE id = 2".to_string(), &mut session).unwrap();
+
+    match execute("showtb t".to_string(), &mut session).unwrap() {
+        Output::Rows(result) => {
+            assert_eq!(result.columns, vec!["id", "name"]);
+            assert_eq!(
+                result.rows,
+                vec![vec![Value::Int(1), Value::Str("a".to_string())]]
+            );
         }
+        output => panic!("unexpected {:?}", output),
     }
+    match execute("showdb".to_string(), &mut session).unwrap() {
+        Output::Rows(result) => assert_eq!(result.rows, vec![vec![Value::Str("t".to_string())]]),
+        output => panic!("unexpected {:?}", output),
+    }
+    match execute("tableinfo t".to_string(), &mut session).unwrap() {
+        Output::Schema { table, columns, .. } => {
+            assert_eq!(table, "t");
+            assert_eq!(columns.get(0, "name"), Some(&Value::Str("id".to_string())));
+            assert_eq!(columns.get(0, "is_pk"), Some(&Value::Bool(true)));
+        }
+        output => panic!("unexpected {:?}", output),
+    }
+    match execute_sys("sys showdb".to_string(), &mut session).unwrap() {
+        Output::Rows(result) => {
+            assert!(result.rows.contains(&vec![Value::Str(db_name.to_string())]))
+        }
+        output => panic!("unexpected {:?}", output),
+    }
+
+    drop(session);
+    execute_sys(format!("sys dropdb {}", db_name), &mut Session::new()).unwrap();
 }
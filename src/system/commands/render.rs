@@ -0,0 +1,107 @@
+use crate::database::base::Value;
+use crate::database::result::{Output, ResultSet};
+use ansi_term::Color;
+use prettytable::{Attr, Cell, Row, Table as PTable};
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Formatter;
+
+pub struct PrettyTable {
+    pub name: String,
+    pub header: Vec<String>,
+    pub rows: Vec<HashMap<String, String>>,
+    pub others: Vec<(String, Vec<String>)>,
+}
+
+impl PrettyTable {
+    pub fn create(
+        name: String,
+        header: Vec<String>,
+        rows: Vec<HashMap<String, String>>,
+    ) -> PrettyTable {
+        PrettyTable {
+            name,
+            header,
+            rows,
+            others: vec![],
+        }
+    }
+
+    /// Table of a result set, NULLs are left blank.
+    pub fn from_result(name: String, result: ResultSet) -> PrettyTable {
+        let rows = result
+            .rows
+            .iter()
+            .map(|row| {
+                result
+                    .columns
+                    .iter()
+                    .zip(row)
+                    .map(|(col, val)| match val {
+                        Value::Null => (col.to_string(), "".to_string()),
+                        _ => (col.to_string(), val.to_string()),
+                    })
+                    .collect::<HashMap<String, String>>()
+            })
+            .collect::<Vec<HashMap<String, String>>>();
+        PrettyTable::create(name, result.columns, rows)
+    }
+
+    pub fn add_more(&mut self, key: String, val: Vec<String>) {
+        self.others.push((key, val));
+    }
+}
+
+impl fmt::Display for PrettyTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut pt = PTable::new();
+        let header_row = Row::new(
+            self.header
+                .iter()
+                .map(|col| Cell::new(col).with_style(Attr::Bold))
+                .collect::<Vec<Cell>>(),
+        );
+        pt.add_row(header_row);
+        for row in &self.rows {
+            pt.add_row(Row::new(
+                self.header
+                    .iter()
+                    .map(|col| Cell::new(row.get(col).unwrap()))
+                    .collect::<Vec<Cell>>(),
+            ));
+        }
+        let col_num = self.header.len().saturating_sub(1);
+        for (k, v) in &self.others {
+            pt.add_row(Row::new(vec![
+                Cell::new(k.as_str()).style_spec("Fb"),
+                Cell::new(v.join(" ").as_str()).style_spec(format!("H{}", col_num).as_str()),
+            ]));
+        }
+        if self.name.is_empty() {
+            write!(f, "{}", pt)
+        } else {
+            write!(f, "{}\n{}", self.name, pt)
+        }
+    }
+}
+
+/// Prints what a statement produced, rows get `title` above them.
+pub fn print_output(output: Output, title: &str) {
+    match output {
+        Output::Rows(result) => println!("{}", PrettyTable::from_result(title.to_string(), result)),
+        Output::Affected(affected) => println!("Number of affected rows: {}", affected),
+        Output::Schema {
+            table,
+            columns,
+            details,
+        } => {
+            let mut pt = PrettyTable::from_result(table, columns);
+            for (key, val) in details {
+                pt.add_more(key, val);
+            }
+            println!("{pt}");
+        }
+        Output::Message(message) => println!("{}", Color::Green.bold().paint(message)),
+        Output::Done => {}
+    }
+}
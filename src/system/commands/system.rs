@@ -1,72 +1,152 @@
+use crate::database::base::{DataType, Value};
 use crate::database::db::Database;
-use crate::database::table::PrettyTable;
+use crate::database::result::{Output, ResultSet};
+use crate::database::session::Session;
+use crate::storage::lock::{FileLock, LockMode};
+use crate::storage::wal::{Mutation, Wal};
 use crate::system::errors::Errors;
-use ansi_term::Color;
-use std::collections::HashMap;
-use std::fs;
 use crate::system::tips;
+use ansi_term::Color;
+use std::fs::{self, OpenOptions};
+use std::io::ErrorKind;
 
 fn database_exists(db_name: &str) -> bool {
     let file_path = format!("sql_files/{}.bin", db_name);
     fs::metadata(file_path).is_ok()
 }
 
-pub fn create_db(command: &str) -> Result<(), Errors> {
-    let vars: Vec<&str> = command.split_whitespace().collect();
-    if vars.len() != 3 {
+// the database a `sys` command names, letters, digits and `_` keep its file in `sql_files`
+fn database_name(command: &str) -> Result<String, Errors> {
+    let vars = command.split_whitespace().collect::<Vec<&str>>();
+    if vars.len() < 3 {
         return Err(Errors::InvalidExpression);
     }
+    let db_name = vars[2..].join(" ");
+    if !db_name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(Errors::InvalidDatabaseName(db_name));
+    }
+    Ok(db_name)
+}
 
-    let db_name = vars[2];
+/// Creates an empty database, one whose file or wal is already there is left alone.
+pub fn create_db(command: &str) -> Result<(), Errors> {
+    let db_name = database_name(command)?;
+    let file_path = format!("sql_files/{}.bin", db_name);
+    if fs::metadata(Wal::path_for(&file_path)).is_ok() {
+        return Err(Errors::DatabaseExisted(db_name));
+    }
+    // claims the name, two processes creating it at once cannot both get here
+    match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&file_path)
+    {
+        Ok(_) => {}
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+            return Err(Errors::DatabaseExisted(db_name))
+        }
+        Err(_) => return Err(Errors::FileSystemError),
+    }
     let mut db = Database::new();
-    db.set_dbname(db_name.to_string());
-    Ok(db.save_disk().map_err(|_| Errors::DiskSaveError)?)
+    db.set_dbname(db_name);
+    let created = db.lock_file(LockMode::Exclusive).and_then(|_| {
+        db.commit(Mutation::CreateDatabase)
+            .map_err(|_| Errors::DiskSaveError)
+    });
+    if created.is_err() {
+        drop(db);
+        let _ = fs::remove_file(Wal::path_for(&file_path));
+        let _ = fs::remove_file(&file_path);
+    }
+    created
 }
 
-pub fn use_db(command: String, db: &mut Database) -> Result<(), Errors> {
-    let vars = command.split(" ").collect::<Vec<&str>>();
-    assert_eq!(vars.len(), 3);
-    let db_name = vars[2];
-    let file_path = format!("sql_files/{}.bin", db_name);
-    if !database_exists(db_name) {
+/// Switches `session` to the database, returning the mutations replayed from its
+/// write-ahead log if it was not shut down cleanly.
+pub fn use_db(command: String, session: &mut Session) -> Result<Vec<Mutation>, Errors> {
+    let db_name = database_name(&command)?;
+    if !database_exists(&db_name) {
         return Err(Errors::DatabaseNotExisted);
     }
-    Ok(db
-        .load_from_disk(file_path.as_str())
-        .map_err(|_| Errors::FileSystemError)?)
+    session.use_db(&db_name)
 }
 
-pub fn drop_db(command: String) -> Result<(), Errors> {
-    let vars = command.split(" ").collect::<Vec<&str>>();
-    assert_eq!(vars.len(), 3);
-    let db_name = vars[2];
+/// Removes a database no other session of this process has open, `session` leaves it.
+pub fn drop_db(command: String, session: &mut Session) -> Result<(), Errors> {
+    let db_name = database_name(&command)?;
     let file_path = format!("sql_files/{}.bin", db_name);
-    Ok(fs::remove_file(file_path).map_err(|_| Errors::DatabaseNotExisted)?)
+    if !database_exists(&db_name) {
+        return Err(Errors::DatabaseNotExisted);
+    }
+    if session.used_by_others(&db_name) {
+        return Err(Errors::DatabaseInUse(db_name));
+    }
+    if session.db_name() == db_name && session.in_transaction() {
+        return Err(Errors::TransactionStarted);
+    }
+    // the lock is kept until the file is gone
+    let mut lock = FileLock::open(file_path.as_str()).map_err(|_| Errors::FileSystemError)?;
+    lock.acquire(LockMode::Exclusive)
+        .map_err(|_| Errors::DatabaseLocked(db_name.clone()))?;
+    fs::remove_file(&file_path).map_err(|_| Errors::DatabaseNotExisted)?;
+    let _ = fs::remove_file(Wal::path_for(&file_path));
+    if session.db_name() == db_name {
+        session.close_db();
+    }
+    Ok(())
+}
+
+/// Runs `CREATE DATABASE`, `DROP DATABASE` and `USE`, which the REPL knows as `sys`
+/// commands. Returns `None` for any other statement.
+pub fn database_statement(sql: &str, session: &mut Session) -> Option<Result<(), Errors>> {
+    let lower = sql.to_lowercase();
+    let words = lower.split_whitespace().collect::<Vec<&str>>();
+    let result = match words.as_slice() {
+        ["create", "database", _] => create_db(&format!("sys createdb {}", word(sql, 2))),
+        ["drop", "database", _] => drop_db(format!("sys dropdb {}", word(sql, 2)), session),
+        ["use", _] => use_db(format!("sys usedb {}", word(sql, 1)), session).map(|_| ()),
+        _ => return None,
+    };
+    Some(result)
+}
+
+// the `n`th word of a statement with its case kept, without quotes
+fn word(sql: &str, n: usize) -> &str {
+    sql.split_whitespace()
+        .nth(n)
+        .unwrap_or("")
+        .trim_matches(|c| c == '`' || c == '"')
 }
 
-pub fn show_databases() -> std::io::Result<()> {
+/// Names of the databases in `sql_files`.
+pub fn database_names() -> std::io::Result<Vec<String>> {
     let files = fs::read_dir("sql_files")?;
 
     let mut databases = Vec::new();
 
-    for file in files {
-        if let Ok(entry) = file {
-            if let Some(file_name) = entry.file_name().to_str() {
-                if file_name.ends_with(".bin") {
-                    let db_name = file_name.trim_end_matches(".bin").to_string();
-                    databases.push(db_name);
-                }
+    for entry in files.flatten() {
+        if let Some(file_name) = entry.file_name().to_str() {
+            if file_name.ends_with(".bin") {
+                let db_name = file_name.trim_end_matches(".bin").to_string();
+                databases.push(db_name);
             }
         }
     }
-    let db_header = vec!["Database".to_string()];
-    let mut col_values: HashMap<String, Vec<String>> = HashMap::new();
-    col_values.insert("Database".to_string(), databases);
-    let pt = PrettyTable::new("All Databases".to_string(), db_header, col_values);
-    println!("{}", pt);
-    Ok(())
+    databases.sort();
+    Ok(databases)
 }
 
+pub fn show_databases() -> Result<Output, Errors> {
+    let databases = database_names().map_err(|_| Errors::FileSystemError)?;
+    Ok(Output::Rows(ResultSet {
+        columns: vec!["Database".to_string()],
+        types: vec![DataType::String],
+        rows: databases.into_iter().map(|x| vec![Value::Str(x)]).collect(),
+    }))
+}
 
 pub fn help(command: String) {
     let vars = command.split(" ").collect::<Vec<&str>>();
@@ -74,10 +154,13 @@ pub fn help(command: String) {
         println!("{}", Color::Yellow.bold().paint("Table Command"));
         println!("{}", tips::create_table(false));
         println!("{}", tips::drop_table(false));
+        println!("{}", tips::create_index(false));
+        println!("{}", tips::drop_index(false));
         println!("{}", tips::select_data(false));
         println!("{}", tips::insert_data(false));
         println!("{}", tips::delete_data(false));
         println!("{}", tips::update_data(false));
+        println!("{}", tips::transaction());
         println!("{}", Color::Yellow.bold().paint("System Command"));
         println!("{}", tips::create_db());
         println!("{}", tips::use_db());
@@ -85,11 +168,99 @@ pub fn help(command: String) {
         println!("{}", tips::show_dbs());
         println!("{}", tips::change_pwd());
     } else if vars.len() == 3 {
-        if vars[2].eq("create") { println!("{}", tips::create_table(true)) }
-        if vars[2].eq("drop") { println!("{}", tips::drop_table(true)) }
-        if vars[2].eq("select") { println!("{}", tips::select_data(true)) }
-        if vars[2].eq("insert") { println!("{}", tips::insert_data(true)) }
-        if vars[2].eq("delete") { println!("{}", tips::delete_data(true)) }
-        if vars[2].eq("update") { println!("{}", tips::update_data(true)) }
+        if vars[2].eq("create") {
+            println!("{}", tips::create_table(true))
+        }
+        if vars[2].eq("drop") {
+            println!("{}", tips::drop_table(true))
+        }
+        if vars[2].eq("index") {
+            println!("{}", tips::create_index(true));
+            println!("{}", tips::drop_index(true))
+        }
+        if vars[2].eq("select") {
+            println!("{}", tips::select_data(true))
+        }
+        if vars[2].eq("insert") {
+            println!("{}", tips::insert_data(true))
+        }
+        if vars[2].eq("delete") {
+            println!("{}", tips::delete_data(true))
+        }
+        if vars[2].eq("update") {
+            println!("{}", tips::update_data(true))
+        }
     }
 }
+
+#[test]
+fn test_database_names() {
+    let mut session = Session::new();
+    for name in ["../victim", "a/b", "a b", "x.bin"] {
+        assert!(matches!(
+            create_db(&format!("sys createdb {}", name)),
+            Err(Errors::InvalidDatabaseName(_))
+        ));
+        assert!(matches!(
+            drop_db(format!("sys dropdb {}", name), &mut session),
+            Err(Errors::InvalidDatabaseName(_))
+        ));
+        assert!(matches!(
+            use_db(format!("sys usedb {}", name), &mut session),
+            Err(Errors::InvalidDatabaseName(_))
+        ));
+    }
+    assert!(matches!(
+        drop_db("sys dropdb".to_string(), &mut session),
+        Err(Errors::InvalidExpression)
+    ));
+    assert!(matches!(
+        database_statement("DROP DATABASE ../victim", &mut session),
+        Some(Err(Errors::InvalidDatabaseName(_)))
+    ));
+}
+
+#[test]
+fn test_create_existing_database() {
+    use crate::connection::Connection;
+    use crate::system::utils::TempDb;
+
+    let temp = TempDb::new("create_twice");
+    let db_name = temp.name.as_str();
+    let mut conn = Connection::open(db_name).unwrap();
+    conn.execute("CREATE TABLE t (id INT PRIMARY KEY)").unwrap();
+    conn.execute("INSERT INTO t (id) VALUES (1)").unwrap();
+
+    assert!(matches!(
+        conn.execute(&format!("CREATE DATABASE {}", db_name)),
+        Err(Errors::DatabaseExisted(_))
+    ));
+    assert!(matches!(
+        create_db(&format!("sys createdb {}", db_name)),
+        Err(Errors::DatabaseExisted(_))
+    ));
+    assert_eq!(conn.execute("SELECT id FROM t").unwrap(), 1);
+    // a wal left without its database file still keeps the name
+    let orphan = TempDb::named("create_wal");
+    fs::write(Wal::path_for(&orphan.path()), b"").unwrap();
+    assert!(matches!(
+        create_db(&format!("sys createdb {}", orphan.name)),
+        Err(Errors::DatabaseExisted(_))
+    ));
+    assert!(!database_exists(&orphan.name));
+
+    // another session still reads the database, it is not dropped under it
+    let mut other = Session::new();
+    assert!(matches!(
+        drop_db(format!("sys dropdb {}", db_name), &mut other),
+        Err(Errors::DatabaseInUse(_))
+    ));
+    drop(conn);
+    let mut conn = Connection::open(db_name).unwrap();
+    assert_eq!(conn.execute("SELECT id FROM t").unwrap(), 1);
+
+    // the session using it may drop it and leaves it
+    conn.execute(&format!("DROP DATABASE {}", db_name)).unwrap();
+    assert_eq!(conn.database(), "");
+    assert!(!database_exists(db_name));
+}
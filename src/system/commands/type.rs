@@ -2,30 +2,41 @@ use crate::system::errors::Errors;
 
 pub enum CommandType {
     CreateTable,
+    CreateIndex,
     Insert,
     Select,
     Delete,
     Drop,
+    DropIndex,
     Update,
     ShowTable,
     ShowDB,
     TableInfo,
+    Transaction,
     System,
 }
 
 impl CommandType {
     pub fn new(command: String) -> Result<CommandType, Errors> {
         let vars = command.split(" ").collect::<Vec<&str>>();
+        let object = vars.get(1).map(|v| v.to_lowercase()).unwrap_or_default();
         match vars[0].to_lowercase().as_str() {
+            "create" if object == "index" => Ok(CommandType::CreateIndex),
             "create" => Ok(CommandType::CreateTable),
             "insert" => Ok(CommandType::Insert),
-            "select" => Ok(CommandType::Select),
+            "select" | "with" => Ok(CommandType::Select),
+            // a set operation may start with a query in parentheses
+            word if word.starts_with('(') => Ok(CommandType::Select),
             "delete" => Ok(CommandType::Delete),
+            "drop" if object == "index" => Ok(CommandType::DropIndex),
             "drop" => Ok(CommandType::Drop),
             "update" => Ok(CommandType::Update),
             "showtb" => Ok(CommandType::ShowTable),
             "showdb" => Ok(CommandType::ShowDB),
             "tableinfo" => Ok(CommandType::TableInfo),
+            "begin" | "start" | "commit" | "rollback" | "savepoint" | "release" | "set" => {
+                Ok(CommandType::Transaction)
+            }
             "sys" => Ok(CommandType::System),
             _ => Err(Errors::InvalidCommand),
         }
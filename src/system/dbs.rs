@@ -1,6 +1,6 @@
+use crate::storage::lock::{FileLock, LockMode};
 use crate::system::pwd::Password;
 use serde::{Deserialize, Serialize};
-use std::fs::File;
 use std::io::Write;
 use std::{fs, io};
 
@@ -11,13 +11,25 @@ pub struct DbSystem {
     sys_password: Password,
 }
 
+impl Default for DbSystem {
+    fn default() -> Self {
+        DbSystem::new()
+    }
+}
+
 impl DbSystem {
     pub fn load_cfg(&mut self) -> io::Result<()> {
-        let file = File::open("cfg.bin")?;
-        *self = serde_json::from_reader(file)?;
+        let mut lock = FileLock::open("cfg.bin")?;
+        lock.acquire(LockMode::Shared)?;
+        *self = serde_json::from_reader(lock.file())?;
         Ok(())
     }
     pub fn new() -> DbSystem {
+        DbSystem::load().unwrap()
+    }
+
+    /// The system of `cfg.bin`, like `new` but failing with an error.
+    pub fn load() -> io::Result<DbSystem> {
         let mut sys = DbSystem {
             sys_name: "".to_string(),
             sys_username: "".to_string(),
@@ -25,20 +37,24 @@ impl DbSystem {
                 hashed_password: "".to_string(),
             },
         };
-        sys.load_cfg().unwrap();
-        sys
+        sys.load_cfg()?;
+        Ok(sys)
     }
 
     pub fn login(&self, username: String, password: String) -> bool {
         let mut status = true;
         status &= username == self.sys_username;
-        status &= self.sys_password.check_password(&*password.to_string());
+        status &= self.sys_password.check_password(&password.to_string());
         status
     }
 
     pub fn update_info(&self) -> io::Result<()> {
         let serialized_data = serde_json::to_string(&self)?;
-        let mut file = File::create("cfg.bin")?;
+        // truncated only once the lock is held, a reader never sees a half written file
+        let mut lock = FileLock::open("cfg.bin")?;
+        lock.acquire(LockMode::Exclusive)?;
+        let file = lock.file();
+        file.set_len(0)?;
         file.write_all(serialized_data.as_bytes())?;
         Ok(())
     }
@@ -52,20 +68,18 @@ impl DbSystem {
         let file_path = "cfg.bin".to_string();
         if fs::metadata(file_path).is_ok() {
             return;
-        } else {
-            eprintln!("Initializing the SimpleDB System.");
-            DbSystem {
-                sys_name: "SimpleDB System Based on Rust".to_string(),
-                sys_username: "root".to_string(),
-                sys_password: Password::new("123456"),
-            }
-                .update_info()
-                .unwrap();
-            if !fs::metadata("sql_files").is_ok() {
-                fs::create_dir("sql_files").unwrap();
-            }
-            eprintln!("Complete initialization.\nDefault username root, password 123456");
-            return;
         }
+        eprintln!("Initializing the SimpleDB System.");
+        DbSystem {
+            sys_name: "SimpleDB System Based on Rust".to_string(),
+            sys_username: "root".to_string(),
+            sys_password: Password::new("123456"),
+        }
+        .update_info()
+        .unwrap();
+        if fs::metadata("sql_files").is_err() {
+            fs::create_dir("sql_files").unwrap();
+        }
+        eprintln!("Complete initialization.\nDefault username root, password 123456");
     }
 }
@@ -1,5 +1,5 @@
 use std::fmt;
-use std::fmt::{Formatter};
+use std::fmt::Formatter;
 
 #[derive(Debug)]
 pub enum Errors {
@@ -7,6 +7,8 @@ pub enum Errors {
     InvalidExpression,
     ElementNotFound,
     DatabaseNotExisted,
+    DatabaseExisted(String),
+    DatabaseInUse(String),
     DiskSaveError,
     FileSystemError,
     ParseSQLError,
@@ -14,6 +16,31 @@ pub enum Errors {
     TableNotExisted(String),
     TableExisted(String),
     InvalidColumnType,
+    ColumnNotExisted(String),
+    IndexNotExisted(String),
+    IndexExisted(String),
+    IndexRequired(String),
+    PrimaryKeyExisted(String),
+    TransactionStarted,
+    TransactionNotStarted,
+    SavepointNotExisted(String),
+    WriteConflict(String),
+    SchemaChangeInTransaction,
+    DatabaseLocked(String),
+    InvalidOrderPosition(String),
+    ColumnNotGrouped(String),
+    TypeMismatch(String),
+    DivisionByZero,
+    NumericOverflow,
+    FunctionNotExisted(String),
+    InvalidCast(String),
+    InvalidSubquery(String),
+    ColumnCountMismatch(String),
+    RecursionLimitExceeded(String, usize),
+    InvalidDatabaseName(String),
+    NullValue(String),
+    AmbiguousColumn(String),
+    RowTooLarge(String),
 }
 
 impl Errors {
@@ -22,20 +49,105 @@ impl Errors {
     }
 }
 
+impl std::error::Error for Errors {}
+
 impl fmt::Display for Errors {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Errors::UnimplementedOperation => { f.write_str("This operation is unimplemented.\n") }
-            Errors::InvalidExpression => { f.write_str("Expression is invalid.\n") }
-            Errors::ElementNotFound => { f.write_str("ElementNotFound.\n") }
-            Errors::DatabaseNotExisted => { f.write_str("DatabaseNotExisted.\n") }
-            Errors::DiskSaveError => { f.write_str("DiskSaveError.\n") }
-            Errors::FileSystemError => { f.write_str("FileSystemError.\n") }
-            Errors::ParseSQLError => { f.write_str("ParseSQLError.\n") }
-            Errors::InvalidCommand => { f.write_str("InvalidCommand.\n") }
-            Errors::TableNotExisted(s) => { f.write_str(format!("Table {} is not existed.\n", s).as_str()) }
-            Errors::TableExisted(s) => { f.write_str(format!("Table {} is existed.\n", s).as_str()) }
-            Errors::InvalidColumnType => { f.write_str("InvalidColumnType\n") }
+            Errors::UnimplementedOperation => f.write_str("This operation is unimplemented.\n"),
+            Errors::InvalidExpression => f.write_str("Expression is invalid.\n"),
+            Errors::ElementNotFound => f.write_str("ElementNotFound.\n"),
+            Errors::DatabaseNotExisted => f.write_str("DatabaseNotExisted.\n"),
+            Errors::DatabaseExisted(s) => {
+                f.write_str(format!("Database {} is existed.\n", s).as_str())
+            }
+            Errors::DatabaseInUse(s) => {
+                f.write_str(format!("Database {} is used by another session.\n", s).as_str())
+            }
+            Errors::DiskSaveError => f.write_str("DiskSaveError.\n"),
+            Errors::FileSystemError => f.write_str("FileSystemError.\n"),
+            Errors::ParseSQLError => f.write_str("ParseSQLError.\n"),
+            Errors::InvalidCommand => f.write_str("InvalidCommand.\n"),
+            Errors::TableNotExisted(s) => {
+                f.write_str(format!("Table {} is not existed.\n", s).as_str())
+            }
+            Errors::TableExisted(s) => f.write_str(format!("Table {} is existed.\n", s).as_str()),
+            Errors::InvalidColumnType => f.write_str("InvalidColumnType\n"),
+            Errors::ColumnNotExisted(s) => {
+                f.write_str(format!("Column {} is not existed.\n", s).as_str())
+            }
+            Errors::IndexNotExisted(s) => {
+                f.write_str(format!("Index {} is not existed.\n", s).as_str())
+            }
+            Errors::IndexExisted(s) => f.write_str(format!("Index {} is existed.\n", s).as_str()),
+            Errors::IndexRequired(s) => {
+                f.write_str(format!("Index {} is required by the primary key.\n", s).as_str())
+            }
+            Errors::PrimaryKeyExisted(s) => {
+                f.write_str(format!("Primary key {} is existed.\n", s).as_str())
+            }
+            Errors::TransactionStarted => f.write_str("A transaction is already in progress.\n"),
+            Errors::TransactionNotStarted => f.write_str("No transaction is in progress.\n"),
+            Errors::SavepointNotExisted(s) => {
+                f.write_str(format!("Savepoint {} is not existed.\n", s).as_str())
+            }
+            Errors::WriteConflict(s) => f.write_str(
+                format!(
+                    "Rows of table {} were changed by a concurrent transaction.\n",
+                    s
+                )
+                .as_str(),
+            ),
+            Errors::SchemaChangeInTransaction => {
+                f.write_str("Schema changes are not allowed inside a transaction.\n")
+            }
+            Errors::DatabaseLocked(s) => {
+                f.write_str(format!("Database {} is locked by another process.\n", s).as_str())
+            }
+            Errors::InvalidOrderPosition(s) => {
+                f.write_str(format!("ORDER BY position {} is not in select list.\n", s).as_str())
+            }
+            Errors::ColumnNotGrouped(s) => f.write_str(
+                format!(
+                    "Column {} must appear in the GROUP BY clause or be used in an aggregate function.\n",
+                    s
+                )
+                .as_str(),
+            ),
+            Errors::TypeMismatch(s) => {
+                f.write_str(format!("Operator does not exist: {}.\n", s).as_str())
+            }
+            Errors::DivisionByZero => f.write_str("Division by zero.\n"),
+            Errors::NumericOverflow => f.write_str("Numeric value out of range.\n"),
+            Errors::FunctionNotExisted(s) => {
+                f.write_str(format!("Function {} is not existed.\n", s).as_str())
+            }
+            Errors::InvalidCast(s) => f.write_str(format!("Cannot cast {}.\n", s).as_str()),
+            Errors::InvalidSubquery(s) => {
+                f.write_str(format!("Subquery {}.\n", s).as_str())
+            }
+            Errors::ColumnCountMismatch(s) => f.write_str(
+                format!("Each {} query must have the same number of columns.\n", s).as_str(),
+            ),
+            Errors::AmbiguousColumn(s) => {
+                f.write_str(format!("Column {} is ambiguous.\n", s).as_str())
+            }
+            Errors::RowTooLarge(s) => f.write_str(
+                format!("Row of table {} does not fit in a page of 4096 bytes.\n", s).as_str(),
+            ),
+            Errors::NullValue(s) => {
+                f.write_str(format!("Column {} cannot be NULL.\n", s).as_str())
+            }
+            Errors::InvalidDatabaseName(s) => f.write_str(
+                format!(
+                    "Database name {} is invalid, use letters, digits and _.\n",
+                    s
+                )
+                .as_str(),
+            ),
+            Errors::RecursionLimitExceeded(s, n) => f.write_str(
+                format!("Recursive query {} did not finish within {} rounds.\n", s, n).as_str(),
+            ),
         }
     }
-}
\ No newline at end of file
+}
@@ -1,6 +1,6 @@
 pub(crate) mod commands;
 pub(crate) mod dbs;
-pub(crate) mod pwd;
-pub(crate) mod utils;
 pub(crate) mod errors;
+pub(crate) mod pwd;
 pub(crate) mod tips;
+pub(crate) mod utils;
@@ -37,15 +37,64 @@ pub fn drop_table(sample: bool) -> String {
     result
 }
 
+pub fn create_index(sample: bool) -> String {
+    let mut result = format!(
+        "{} {} {} {} {}",
+        Color::Red.bold().paint("Create index"),
+        Color::Green.paint("index_name"),
+        Color::Red.bold().paint("on"),
+        Color::Green.paint("table"),
+        Color::Green.paint("(column)"),
+    );
+    if sample {
+        result = result
+            + "\n"
+            + format!(
+                "{} {} {} {} {}",
+                Color::Red.bold().paint("Create index"),
+                Color::Green.paint("articles_user"),
+                Color::Red.bold().paint("on"),
+                Color::Green.paint("articles"),
+                Color::Green.paint("(user_id)"),
+            )
+            .as_str();
+    }
+    result
+}
+
+pub fn drop_index(sample: bool) -> String {
+    let mut result = format!(
+        "{} {}",
+        Color::Red.bold().paint("Drop index"),
+        Color::Green.paint("index_name")
+    );
+    if sample {
+        result = result
+            + "\n"
+            + format!(
+                "{} {}",
+                Color::Red.bold().paint("Drop index"),
+                Color::Green.paint("articles_user")
+            )
+            .as_str();
+    }
+    result
+}
+
 pub fn select_data(sample: bool) -> String {
     let mut result = format!(
-        "{} {} {} {} {} {}",
+        "{} {} {} {} {} {} {} {} {} {} {}",
+        Color::RGB(240, 240, 240).paint("<with [recursive] name [(fields)] as (select ...)>"),
         Color::Red.bold().paint("Select"),
-        Color::Green.paint("projections"),
+        Color::Green.paint("[distinct [on (expressions)]] expressions [as alias]"),
         Color::Red.bold().paint("from"),
-        Color::Green.paint("table"),
-        Color::RGB(240, 240, 240).paint("<join another table on field1=field2>"),
-        Color::RGB(240, 240, 240).paint("<where conditions>")
+        Color::Green.paint("table|(select ...) as alias"),
+        Color::RGB(240, 240, 240).paint("<[left|right|full] join another table on field1=field2>"),
+        Color::RGB(240, 240, 240).paint("<where conditions>"),
+        Color::RGB(240, 240, 240).paint("<group by fields having conditions>"),
+        Color::RGB(240, 240, 240).paint("<union|intersect|except [all] select ...>"),
+        Color::RGB(240, 240, 240).paint("<order by fields [asc|desc] [nulls first|last]>"),
+        Color::RGB(240, 240, 240).paint("<limit n offset m>")
     );
     if sample {
         result = result
@@ -134,6 +183,21 @@ pub fn update_data(sample: bool) -> String {
     result
 }
 
+pub fn transaction() -> String {
+    format!(
+        "{} | {} | {} | {} {} | {} {} | {} {}",
+        Color::Red.bold().paint("Begin"),
+        Color::Red.bold().paint("Commit"),
+        Color::Red.bold().paint("Rollback"),
+        Color::Red.bold().paint("Savepoint"),
+        Color::Green.paint("name"),
+        Color::Red.bold().paint("Rollback to"),
+        Color::Green.paint("name"),
+        Color::Red.bold().paint("Set transaction isolation level"),
+        Color::Green.paint("read committed/snapshot"),
+    )
+}
+
 pub fn create_db() -> String {
     format!(
         "{} {}",
@@ -1,11 +1,7 @@
 use regex::Regex;
 
 pub fn custom_strip_prefix<'a>(s: &'a str, prefix: &str) -> &'a str {
-    if s.starts_with(prefix) {
-        &s[prefix.len()..]
-    } else {
-        s
-    }
+    s.strip_prefix(prefix).unwrap_or(s)
 }
 
 pub fn custom_strip_suffix<'a>(s: &'a str, suffix: &str) -> &'a str {
@@ -21,8 +17,75 @@ pub fn custom_strip<'a>(s: &'a str, replace: &str) -> &'a str {
     custom_strip_prefix(custom_strip_suffix(s, replace), replace)
 }
 
-pub fn wildcard_match(pattern: &str, input: &str) -> bool {
-    let pattern = pattern.replace("%", ".*").replace("_", ".");
-    let regex = Regex::new(&format!("^{}$", pattern)).unwrap();
-    regex.is_match(input)
+/// `LIKE` matching: `%` is any run of characters, `_` one character and `escape` makes
+/// the next character literal.
+pub fn wildcard_match(
+    pattern: &str,
+    input: &str,
+    case_insensitive: bool,
+    escape: Option<char>,
+) -> bool {
+    let mut regex = String::from(if case_insensitive { "(?is)^" } else { "(?s)^" });
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if Some(c) == escape => {
+                if let Some(next) = chars.next() {
+                    regex.push_str(&regex::escape(&next.to_string()));
+                }
+            }
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex)
+        .map(|re| re.is_match(input))
+        .unwrap_or(false)
+}
+
+/// A database of `sql_files` for a test, named after the test and this process. Its
+/// files are removed when it goes out of scope, even if the test panics.
+#[cfg(test)]
+pub struct TempDb {
+    pub name: String,
+}
+
+#[cfg(test)]
+impl TempDb {
+    /// Creates the database `simpledb_<prefix>_<pid>`.
+    pub fn new(prefix: &str) -> TempDb {
+        let db = TempDb::named(prefix);
+        crate::system::commands::system::create_db(&format!("sys createdb {}", db.name)).unwrap();
+        db
+    }
+
+    /// Only picks the name, for tests that create the database themselves.
+    pub fn named(prefix: &str) -> TempDb {
+        std::fs::create_dir_all("sql_files").unwrap();
+        let db = TempDb {
+            name: format!("simpledb_{}_{}", prefix, std::process::id()),
+        };
+        // what a killed run of the same pid left behind
+        db.remove();
+        db
+    }
+
+    pub fn path(&self) -> String {
+        format!("sql_files/{}.bin", self.name)
+    }
+
+    fn remove(&self) {
+        let path = self.path();
+        let _ = std::fs::remove_file(crate::storage::wal::Wal::path_for(&path));
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        self.remove();
+    }
 }